@@ -5,6 +5,7 @@
 
 use std::cell::RefCell;
 use std::cmp::max;
+use std::cmp::min;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 
@@ -36,6 +37,10 @@ impl Chunk {
         self.data.len()
     }
 
+    fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        std::iter::successors(Some(self), |chunk| chunk.prev.as_deref())
+    }
+
     fn can_fit(&self, requested_size: usize) -> bool {
         self.index + requested_size <= self.data.len()
     }
@@ -48,25 +53,67 @@ impl Chunk {
     }
 }
 
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        // Drop the list of previous chunks iteratively, to avoid overflowing
+        // the stack when an arena has allocated a great many chunks.
+        let mut prev = self.prev.take();
+        while let Some(mut chunk) = prev {
+            prev = chunk.prev.take();
+        }
+    }
+}
+
 // The generation number is used solely to identify which arena a cached value
 // belongs to in `RcOc`.
 //
-// We use usize::max_value() / 2 here to avoid colliding with ocamlpool and
-// SlabAllocator generation numbers (ocamlpool starts at 0, and SlabAllocator
-// starts at usize::max_value() / 4). This generation trick isn't sound with the
-// use of multiple generation counters, but this mitigation should make it
-// extremely difficult to mix up values allocated with ocamlpool, Arena, and
-// SlabAllocator in practice (one would have to serialize the same value with
-// multiple Allocators, and only after increasing the generation of one by an
-// absurd amount).
+// We use usize::max_value() / 2 here to avoid colliding with ocamlpool,
+// SlabAllocator, and FixedBufferAllocator generation numbers (ocamlpool starts
+// at 0, SlabAllocator starts at usize::max_value() / 4, and
+// FixedBufferAllocator starts at usize::max_value() / 4 * 3). This generation
+// trick isn't sound with the use of multiple generation counters, but this
+// mitigation should make it extremely difficult to mix up values allocated
+// with different Allocators in practice (one would have to serialize the same
+// value with multiple Allocators, and only after increasing the generation of
+// one by an absurd amount).
 //
 // If we add more allocators, we might want to rethink this strategy.
 static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(usize::MAX / 2);
 
+/// Determines the capacity of each chunk an `Arena` allocates once its
+/// current chunk is full. Regardless of policy, a new chunk will always have
+/// room for at least twice the size of the allocation which required it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkGrowth {
+    /// Allocate chunks with the same capacity as the previous chunk.
+    #[default]
+    Constant,
+    /// Allocate chunks with double the capacity of the previous chunk, up to
+    /// the given maximum capacity (in bytes).
+    Doubling { max_capacity_in_bytes: usize },
+}
+
+impl ChunkGrowth {
+    fn next_capacity(self, prev_capacity: usize, requested_size: usize) -> usize {
+        let capacity = match self {
+            ChunkGrowth::Constant => prev_capacity,
+            ChunkGrowth::Doubling {
+                max_capacity_in_bytes,
+            } => {
+                let max_capacity = max_capacity_in_bytes / std::mem::size_of::<Value<'_>>();
+                max(prev_capacity, min(prev_capacity * 2, max_capacity))
+            }
+        };
+        max(requested_size * 2, capacity)
+    }
+}
+
 /// An [`Allocator`](trait.Allocator.html) which builds values in Rust-managed
-/// memory. The memory is freed when the Arena is dropped.
+/// memory. The memory is freed when the Arena is dropped (or reused after
+/// calling `Arena::reset`).
 pub struct Arena {
     generation: usize,
+    growth: ChunkGrowth,
     current_chunk: RefCell<Chunk>,
     cache: MemoizationCache,
 }
@@ -86,24 +133,74 @@ impl Arena {
 
     /// Create a new Arena with `capacity_in_bytes` preallocated.
     pub fn with_capacity(capacity_in_bytes: usize) -> Self {
+        Self::with_capacity_and_growth(capacity_in_bytes, ChunkGrowth::default())
+    }
+
+    /// Create a new Arena with `capacity_in_bytes` preallocated, which will
+    /// allocate additional chunks according to the given `ChunkGrowth` policy.
+    pub fn with_capacity_and_growth(capacity_in_bytes: usize, growth: ChunkGrowth) -> Self {
         let generation = NEXT_GENERATION.fetch_add(1, Ordering::SeqCst);
         let capacity_in_words = max(2, capacity_in_bytes / std::mem::size_of::<Value<'_>>());
         Self {
             generation,
+            growth,
             current_chunk: RefCell::new(Chunk::with_capacity(capacity_in_words)),
             cache: MemoizationCache::new(),
         }
     }
 
+    /// Free all values allocated in this Arena, so that its memory can be
+    /// reused. The largest chunk is retained and all others are freed.
+    ///
+    /// The Arena is assigned a new generation number, so that values cached
+    /// in `RcOc`s by previous conversions are not reused.
+    pub fn reset(&mut self) {
+        let current_chunk = self.current_chunk.get_mut();
+        let mut largest = std::mem::replace(current_chunk, Chunk::with_capacity(0));
+        let mut prev = largest.prev.take();
+        while let Some(mut chunk) = prev {
+            prev = chunk.prev.take();
+            if chunk.capacity() > largest.capacity() {
+                largest = *chunk;
+            }
+        }
+        // Restore the initial state of the chunk's memory, so that fields
+        // which are never written behave the same as in a fresh chunk.
+        largest.data[..largest.index].fill(Value::int(0));
+        largest.index = 0;
+        *current_chunk = largest;
+        self.generation = NEXT_GENERATION.fetch_add(1, Ordering::SeqCst);
+        self.cache = MemoizationCache::new();
+    }
+
+    /// The number of bytes allocated in this Arena (including block headers).
+    pub fn allocated_bytes(&self) -> usize {
+        let chunk = self.current_chunk.borrow();
+        let words: usize = chunk.chunks().map(|chunk| chunk.index).sum();
+        words * std::mem::size_of::<Value<'_>>()
+    }
+
+    /// The number of chunks of memory this Arena has allocated.
+    pub fn chunk_count(&self) -> usize {
+        self.current_chunk.borrow().chunks().count()
+    }
+
+    /// The total capacity (in bytes) of the chunks this Arena has allocated.
+    pub fn capacity(&self) -> usize {
+        let chunk = self.current_chunk.borrow();
+        let words: usize = chunk.chunks().map(Chunk::capacity).sum();
+        words * std::mem::size_of::<Value<'_>>()
+    }
+
     #[inline]
     #[allow(clippy::mut_from_ref)]
     fn alloc<'a>(&'a self, requested_size: usize) -> &'a mut [Value<'a>] {
         if !self.current_chunk.borrow().can_fit(requested_size) {
             let prev_chunk_capacity = self.current_chunk.borrow().capacity();
-            let prev_chunk = self.current_chunk.replace(Chunk::with_capacity(max(
-                requested_size * 2,
-                prev_chunk_capacity,
-            )));
+            let prev_chunk = self.current_chunk.replace(Chunk::with_capacity(
+                self.growth
+                    .next_capacity(prev_chunk_capacity, requested_size),
+            ));
             self.current_chunk.borrow_mut().prev = Some(Box::new(prev_chunk));
         }
         let mut chunk = self.current_chunk.borrow_mut();
@@ -131,6 +228,101 @@ impl Arena {
     pub fn add_root<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
         Allocator::add_root(self, value)
     }
+
+    #[inline(always)]
+    pub fn add_hashconsed<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
+        Allocator::add_hashconsed(self, value)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Arena {
+    /// Convert `items` to an OCaml list in parallel, using the rayon thread
+    /// pool. The result is the same value which `Arena::add` would produce
+    /// for `items` (in particular, values shared between items are not shared
+    /// in the result).
+    ///
+    /// The items are divided among worker threads, each of which converts its
+    /// share into an arena of its own. The chunks of those arenas are then
+    /// moved into this one (without copying the converted values), and the
+    /// cells of the list are allocated here.
+    pub fn add_list_par<T: ToOcamlRep + Sync>(&self, items: &[T]) -> Value<'_> {
+        let elements = self.convert_par(items);
+        let mut hd = Value::int(0);
+        for &element in elements.iter().rev() {
+            let mut block = self.block_with_size(2);
+            self.set_field(&mut block, 0, element);
+            self.set_field(&mut block, 1, hd);
+            hd = block.build();
+        }
+        hd
+    }
+
+    /// Convert `items` to an OCaml array in parallel, using the rayon thread
+    /// pool (as in `Arena::add_list_par`).
+    pub fn add_array_par<T: ToOcamlRep + Sync>(&self, items: &[T]) -> Value<'_> {
+        if items.is_empty() {
+            // `[||]` is an atom (a block of size zero), which can't be
+            // allocated in an arena.
+            return crate::ocaml_static!(array[]);
+        }
+        let elements = self.convert_par(items);
+        let mut block = self.block_with_size(elements.len());
+        for (i, &element) in elements.iter().enumerate() {
+            self.set_field(&mut block, i, element);
+        }
+        block.build()
+    }
+
+    /// Convert each of `items` in a worker-local arena, then take ownership of
+    /// the workers' chunks.
+    fn convert_par<T: ToOcamlRep + Sync>(&self, items: &[T]) -> Vec<Value<'_>> {
+        use rayon::prelude::*;
+
+        let chunk_capacity_in_bytes =
+            self.current_chunk.borrow().capacity() * std::mem::size_of::<Value<'_>>();
+        let growth = self.growth;
+        let batch_size = items
+            .len()
+            .div_ceil(rayon::current_num_threads() * 4)
+            .max(1);
+        let batches: Vec<(Arena, Vec<usize>)> = items
+            .par_chunks(batch_size)
+            .map(|batch| {
+                let arena = Arena::with_capacity_and_growth(chunk_capacity_in_bytes, growth);
+                let elements = batch.iter().map(|item| arena.add(item).to_bits()).collect();
+                (arena, elements)
+            })
+            .collect();
+        let mut elements = Vec::with_capacity(items.len());
+        for (arena, batch) in batches {
+            self.adopt(arena);
+            // SAFETY: The blocks allocated for these values are now owned by
+            // `self`.
+            elements.extend(
+                batch
+                    .into_iter()
+                    .map(|bits| unsafe { Value::from_bits(bits) }),
+            );
+        }
+        elements
+    }
+
+    /// Move all chunks of `other` into this arena. Values allocated in `other`
+    /// remain valid for as long as `self` (chunks are boxed, so moving them
+    /// doesn't move their contents).
+    fn adopt(&self, other: Arena) {
+        let mut adopted = Box::new(other.current_chunk.into_inner());
+        let mut tail = &mut *adopted;
+        while tail.prev.is_some() {
+            tail = tail.prev.as_deref_mut().unwrap();
+        }
+        // Insert the adopted chunks behind the current chunk, so that we can
+        // continue allocating in the current chunk.
+        let mut current_chunk = self.current_chunk.borrow_mut();
+        tail.prev = current_chunk.prev.take();
+        current_chunk.prev = Some(adopted);
+    }
 }
 
 impl Allocator for Arena {
@@ -163,9 +355,12 @@ impl Allocator for Arena {
         &'a self,
         ptr: usize,
         size: usize,
+        type_id: std::any::TypeId,
         f: impl FnOnce(&'a Self) -> Value<'a>,
     ) -> Value<'a> {
-        let bits = self.cache.memoized(ptr, size, || f(self).to_bits());
+        let bits = self
+            .cache
+            .memoized(ptr, size, type_id, || f(self).to_bits());
         // SAFETY: The only memoized values in the cache are those computed in
         // the closure on the previous line. Since f returns Value<'a>, any
         // cached bits must represent a valid Value<'a>,
@@ -183,6 +378,7 @@ mod tests {
     use std::time::Instant;
 
     use super::*;
+    use crate::block::Color;
 
     #[test]
     fn test_alloc_byte_string_with_len() {
@@ -213,6 +409,49 @@ mod tests {
         assert_eq!(block[2].as_int().unwrap(), 3);
     }
 
+    #[test]
+    fn test_headers_have_default_color() {
+        let arena = Arena::new();
+        let value = arena.add(&(1isize, "two"));
+        let block = value.as_block().unwrap();
+        assert_eq!(block.header().color(), Color::DEFAULT);
+        assert_eq!(
+            block[1].as_block().unwrap().header().color(),
+            Color::DEFAULT
+        );
+        if crate::OCAML5 {
+            assert_eq!(Color::DEFAULT, Color::NOT_MARKABLE);
+        } else {
+            assert_eq!(Color::DEFAULT, Color::White);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_add_par() {
+        let items: Vec<(String, Vec<isize>)> = (0..10_000)
+            .map(|i| (i.to_string(), (0..i % 7).collect()))
+            .collect();
+        let arena = Arena::with_capacity(1000);
+        let list = arena.add_list_par(&items);
+        let expected = arena.add(&items);
+        crate::assert_values_eq!(list, expected);
+        assert!(arena.chunk_count() > 1);
+
+        let array = arena.add_array_par(&items);
+        let fields = crate::iter::ArrayIter::new(array).unwrap();
+        assert_eq!(fields.len(), items.len());
+        for (field, element) in fields.zip(crate::iter::ListIter::new(expected)) {
+            crate::assert_values_eq!(field, element.unwrap());
+        }
+
+        assert_eq!(arena.add_list_par::<isize>(&[]), Value::int(0));
+        assert_eq!(
+            arena.add_array_par::<isize>(&[]).as_block().unwrap().size(),
+            0
+        );
+    }
+
     #[test]
     fn test_large_allocs() {
         let arena = Arena::with_capacity(1000);
@@ -229,6 +468,58 @@ mod tests {
         assert_eq!(four_thousand.size(), 4000);
     }
 
+    #[test]
+    fn test_reset() {
+        let mut arena = Arena::with_capacity(1000);
+        let generation = arena.generation();
+        for _ in 0..10 {
+            arena.block_with_size(100);
+        }
+        assert!(arena.chunk_count() > 1);
+        assert_eq!(arena.allocated_bytes(), 10 * 101 * 8);
+        arena.block_with_size(1000);
+
+        arena.reset();
+        assert_eq!(arena.chunk_count(), 1);
+        assert_eq!(arena.allocated_bytes(), 0);
+        // The largest chunk (allocated for the 1000-field block) is retained.
+        assert_eq!(arena.capacity(), 2002 * 8);
+        assert_ne!(arena.generation(), generation);
+
+        let block = arena.block_with_size(3).build().as_block().unwrap();
+        assert_eq!(block[0].as_int(), Some(0));
+        assert_eq!(arena.chunk_count(), 1);
+    }
+
+    #[test]
+    fn test_chunk_growth() {
+        let constant = Arena::with_capacity(800);
+        let doubling = Arena::with_capacity_and_growth(
+            800,
+            ChunkGrowth::Doubling {
+                max_capacity_in_bytes: 3200,
+            },
+        );
+        for _ in 0..16 {
+            constant.block_with_size(49);
+            doubling.block_with_size(49);
+        }
+        assert_eq!(constant.chunk_count(), 8);
+        assert_eq!(constant.capacity(), 8 * 800);
+        // Chunks of 800, 1600, 3200, and 3200 bytes
+        assert_eq!(doubling.chunk_count(), 4);
+        assert_eq!(doubling.capacity(), 800 + 1600 + 3200 + 3200);
+    }
+
+    #[test]
+    fn test_many_chunks() {
+        let arena = Arena::with_capacity(16);
+        for _ in 0..1_000_000 {
+            arena.block_with_size(1);
+        }
+        assert_eq!(arena.chunk_count(), 500_001);
+    }
+
     #[test]
     fn perf_test() {
         let arena = Arena::with_capacity(10_000);
@@ -0,0 +1,424 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Wrapper types which convert to and from OCaml arrays.
+//!
+//! Sequence types like `Vec<T>` and `[T]` are converted to OCaml lists. These
+//! wrappers instead convert to OCaml arrays: `OcamlArray<T>` and
+//! `OcamlArraySlice<T>` to a block with tag 0 and one field per element (`'a
+//! array`), and `FloatArray` and `FloatArraySlice` to a block with tag
+//! `DOUBLE_ARRAY_TAG` containing unboxed floats (`float array`).
+//!
+//! Since OCaml represents `float array` specially, `OcamlArray<f64>` does
+//! *not* produce a valid `float array`; use `FloatArray` instead.
+
+use std::fmt;
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+use bumpalo::Bump;
+
+use crate::Allocator;
+use crate::FromError;
+use crate::FromOcamlRep;
+use crate::FromOcamlRepIn;
+use crate::FromOcamlRepRef;
+use crate::ToOcamlRep;
+use crate::Value;
+use crate::block;
+use crate::from;
+use crate::iter;
+
+/// An owned sequence which converts to an OCaml array.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OcamlArray<T>(Vec<T>);
+
+/// A borrowed sequence which converts to an OCaml array (the slice
+/// counterpart of `OcamlArray`).
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OcamlArraySlice<T>([T]);
+
+/// An owned sequence of floats which converts to an OCaml `float array`.
+#[derive(Clone, Default, PartialEq, PartialOrd)]
+pub struct FloatArray(Vec<f64>);
+
+/// A borrowed sequence of floats which converts to an OCaml `float array` (the
+/// slice counterpart of `FloatArray`).
+#[repr(transparent)]
+#[derive(PartialEq, PartialOrd)]
+pub struct FloatArraySlice([f64]);
+
+impl<T> OcamlArray<T> {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> OcamlArraySlice<T> {
+    pub fn new(slice: &[T]) -> &Self {
+        // SAFETY: `OcamlArraySlice<T>` is `repr(transparent)` over `[T]`.
+        unsafe { &*(slice as *const [T] as *const Self) }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl FloatArray {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn into_vec(self) -> Vec<f64> {
+        self.0
+    }
+}
+
+impl FloatArraySlice {
+    pub fn new(slice: &[f64]) -> &Self {
+        // SAFETY: `FloatArraySlice` is `repr(transparent)` over `[f64]`.
+        unsafe { &*(slice as *const [f64] as *const Self) }
+    }
+
+    pub fn as_slice(&self) -> &[f64] {
+        &self.0
+    }
+}
+
+impl<T> Deref for OcamlArray<T> {
+    type Target = OcamlArraySlice<T>;
+    fn deref(&self) -> &Self::Target {
+        OcamlArraySlice::new(&self.0)
+    }
+}
+
+impl<T> DerefMut for OcamlArray<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let slice: &mut [T] = &mut self.0;
+        // SAFETY: `OcamlArraySlice<T>` is `repr(transparent)` over `[T]`.
+        unsafe { &mut *(slice as *mut [T] as *mut OcamlArraySlice<T>) }
+    }
+}
+
+impl<T> Deref for OcamlArraySlice<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for OcamlArraySlice<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.0
+    }
+}
+
+impl Deref for FloatArray {
+    type Target = FloatArraySlice;
+    fn deref(&self) -> &Self::Target {
+        FloatArraySlice::new(&self.0)
+    }
+}
+
+impl DerefMut for FloatArray {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let slice: &mut [f64] = &mut self.0;
+        // SAFETY: `FloatArraySlice` is `repr(transparent)` over `[f64]`.
+        unsafe { &mut *(slice as *mut [f64] as *mut FloatArraySlice) }
+    }
+}
+
+impl Deref for FloatArraySlice {
+    type Target = [f64];
+    fn deref(&self) -> &[f64] {
+        &self.0
+    }
+}
+
+impl DerefMut for FloatArraySlice {
+    fn deref_mut(&mut self) -> &mut [f64] {
+        &mut self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OcamlArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OcamlArraySlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Debug for FloatArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Debug for FloatArraySlice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T> From<Vec<T>> for OcamlArray<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Self(vec)
+    }
+}
+
+impl<T> From<OcamlArray<T>> for Vec<T> {
+    fn from(array: OcamlArray<T>) -> Self {
+        array.0
+    }
+}
+
+impl From<Vec<f64>> for FloatArray {
+    fn from(vec: Vec<f64>) -> Self {
+        Self(vec)
+    }
+}
+
+impl From<FloatArray> for Vec<f64> {
+    fn from(array: FloatArray) -> Self {
+        array.0
+    }
+}
+
+impl<T> FromIterator<T> for OcamlArray<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl FromIterator<f64> for FloatArray {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<T> IntoIterator for OcamlArray<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl IntoIterator for FloatArray {
+    type Item = f64;
+    type IntoIter = std::vec::IntoIter<f64>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// The empty array `[||]`, which is an atom (a block of size zero) shared by
+/// `'a array` and `float array`.
+fn empty_array<'a>() -> Value<'a> {
+    crate::ocaml_static!(array[])
+}
+
+impl<T: ToOcamlRep> ToOcamlRep for OcamlArraySlice<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        if self.0.is_empty() {
+            return empty_array();
+        }
+        let mut block = alloc.block_with_size(self.0.len());
+        for (i, elem) in self.0.iter().enumerate() {
+            alloc.set_field(&mut block, i, alloc.add(elem));
+        }
+        block.build()
+    }
+}
+
+impl<T: ToOcamlRep> ToOcamlRep for &'_ OcamlArraySlice<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.memoized(
+            self.0.as_ptr() as usize,
+            std::mem::size_of_val(*self),
+            crate::non_static_type_id::<OcamlArraySlice<T>>(),
+            |alloc| (**self).to_ocamlrep(alloc),
+        )
+    }
+}
+
+impl<T: ToOcamlRep> ToOcamlRep for OcamlArray<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.add(&**self)
+    }
+}
+
+impl<T: FromOcamlRep> FromOcamlRep for OcamlArray<T> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        iter::array_iter(value).collect()
+    }
+}
+
+impl<T: FromOcamlRep> FromOcamlRepIn<'_> for OcamlArray<T> {
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
+impl<'v, T: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for OcamlArray<T> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        iter::array_iter_ref(value).collect()
+    }
+}
+
+impl<'a, T: FromOcamlRepIn<'a>> FromOcamlRepIn<'a> for &'a OcamlArraySlice<T> {
+    fn from_ocamlrep_in(value: Value<'_>, alloc: &'a Bump) -> Result<Self, FromError> {
+        let fields = iter::ArrayIter::new(value)?;
+        let mut vec = bumpalo::collections::Vec::with_capacity_in(fields.len(), alloc);
+        for (idx, field) in fields.enumerate() {
+            vec.push(
+                T::from_ocamlrep_in(field, alloc)
+                    .map_err(|e| FromError::ErrorInField(idx, Box::new(e)))?,
+            );
+        }
+        Ok(OcamlArraySlice::new(vec.into_bump_slice()))
+    }
+}
+
+impl ToOcamlRep for FloatArraySlice {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        if self.0.is_empty() {
+            return empty_array();
+        }
+        let mut block = alloc.block_with_size_and_tag(self.0.len(), block::DOUBLE_ARRAY_TAG);
+        for (i, &elem) in self.0.iter().enumerate() {
+            alloc.set_field(&mut block, i, unsafe {
+                Value::from_bits(elem.to_bits() as usize)
+            });
+        }
+        block.build()
+    }
+}
+
+impl ToOcamlRep for &'_ FloatArraySlice {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.memoized(
+            self.0.as_ptr() as usize,
+            std::mem::size_of_val(*self),
+            crate::non_static_type_id::<FloatArraySlice>(),
+            |alloc| (**self).to_ocamlrep(alloc),
+        )
+    }
+}
+
+impl ToOcamlRep for FloatArray {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.add(&**self)
+    }
+}
+
+impl FromOcamlRep for FloatArray {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(Self(from::expect_float_array(value)?.to_vec()))
+    }
+}
+
+impl FromOcamlRepIn<'_> for FloatArray {
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
+impl FromOcamlRepRef<'_> for FloatArray {
+    fn from_ocamlrep_ref(value: Value<'_>) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
+/// Copies the floats into the arena with a single `memcpy`. Use
+/// `FromOcamlRepRef` to borrow them from the OCaml value instead.
+impl<'a> FromOcamlRepIn<'a> for &'a FloatArraySlice {
+    fn from_ocamlrep_in(value: Value<'_>, alloc: &'a Bump) -> Result<Self, FromError> {
+        let floats = from::expect_float_array(value)?;
+        Ok(FloatArraySlice::new(alloc.alloc_slice_copy(floats)))
+    }
+}
+
+impl<'v> FromOcamlRepRef<'v> for &'v FloatArraySlice {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        Ok(FloatArraySlice::new(from::expect_float_array(value)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Arena;
+
+    #[test]
+    fn array_round_trip() {
+        let arena = Arena::new();
+        let array: OcamlArray<(isize, String)> =
+            vec![(1, String::from("a")), (2, String::from("b"))].into();
+        let value = arena.add(&array);
+        let block = value.as_block().unwrap();
+        assert_eq!((block.tag(), block.size()), (0, 2));
+        assert_eq!(
+            <(isize, String)>::from_ocamlrep(block[1]),
+            Ok((2, String::from("b")))
+        );
+        assert_eq!(OcamlArray::from_ocamlrep(value).as_ref(), Ok(&array));
+
+        let bump = Bump::new();
+        let slice = <&OcamlArraySlice<(isize, &str)>>::from_ocamlrep_in(value, &bump).unwrap();
+        assert_eq!(slice.as_slice(), [(1, "a"), (2, "b")]);
+        let borrowed = <OcamlArray<(isize, &str)>>::from_ocamlrep_ref(value).unwrap();
+        assert_eq!(borrowed.as_slice(), slice.as_slice());
+    }
+
+    #[test]
+    fn empty_arrays_are_atoms() {
+        let arena = Arena::new();
+        let empty = OcamlArray::<isize>::new();
+        let value = arena.add(&empty);
+        let block = value.as_block().unwrap();
+        assert_eq!((block.tag(), block.size()), (0, 0));
+        assert_eq!(
+            OcamlArray::<isize>::from_ocamlrep(value),
+            Ok(OcamlArray::new())
+        );
+        assert_eq!(FloatArray::from_ocamlrep(value), Ok(FloatArray::new()));
+        assert_eq!(arena.add(&FloatArray::new()).as_block().unwrap().size(), 0);
+    }
+
+    #[test]
+    fn float_array_round_trip() {
+        let arena = Arena::new();
+        let array = FloatArray::from(vec![1.5, -2.0, 0.25]);
+        let value = arena.add(&array);
+        assert_eq!(value.as_double_array(), Some(&[1.5, -2.0, 0.25][..]));
+        assert_eq!(FloatArray::from_ocamlrep(value).as_ref(), Ok(&array));
+
+        let borrowed = <&FloatArraySlice>::from_ocamlrep_ref(value).unwrap();
+        assert_eq!(borrowed.as_ptr() as usize, value.to_bits());
+        let bump = Bump::new();
+        let copied = <&FloatArraySlice>::from_ocamlrep_in(value, &bump).unwrap();
+        assert_eq!(copied, borrowed);
+
+        assert_eq!(
+            FloatArray::from_ocamlrep(arena.add(&OcamlArray::from(vec![1.5f64]))),
+            Err(FromError::ExpectedBlockTag {
+                expected: block::DOUBLE_ARRAY_TAG,
+                actual: 0,
+            })
+        );
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Conversion of maps to and from OCaml association lists.
+//!
+//! Map types like `BTreeMap` and `IndexMap` are converted to OCaml `Map`s,
+//! which requires sorting their keys. `AssocList<K, V>` (and
+//! `iter_to_ocaml_assoc_list`, which converts any map without copying it)
+//! instead convert to a `(k * v) list` in iteration order, preserving the
+//! insertion order of an `IndexMap`. Converting an association list back to a
+//! map is a matter of collecting an `AssocList`, e.g.
+//! `AssocList::from_ocamlrep(value)?.into_iter().collect::<IndexMap<_, _>>()`.
+//!
+//! Sequences of pairs like `Vec<(K, V)>` and `&'a [(K, V)]` are already
+//! converted to and from association lists.
+
+use std::fmt;
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+use bumpalo::Bump;
+
+use crate::Allocator;
+use crate::FromError;
+use crate::FromOcamlRep;
+use crate::FromOcamlRepIn;
+use crate::FromOcamlRepRef;
+use crate::ToOcamlRep;
+use crate::Value;
+use crate::iter;
+
+/// An owned sequence of key-value pairs which converts to an OCaml association
+/// list, `(k * v) list`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AssocList<K, V>(Vec<(K, V)>);
+
+impl<K, V> AssocList<K, V> {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn into_vec(self) -> Vec<(K, V)> {
+        self.0
+    }
+}
+
+impl<K, V> Default for AssocList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Deref for AssocList<K, V> {
+    type Target = [(K, V)];
+    fn deref(&self) -> &[(K, V)] {
+        &self.0
+    }
+}
+
+impl<K, V> DerefMut for AssocList<K, V> {
+    fn deref_mut(&mut self) -> &mut [(K, V)] {
+        &mut self.0
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for AssocList<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<K, V> From<Vec<(K, V)>> for AssocList<K, V> {
+    fn from(vec: Vec<(K, V)>) -> Self {
+        Self(vec)
+    }
+}
+
+impl<K, V> From<AssocList<K, V>> for Vec<(K, V)> {
+    fn from(list: AssocList<K, V>) -> Self {
+        list.0
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for AssocList<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<K, V> IntoIterator for AssocList<K, V> {
+    type Item = (K, V);
+    type IntoIter = std::vec::IntoIter<(K, V)>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// A borrowed key-value pair, as yielded by the iterators of maps (`(&K, &V)`)
+/// and of sequences of pairs (`&(K, V)`).
+pub trait AssocPair<'a> {
+    type Key: ToOcamlRep + 'a;
+    type Value: ToOcamlRep + 'a;
+
+    fn into_pair(self) -> (&'a Self::Key, &'a Self::Value);
+}
+
+impl<'a, K: ToOcamlRep, V: ToOcamlRep> AssocPair<'a> for (&'a K, &'a V) {
+    type Key = K;
+    type Value = V;
+
+    fn into_pair(self) -> (&'a K, &'a V) {
+        self
+    }
+}
+
+impl<'a, K: ToOcamlRep, V: ToOcamlRep> AssocPair<'a> for &'a (K, V) {
+    type Key = K;
+    type Value = V;
+
+    fn into_pair(self) -> (&'a K, &'a V) {
+        (&self.0, &self.1)
+    }
+}
+
+/// Build an OCaml association list containing the key-value pairs emitted by
+/// the given iterator (e.g., `&map` for any map type), in iteration order.
+pub fn iter_to_ocaml_assoc_list<'a, A: Allocator, P: AssocPair<'a>>(
+    iter: impl IntoIterator<Item = P>,
+    alloc: &'a A,
+) -> Value<'a> {
+    // Convert the pairs in iteration order, then build the list from its tail.
+    let pairs: Vec<Value<'a>> = iter
+        .into_iter()
+        .map(|pair| {
+            let (key, value) = pair.into_pair();
+            let mut block = alloc.block_with_size(2);
+            alloc.set_field(&mut block, 0, alloc.add(key));
+            alloc.set_field(&mut block, 1, alloc.add(value));
+            block.build()
+        })
+        .collect();
+    let mut hd = Value::int(0);
+    for pair in pairs.into_iter().rev() {
+        let mut block = alloc.block_with_size(2);
+        alloc.set_field(&mut block, 0, pair);
+        alloc.set_field(&mut block, 1, hd);
+        hd = block.build();
+    }
+    hd
+}
+
+impl<K: ToOcamlRep, V: ToOcamlRep> ToOcamlRep for AssocList<K, V> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        iter_to_ocaml_assoc_list(&self.0, alloc)
+    }
+}
+
+impl<K: FromOcamlRep, V: FromOcamlRep> FromOcamlRep for AssocList<K, V> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        iter::list_iter(value).collect()
+    }
+}
+
+impl<'a, K: FromOcamlRepIn<'a>, V: FromOcamlRepIn<'a>> FromOcamlRepIn<'a> for AssocList<K, V> {
+    fn from_ocamlrep_in(value: Value<'_>, alloc: &'a Bump) -> Result<Self, FromError> {
+        iter::list_iter_in(value, alloc).collect()
+    }
+}
+
+impl<'v, K: FromOcamlRepRef<'v>, V: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for AssocList<K, V> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        iter::list_iter_ref(value).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use indexmap::IndexMap;
+
+    use super::*;
+    use crate::Arena;
+
+    #[test]
+    fn preserves_insertion_order() {
+        let arena = Arena::new();
+        let mut map = IndexMap::new();
+        map.insert(String::from("b"), 2isize);
+        map.insert(String::from("a"), 1);
+        map.insert(String::from("c"), 3);
+        let value = iter_to_ocaml_assoc_list(&map, &arena);
+        let pairs = <Vec<(String, isize)>>::from_ocamlrep(value).unwrap();
+        assert_eq!(pairs, [("b".into(), 2), ("a".into(), 1), ("c".into(), 3)]);
+
+        let list = AssocList::<String, isize>::from_ocamlrep(value).unwrap();
+        let round_trip: IndexMap<_, _> = list.into_iter().collect();
+        assert!(round_trip.iter().eq(map.iter()));
+    }
+
+    #[test]
+    fn same_representation_as_list_of_pairs() {
+        let arena = Arena::new();
+        let vec = vec![(1isize, "one"), (2, "two")];
+        let list = AssocList::from(vec.clone());
+        assert_eq!(
+            format!("{:?}", arena.add(&list)),
+            format!("{:?}", arena.add(&vec))
+        );
+        let btree_map: BTreeMap<_, _> = vec.iter().copied().collect();
+        assert_eq!(
+            format!("{:?}", iter_to_ocaml_assoc_list(&btree_map, &arena)),
+            format!("{:?}", arena.add(&vec))
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                iter_to_ocaml_assoc_list(&AssocList::<isize, isize>::new()[..], &arena)
+            ),
+            "0"
+        );
+    }
+
+    #[test]
+    fn convert_in_arena() {
+        let arena = Arena::new();
+        let list = AssocList::from(vec![(1isize, String::from("one"))]);
+        let value = arena.add(&list);
+        let bump = Bump::new();
+        let borrowed = AssocList::<isize, &str>::from_ocamlrep_in(value, &bump).unwrap();
+        assert_eq!(borrowed.into_vec(), [(1, "one")]);
+        let slice = <&[(isize, &str)]>::from_ocamlrep_in(value, &bump).unwrap();
+        assert_eq!(slice, [(1, "one")]);
+        let borrowed = AssocList::<isize, &str>::from_ocamlrep_ref(value).unwrap();
+        assert_eq!(borrowed.into_vec(), [(1, "one")]);
+    }
+}
@@ -30,6 +30,20 @@ pub const DOUBLE_TAG: u8 = 253;
 pub const DOUBLE_ARRAY_TAG: u8 = 254;
 pub const CUSTOM_TAG: u8 = 255;
 
+/// The leading fields of OCaml's `struct custom_operations` (see
+/// `ocamlrep_custom::CustomOperations`), for use by functions which inspect
+/// custom blocks without the OCaml runtime.
+#[repr(C)]
+pub(crate) struct CustomOperations {
+    pub identifier: *const std::ffi::c_char,
+    pub finalize: Option<extern "C" fn(usize)>,
+    pub compare: Option<extern "C" fn(usize, usize) -> std::ffi::c_int>,
+    pub hash: Option<extern "C" fn(usize) -> isize>,
+    pub serialize: Option<extern "C" fn(usize, *mut usize, *mut usize)>,
+    pub deserialize: Option<extern "C" fn(*mut std::ffi::c_void) -> usize>,
+    pub compare_ext: Option<extern "C" fn(usize, usize) -> std::ffi::c_int>,
+}
+
 /// A recently-allocated, not-yet-finalized Block.
 #[repr(transparent)]
 pub struct BlockBuilder<'a> {
@@ -114,6 +128,28 @@ impl<'a> Block<'a> {
         unsafe { std::slice::from_raw_parts(slice.as_ptr().cast(), slice.len()) }
     }
 
+    /// If this is a block with tag `CUSTOM_TAG`, return its custom operations
+    /// struct (stored in its first field).
+    pub(crate) fn custom_operations(self) -> Option<&'a CustomOperations> {
+        if self.tag() != CUSTOM_TAG {
+            return None;
+        }
+        // Safety: The first field of a custom block is a pointer to a
+        // statically allocated `struct custom_operations`.
+        unsafe { (self.0[1].0 as *const CustomOperations).as_ref() }
+    }
+
+    /// If this is a block with tag `CLOSURE_TAG`, return the index of the
+    /// first field of its environment (stored in its closure info field).
+    /// Fields before this index are code pointers, closure info fields, and
+    /// infix headers rather than values.
+    pub(crate) fn closure_start_env(self) -> Option<usize> {
+        if self.tag() != CLOSURE_TAG || self.size() < 2 {
+            return None;
+        }
+        Some((self.0[2].0 << 8) >> 9)
+    }
+
     /// Helper for `Value::clone_with_allocator`.
     pub(crate) fn clone_with<'b, A: Allocator>(
         self,
@@ -171,9 +207,15 @@ impl Debug for Block<'_> {
     }
 }
 
-// values from ocaml 'gc.h'
+/// The two color bits of a block header.
+///
+/// In OCaml 4, these are the tri-color marking colors from 'gc.h'. In OCaml 5,
+/// the GC instead rotates the meaning of the first three values between
+/// collection cycles, and reserves the fourth (`Color::Black`, i.e.
+/// `NOT_MARKABLE` in 'shared_heap.h') for blocks which the GC must never mark,
+/// such as statically allocated blocks and blocks outside the OCaml heap.
 #[repr(usize)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Color {
     White = crate::CAML_WHITE,
     Gray = crate::CAML_GRAY,
@@ -181,25 +223,71 @@ pub enum Color {
     Black = crate::CAML_BLACK,
 }
 
+impl Color {
+    /// The color OCaml 5 requires for blocks it must not mark.
+    pub const NOT_MARKABLE: Color = Color::Black;
+
+    /// The color given to blocks by `Header::new`, suitable for blocks
+    /// allocated outside the OCaml heap (e.g., by an `Arena`).
+    ///
+    /// OCaml 4 ignores the headers of blocks outside its heap, so this is
+    /// `Color::White`, as in a freshly allocated block. OCaml 5 has no page
+    /// table with which to tell such blocks apart, so it would attempt to mark
+    /// them (and their fields) unless they are `NOT_MARKABLE`.
+    #[cfg(not(ocaml5))]
+    pub const DEFAULT: Color = Color::White;
+    #[cfg(ocaml5)]
+    pub const DEFAULT: Color = Color::NOT_MARKABLE;
+}
+
 #[repr(transparent)]
 #[derive(Clone, Copy)]
 pub struct Header(usize);
 
 impl Header {
+    /// Create a header with the color `Color::DEFAULT` for the OCaml version
+    /// ocamlrep was built for.
     #[inline(always)]
     pub const fn new(size: usize, tag: u8) -> Self {
-        Self::with_color(size, tag, Color::White)
+        Self::with_color(size, tag, Color::DEFAULT)
     }
 
+    /// Create a header with the given color, and with its reserved bits (if
+    /// the OCaml runtime ocamlrep was built for has any; see
+    /// `HEADER_RESERVED_BITS`) clear. `size` must not exceed `MAX_WOSIZE`.
+    ///
+    /// The color bits are in the same place in OCaml 4 and OCaml 5, but their
+    /// meaning differs (see `Color`). Use `Header::new` for blocks which will
+    /// be passed to OCaml without being copied onto its heap.
     #[inline(always)]
     pub const fn with_color(size: usize, tag: u8, color: Color) -> Self {
+        debug_assert!(size <= crate::MAX_WOSIZE);
         let bits = size << 10 | (color as usize) | (tag as usize);
         Header(bits)
     }
 
+    /// Return this header with its reserved bits replaced by `reserved`, which
+    /// must fit in `HEADER_RESERVED_BITS` bits.
+    #[inline(always)]
+    pub const fn with_reserved(self, reserved: usize) -> Self {
+        if crate::HEADER_RESERVED_BITS == 0 {
+            assert!(reserved == 0, "no header bits are reserved");
+            return self;
+        }
+        let shift = usize::BITS - crate::HEADER_RESERVED_BITS;
+        assert!(reserved >> crate::HEADER_RESERVED_BITS == 0);
+        Header(self.0 & (usize::MAX >> crate::HEADER_RESERVED_BITS) | reserved << shift)
+    }
+
+    /// Return this header with its color bits replaced by the given color.
+    #[inline(always)]
+    pub const fn recolor(self, color: Color) -> Self {
+        Header(self.0 & !(Color::Black as usize) | color as usize)
+    }
+
     #[inline(always)]
     pub const fn size(self) -> usize {
-        self.0 >> 10
+        (self.0 >> 10) & crate::MAX_WOSIZE
     }
 
     #[inline(always)]
@@ -207,6 +295,19 @@ impl Header {
         self.0 as u8
     }
 
+    /// The reserved bits of this header (always 0 if `HEADER_RESERVED_BITS`
+    /// is 0).
+    #[inline(always)]
+    pub const fn reserved(self) -> usize {
+        if crate::HEADER_RESERVED_BITS == 0 {
+            0
+        } else {
+            self.0 >> (usize::BITS - crate::HEADER_RESERVED_BITS)
+        }
+    }
+
+    /// The color bits of this header. As with `with_color`, their meaning
+    /// depends on the OCaml version (see `Color`).
     #[inline(always)]
     pub const fn color(self) -> Color {
         match self.0 & Color::Black as usize {
@@ -270,3 +371,37 @@ impl<'a> BlockBytes<'a> {
         unsafe { Value::from_bits(block as usize) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_fields() {
+        let header = Header::with_color(crate::MAX_WOSIZE, STRING_TAG, Color::Gray);
+        assert_eq!(header.size(), crate::MAX_WOSIZE);
+        assert_eq!(header.tag(), STRING_TAG);
+        assert_eq!(header.color(), Color::Gray);
+        assert_eq!(header.reserved(), 0);
+        let header = header.recolor(Color::DEFAULT);
+        assert_eq!(header.size(), crate::MAX_WOSIZE);
+        assert_eq!(header.color(), Color::DEFAULT);
+    }
+
+    #[test]
+    fn reserved_bits() {
+        let header = Header::new(3, 0).with_reserved(0);
+        assert_eq!((header.size(), header.reserved()), (3, 0));
+        if crate::HEADER_RESERVED_BITS != 0 {
+            let reserved = (1 << crate::HEADER_RESERVED_BITS) - 1;
+            let header = header.with_reserved(reserved).recolor(Color::Blue);
+            assert_eq!(header.size(), 3);
+            assert_eq!(header.tag(), 0);
+            assert_eq!(header.reserved(), reserved);
+            assert_eq!(
+                header.with_reserved(0).to_bits(),
+                Header::with_color(3, 0, Color::Blue).to_bits()
+            );
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+// OCaml 5 changed the meaning of the color bits in block headers (see
+// `ocamlrep::Color`), and either version may be configured to reserve the high
+// bits of the header (see `ocamlrep::HEADER_RESERVED_BITS`), so we need to know
+// which OCaml runtime the values we build are destined for. Like
+// 'ocamlrep_marshal/ocaml_version.c', we ask the prevailing OCaml toolchain
+// (assuming an opam environment, e.g. `eval "$(opam env --switch=default
+// --set-switch)"`). The version and reserved bits may also be given explicitly
+// with `OCAMLREP_OCAML_VERSION` and `OCAMLREP_HEADER_RESERVED_BITS` (e.g.
+// `OCAMLREP_OCAML_VERSION=5.1.1`).
+//
+// If no OCaml toolchain can be found, we assume OCaml 4.x with no reserved
+// bits, which is the layout ocamlrep has always produced.
+
+/// Return the output of the first OCaml compiler found on the PATH when run
+/// with the given argument.
+fn ocaml_compiler_output(arg: &str) -> Option<String> {
+    ["ocamlopt.opt", "ocamlopt", "ocamlc"]
+        .into_iter()
+        .find_map(|compiler| {
+            let output = std::process::Command::new(compiler)
+                .arg(arg)
+                .output()
+                .ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            String::from_utf8(output.stdout).ok()
+        })
+}
+
+fn ocaml_version() -> Option<String> {
+    if let Ok(version) = std::env::var("OCAMLREP_OCAML_VERSION") {
+        return Some(version);
+    }
+    ocaml_compiler_output("-version")
+}
+
+/// The number of reserved header bits, as reported by `ocamlc -config` (as
+/// `reserved_header_bits` in OCaml 5, or `profinfo_width` in OCaml 4, which
+/// reserved them for profiling information).
+fn header_reserved_bits() -> u32 {
+    if let Ok(bits) = std::env::var("OCAMLREP_HEADER_RESERVED_BITS") {
+        return bits
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("Could not parse OCAMLREP_HEADER_RESERVED_BITS={bits:?}"));
+    }
+    let Some(config) = ocaml_compiler_output("-config") else {
+        return 0;
+    };
+    config
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            match name.trim() {
+                "reserved_header_bits" | "profinfo_width" => value.trim().parse().ok(),
+                _ => None,
+            }
+        })
+        .unwrap_or(0)
+}
+
+/// Parse the major version from a version string like "5.1.1" or
+/// "4.14.1+options".
+fn major_version(version: &str) -> Option<u32> {
+    version.trim().split('.').next()?.parse().ok()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=OCAMLREP_OCAML_VERSION");
+    println!("cargo:rerun-if-env-changed=OCAMLREP_HEADER_RESERVED_BITS");
+    // Switching opam switches changes which compiler we find on the PATH.
+    println!("cargo:rerun-if-env-changed=OPAM_SWITCH_PREFIX");
+    println!("cargo:rustc-check-cfg=cfg(ocaml5)");
+
+    if let Some(version) = ocaml_version() {
+        let major = major_version(&version).unwrap_or_else(|| {
+            panic!(
+                "Could not parse OCaml version {version:?} (set OCAMLREP_OCAML_VERSION to override)"
+            )
+        });
+        if major >= 5 {
+            println!("cargo:rustc-cfg=ocaml5");
+        }
+    }
+    println!(
+        "cargo:rustc-env=OCAMLREP_HEADER_RESERVED_BITS={}",
+        header_reserved_bits()
+    );
+}
@@ -6,16 +6,51 @@
 //! Provides `MemoizationCache`, a simple cache designed to aid implementation
 //! of the `Allocator` trait.
 
+use std::any::TypeId;
 use std::cell::RefCell;
+use std::marker::PhantomData;
 
 type HashMap<K, V> = rustc_hash::FxHashMap<K, V>;
 
+/// (address, size_in_bytes, type_id)
+type Key = (usize, usize, TypeId);
+
+/// Return the `TypeId` of `T`, which need not be `'static`. Lifetimes are
+/// erased, so (for instance) `&'a str` and `&'static str` have the same ID.
+///
+/// Used to distinguish differently-typed views of the same memory in calls to
+/// `Allocator::memoized`.
+pub fn non_static_type_id<T: ?Sized>() -> TypeId {
+    trait NonStaticAny {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+    impl<T: ?Sized> NonStaticAny for PhantomData<T> {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<T>()
+        }
+    }
+    let phantom = PhantomData::<T>;
+    // SAFETY: Lifetimes are erased before code generation, so `TypeId::of` is
+    // unaffected by extending them to 'static. `PhantomData` holds no data,
+    // so the reference cannot be used to access anything beyond its lifetime.
+    let phantom = unsafe {
+        std::mem::transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(&phantom)
+    };
+    phantom.type_id()
+}
+
 /// A simple scoped cache for memoizing conversions from one pointer-sized value
 /// to another. Useful for memoizing conversions between OCaml values and Rust
 /// references.
 pub struct MemoizationCache {
-    /// Maps from input (address,size_in_bytes) -> output
-    cache: RefCell<Option<HashMap<(usize, usize), usize>>>,
+    /// One layer per active invocation of `with_cache`, innermost last. Each
+    /// maps from input (address,size_in_bytes,type_id) -> output.
+    layers: RefCell<Vec<HashMap<Key, usize>>>,
 }
 
 impl Default for MemoizationCache {
@@ -29,58 +64,129 @@ impl MemoizationCache {
     #[inline(always)]
     pub fn new() -> Self {
         Self {
-            cache: RefCell::new(None),
+            layers: RefCell::new(Vec::new()),
         }
     }
 
+    /// Run `f` with an active cache, in which `memoized` will cache its
+    /// results. The results are discarded when this invocation of
+    /// `with_cache` returns.
+    ///
+    /// Invocations of `with_cache` may be nested. A nested invocation reuses
+    /// results memoized by the enclosing invocations (whose inputs remain
+    /// borrowed until they return), but results memoized in the nested scope
+    /// are discarded when it returns, since its inputs may not outlive it.
     #[inline(always)]
     pub fn with_cache<T>(&self, f: impl FnOnce() -> T) -> T {
-        // The `replace` below should not panic because the only borrows of
-        // `self.cache` are in this function and in `memoized`. In both
-        // functions, we do not hold a `Ref` or `RefMut` while calling into code
-        // which might attempt to re-enter `memoized` or `with_cache`.
-        let prev_value = self.cache.replace(Some(Default::default()));
-        if prev_value.is_some() {
-            panic!(
-                "Attempted to re-enter MemoizationCache::with_cache \
-                (probably via ocamlrep::Allocator::add_root, which is not re-entrant)"
-            );
+        // The `borrow_mut` calls below should not panic because the only
+        // borrows of `self.layers` are in this function, `retain`, and
+        // `memoized`. None of them hold a `Ref` or `RefMut` while calling into
+        // code which might attempt to re-enter `memoized` or `with_cache`.
+        self.layers.borrow_mut().push(Default::default());
+        // Pop the layer even if `f` panics, so that a later conversion
+        // doesn't use results memoized during an aborted one.
+        struct PopOnDrop<'a>(&'a MemoizationCache);
+        impl Drop for PopOnDrop<'_> {
+            fn drop(&mut self) {
+                self.0.layers.borrow_mut().pop();
+            }
+        }
+        let _guard = PopOnDrop(self);
+        f()
+    }
+
+    /// Remove the cached outputs for which `f` returns false (e.g., because
+    /// they point into memory which an allocator is about to reuse).
+    pub fn retain(&self, mut f: impl FnMut(usize) -> bool) {
+        for layer in self.layers.borrow_mut().iter_mut() {
+            layer.retain(|_, &mut output| f(output));
         }
-        let result = f();
-        // As above, this `replace` should not panic.
-        self.cache.replace(None);
-        result
     }
 
+    /// Return the output cached for the given input, or compute, cache, and
+    /// return it. Inputs are identified by address, size, and type (e.g., from
+    /// `non_static_type_id`), so that differently-typed views of the same
+    /// memory do not share an output.
     #[inline(always)]
-    pub fn memoized(&self, input: usize, size_in_bytes: usize, f: impl FnOnce() -> usize) -> usize {
+    pub fn memoized(
+        &self,
+        input: usize,
+        size_in_bytes: usize,
+        type_id: TypeId,
+        f: impl FnOnce() -> usize,
+    ) -> usize {
         if size_in_bytes == 0 {
             return f();
         }
-        let memoized_output = match (self.cache.borrow().as_ref())
-            .map(|cache| cache.get(&(input, size_in_bytes)).copied())
-        {
-            None => return f(),
-            Some(output) => output,
+        let key = (input, size_in_bytes, type_id);
+        let memoized_output = {
+            let layers = self.layers.borrow();
+            if layers.is_empty() {
+                drop(layers);
+                return f();
+            }
+            layers
+                .iter()
+                .rev()
+                .find_map(|layer| layer.get(&key).copied())
         };
         match memoized_output {
             Some(output) => output,
             None => {
                 let output = f();
-                // The `borrow_mut` below should not panic because the only
-                // borrows of `self.cache` are in this function and in
-                // `with_cache`. In this function, we do not hold a `Ref` or
-                // `RefMut` while calling into `f` (or any other function which
-                // might attempt to re-enter this function or `with_cache`).
-                let mut cache = self.cache.borrow_mut();
-                // The `unwrap` below should not panic. We know `self.cache` was
-                // not None upon entering this function because we looked up
-                // `memoized_output`. The only function which can replace the
-                // cache with None is `with_cache`, which would have panicked if
-                // `f` attempted to re-enter it.
-                (cache.as_mut().unwrap()).insert((input, size_in_bytes), output);
+                // The `borrow_mut` below should not panic because we do not
+                // hold a `Ref` or `RefMut` of `self.layers` while calling into
+                // `f` (or any other function which might attempt to re-enter
+                // this function or `with_cache`).
+                let mut layers = self.layers.borrow_mut();
+                // The `unwrap` below should not panic. We know there was a
+                // layer upon entering this function, and any invocations of
+                // `with_cache` within `f` popped the layers they pushed before
+                // returning, so the innermost layer is the one we searched.
+                layers.last_mut().unwrap().insert(key, output);
                 output
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_static_type_ids() {
+        fn id_of<'a>(_: &'a str) -> TypeId {
+            non_static_type_id::<&'a str>()
+        }
+        let s = String::from("a");
+        assert_eq!(id_of(&s), TypeId::of::<&'static str>());
+        assert_ne!(
+            non_static_type_id::<(u32, u32)>(),
+            non_static_type_id::<u64>()
+        );
+        assert_ne!(non_static_type_id::<[u8]>(), non_static_type_id::<str>());
+    }
+
+    #[test]
+    fn nested_scopes() {
+        let cache = MemoizationCache::new();
+        let id = TypeId::of::<u8>();
+        let memoize = |input, output| cache.memoized(input, 1, id, || output);
+        assert_eq!(memoize(1, 10), 10);
+        cache.with_cache(|| {
+            assert_eq!(memoize(1, 10), 10);
+            assert_eq!(memoize(1, 11), 10);
+            cache.with_cache(|| {
+                // Results from the enclosing scope are reused...
+                assert_eq!(memoize(1, 12), 10);
+                assert_eq!(memoize(2, 20), 20);
+                assert_eq!(memoize(2, 21), 20);
+            });
+            // ...but results from the nested scope are discarded.
+            assert_eq!(memoize(2, 22), 22);
+            assert_eq!(memoize(1, 13), 10);
+        });
+        assert_eq!(memoize(1, 14), 14);
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A port of OCaml's polymorphic comparison (`compare_val` in 'compare.c'),
+//! which backs `Stdlib.compare` and `Stdlib.(=)`.
+//!
+//! These functions can be used to check that the Rust `Ord` implementation for
+//! a type agrees with the order OCaml will use for its representation (e.g.,
+//! for keys converted with `sorted_iter_to_ocaml_map`, which must be sorted
+//! according to OCaml's `compare` for `Map.Make(...)` lookups to work), and to
+//! compare values which are not on the OCaml heap.
+
+use std::cmp::Ordering;
+use std::ffi::CStr;
+
+use crate::Value;
+use crate::block;
+
+/// Compare two values with the semantics of OCaml's `Stdlib.compare`.
+///
+/// Integers are ordered before blocks, blocks with different tags are ordered
+/// by tag, strings are compared lexicographically, and `nan` is considered
+/// equal to itself and less than any other float.
+///
+/// Custom blocks are compared using the `compare` (or `compare_ext`, when
+/// compared with an integer) function in their custom operations struct.
+///
+/// # Panics
+///
+/// Panics when encountering functional values, abstract values, or custom
+/// blocks without a `compare` function (where OCaml would raise
+/// `Invalid_argument`).
+pub fn compare_values(a: Value<'_>, b: Value<'_>) -> Ordering {
+    // A total comparison never reports that its inputs are unordered.
+    compare_val(a, b, true).unwrap()
+}
+
+/// Compare two values for structural equality with the semantics of OCaml's
+/// `Stdlib.(=)`.
+///
+/// Unlike `compare_values`, `nan` is not considered equal to itself.
+///
+/// # Panics
+///
+/// Panics under the same conditions as `compare_values`.
+pub fn equal_values(a: Value<'_>, b: Value<'_>) -> bool {
+    compare_val(a, b, false) == Some(Ordering::Equal)
+}
+
+/// Returns `None` if `total` is false and the values are unordered (i.e., a
+/// `nan` was encountered).
+fn compare_val<'a>(mut v1: Value<'a>, mut v2: Value<'a>, total: bool) -> Option<Ordering> {
+    // Fields which remain to be compared in blocks we have already begun
+    // comparing (we use an explicit stack rather than recursing, so that long
+    // lists can't overflow the Rust stack).
+    let mut stack: Vec<(&'a [Value<'a>], &'a [Value<'a>])> = vec![];
+    'compare: loop {
+        'item: {
+            if v1 == v2 && total {
+                break 'item;
+            }
+            let (b1, b2) = match (v1.as_block(), v2.as_block()) {
+                (None, None) => {
+                    if v1 == v2 {
+                        break 'item;
+                    }
+                    return Some(v1.as_int().unwrap().cmp(&v2.as_int().unwrap()));
+                }
+                (None, Some(b2)) => {
+                    match b2.tag() {
+                        block::FORWARD_TAG => {
+                            v2 = b2[0];
+                            continue 'compare;
+                        }
+                        block::CUSTOM_TAG => {
+                            let ops = b2.custom_operations().unwrap();
+                            if let Some(compare) = ops.compare_ext {
+                                match compare(v1.to_bits(), v2.to_bits()).cmp(&0) {
+                                    Ordering::Equal => break 'item,
+                                    ord => return Some(ord),
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    return Some(Ordering::Less);
+                }
+                (Some(b1), None) => {
+                    match b1.tag() {
+                        block::FORWARD_TAG => {
+                            v1 = b1[0];
+                            continue 'compare;
+                        }
+                        block::CUSTOM_TAG => {
+                            let ops = b1.custom_operations().unwrap();
+                            if let Some(compare) = ops.compare_ext {
+                                match compare(v1.to_bits(), v2.to_bits()).cmp(&0) {
+                                    Ordering::Equal => break 'item,
+                                    ord => return Some(ord),
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    return Some(Ordering::Greater);
+                }
+                (Some(b1), Some(b2)) => (b1, b2),
+            };
+
+            let mut t1 = b1.tag();
+            let mut t2 = b2.tag();
+            if t1 != t2 {
+                if t1 == block::FORWARD_TAG {
+                    v1 = b1[0];
+                    continue 'compare;
+                }
+                if t2 == block::FORWARD_TAG {
+                    v2 = b2[0];
+                    continue 'compare;
+                }
+                if t1 == block::INFIX_TAG {
+                    t1 = block::CLOSURE_TAG;
+                }
+                if t2 == block::INFIX_TAG {
+                    t2 = block::CLOSURE_TAG;
+                }
+                if t1 != t2 {
+                    return Some(t1.cmp(&t2));
+                }
+            }
+
+            match t1 {
+                block::FORWARD_TAG => {
+                    v1 = b1[0];
+                    v2 = b2[0];
+                    continue 'compare;
+                }
+                block::STRING_TAG => {
+                    let s1 = v1.as_byte_string().unwrap();
+                    let s2 = v2.as_byte_string().unwrap();
+                    match s1.cmp(s2) {
+                        Ordering::Equal => {}
+                        ord => return Some(ord),
+                    }
+                }
+                block::DOUBLE_TAG => {
+                    let d1 = v1.as_float().unwrap();
+                    let d2 = v2.as_float().unwrap();
+                    match compare_floats(d1, d2, total) {
+                        Some(Ordering::Equal) => {}
+                        res => return res,
+                    }
+                }
+                block::DOUBLE_ARRAY_TAG => {
+                    let a1 = v1.as_double_array().unwrap();
+                    let a2 = v2.as_double_array().unwrap();
+                    if a1.len() != a2.len() {
+                        return Some(a1.len().cmp(&a2.len()));
+                    }
+                    for (&d1, &d2) in a1.iter().zip(a2) {
+                        match compare_floats(d1, d2, total) {
+                            Some(Ordering::Equal) => {}
+                            res => return res,
+                        }
+                    }
+                }
+                block::ABSTRACT_TAG => panic!("compare: abstract value"),
+                block::CLOSURE_TAG | block::INFIX_TAG => panic!("compare: functional value"),
+                block::CONT_TAG => panic!("compare: continuation value"),
+                block::OBJECT_TAG => {
+                    let oid1 = b1[1].as_int().unwrap();
+                    let oid2 = b2[1].as_int().unwrap();
+                    if oid1 != oid2 {
+                        return Some(oid1.cmp(&oid2));
+                    }
+                }
+                block::CUSTOM_TAG => {
+                    let ops1 = b1.custom_operations().unwrap();
+                    let ops2 = b2.custom_operations().unwrap();
+                    // Hardening against comparisons between different types.
+                    if ops1.compare.map(|f| f as usize) != ops2.compare.map(|f| f as usize) {
+                        // Safety: custom operations structs must contain a
+                        // valid null-terminated identifier.
+                        let (id1, id2) = unsafe {
+                            (
+                                CStr::from_ptr(ops1.identifier),
+                                CStr::from_ptr(ops2.identifier),
+                            )
+                        };
+                        return Some(if id1 < id2 {
+                            Ordering::Less
+                        } else {
+                            Ordering::Greater
+                        });
+                    }
+                    let compare = match ops1.compare {
+                        Some(compare) => compare,
+                        None => panic!("compare: abstract value"),
+                    };
+                    match compare(v1.to_bits(), v2.to_bits()).cmp(&0) {
+                        Ordering::Equal => {}
+                        ord => return Some(ord),
+                    }
+                }
+                _ => {
+                    let f1 = b1.as_values().unwrap();
+                    let f2 = b2.as_values().unwrap();
+                    // Compare sizes first for speed.
+                    if f1.len() != f2.len() {
+                        return Some(f1.len().cmp(&f2.len()));
+                    }
+                    if f1.is_empty() {
+                        break 'item;
+                    }
+                    // Remember that we still have to compare fields 1..size,
+                    // and continue comparison with the first field.
+                    if f1.len() > 1 {
+                        stack.push((&f1[1..], &f2[1..]));
+                    }
+                    v1 = f1[0];
+                    v2 = f2[0];
+                    continue 'compare;
+                }
+            }
+        }
+        // Pop one more item to compare, if any.
+        match stack.last_mut() {
+            None => return Some(Ordering::Equal),
+            Some((f1, f2)) => {
+                v1 = f1[0];
+                v2 = f2[0];
+                *f1 = &f1[1..];
+                *f2 = &f2[1..];
+                if f1.is_empty() {
+                    stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Returns `Some(Ordering::Equal)` if comparison should continue, or `None` if
+/// `total` is false and one of the floats is `nan`.
+fn compare_floats(d1: f64, d2: f64, total: bool) -> Option<Ordering> {
+    if d1 < d2 {
+        return Some(Ordering::Less);
+    }
+    if d1 > d2 {
+        return Some(Ordering::Greater);
+    }
+    if d1 != d2 {
+        if !total {
+            return None;
+        }
+        // One or both of d1 and d2 is NaN. Order according to the convention
+        // NaN = NaN and NaN < f for all other floats f.
+        if !d1.is_nan() {
+            return Some(Ordering::Greater);
+        }
+        if !d2.is_nan() {
+            return Some(Ordering::Less);
+        }
+    }
+    Some(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::Allocator;
+    use crate::Arena;
+
+    #[test]
+    fn ints_before_blocks() {
+        let arena = Arena::new();
+        assert_eq!(
+            compare_values(Value::int(1000), arena.add(&Some(0))),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_values(arena.add(&Some(0)), Value::int(-1000)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_values(Value::int(-1), Value::int(1)),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn tags() {
+        let arena = Arena::new();
+        let ok = Ok::<isize, isize>(2);
+        let err = Err::<isize, isize>(1);
+        assert_eq!(
+            compare_values(arena.add(&ok), arena.add(&err)),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let arena = Arena::new();
+        let cmp = |a: &str, b: &str| compare_values(arena.add(a), arena.add(b));
+        assert_eq!(cmp("a", "ab"), Ordering::Less);
+        assert_eq!(cmp("ab", "b"), Ordering::Less);
+        assert_eq!(cmp("abc", "abc"), Ordering::Equal);
+        assert_eq!(cmp("abcdefghi", "abcdefgh"), Ordering::Greater);
+    }
+
+    #[test]
+    fn nan() {
+        let arena = Arena::new();
+        let nan = arena.add(&f64::NAN);
+        let one = arena.add(&1.0f64);
+        assert_eq!(compare_values(nan, nan), Ordering::Equal);
+        assert_eq!(compare_values(nan, arena.add(&f64::NAN)), Ordering::Equal);
+        assert_eq!(compare_values(nan, one), Ordering::Less);
+        assert_eq!(compare_values(one, nan), Ordering::Greater);
+        assert!(!equal_values(nan, nan));
+        assert!(!equal_values(nan, one));
+        assert!(equal_values(one, arena.add(&1.0f64)));
+    }
+
+    #[test]
+    fn double_arrays() {
+        let arena = Arena::new();
+        let float_array = |floats: &[f64]| {
+            let mut block = arena.block_with_size_and_tag(floats.len(), block::DOUBLE_ARRAY_TAG);
+            for (i, f) in floats.iter().enumerate() {
+                arena.set_field(&mut block, i, unsafe {
+                    Value::from_bits(f.to_bits() as usize)
+                });
+            }
+            block.build()
+        };
+        let a = float_array(&[1.0, f64::NAN]);
+        let b = float_array(&[1.0, 2.0]);
+        let c = float_array(&[1.0]);
+        assert_eq!(compare_values(a, b), Ordering::Less);
+        assert_eq!(compare_values(c, a), Ordering::Less);
+        assert_eq!(
+            compare_values(a, float_array(&[1.0, f64::NAN])),
+            Ordering::Equal
+        );
+        assert!(!equal_values(a, float_array(&[1.0, f64::NAN])));
+        assert!(equal_values(b, float_array(&[1.0, 2.0])));
+    }
+
+    #[test]
+    fn forward_blocks() {
+        let arena = Arena::new();
+        let mut fwd = arena.block_with_size_and_tag(1, block::FORWARD_TAG);
+        arena.set_field(&mut fwd, 0, Value::int(5));
+        let fwd = fwd.build();
+        assert_eq!(compare_values(fwd, Value::int(5)), Ordering::Equal);
+        assert_eq!(compare_values(Value::int(6), fwd), Ordering::Greater);
+    }
+
+    #[test]
+    fn long_lists() {
+        let arena = Arena::new();
+        let a: Vec<isize> = (0..100_000).collect();
+        let mut b = a.clone();
+        assert!(equal_values(arena.add(&a), arena.add(&b)));
+        *b.last_mut().unwrap() += 1;
+        assert_eq!(compare_values(arena.add(&a), arena.add(&b)), Ordering::Less);
+    }
+
+    #[test]
+    fn agrees_with_rust_ord() {
+        let arena = Arena::new();
+        let keys: BTreeSet<(Option<isize>, String)> = [
+            (None, "b"),
+            (Some(-5), "a"),
+            (Some(3), ""),
+            (Some(3), "a"),
+            (Some(3), "ab"),
+            (None, "abc"),
+            (Some(100), "z"),
+        ]
+        .into_iter()
+        .map(|(i, s)| (i, String::from(s)))
+        .collect();
+        let keys: Vec<_> = keys.iter().collect();
+        for pair in keys.windows(2) {
+            assert_eq!(
+                compare_values(arena.add(pair[0]), arena.add(pair[1])),
+                Ordering::Less,
+                "{:?} < {:?}",
+                pair[0],
+                pair[1],
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "compare: functional value")]
+    fn closures() {
+        let arena = Arena::new();
+        let f = arena.block_with_size_and_tag(2, block::CLOSURE_TAG).build();
+        let g = arena.block_with_size_and_tag(2, block::CLOSURE_TAG).build();
+        compare_values(f, g);
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Structural comparison of OCaml values which reports where (and how) two
+//! values differ, for use in tests (see `assert_values_eq!`).
+
+use std::collections::HashSet;
+use std::fmt;
+
+use bstr::BString;
+
+use crate::Value;
+use crate::block;
+
+/// One step in the path from the root of a value to one of its subvalues.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// The field of a block with the given index.
+    Field(usize),
+    /// The element of a list or float array with the given index.
+    Element(usize),
+}
+
+/// A way in which two values at the same path differ.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mismatch {
+    /// Both values are immediate integers, with different values.
+    Int { a: isize, b: isize },
+    /// One value is an immediate integer and the other is a block. The value
+    /// of the integer is given for the side which is an integer.
+    IntAndBlock { a: Option<isize>, b: Option<isize> },
+    /// Both values are blocks, with different tags.
+    Tag { a: u8, b: u8 },
+    /// Both values are blocks with the same tag, but different sizes.
+    Size { a: usize, b: usize },
+    /// Both values are lists, with different lengths. The elements of the
+    /// common prefix of the lists are compared as well.
+    ListLength { a: usize, b: usize },
+    /// Both values are strings, with different contents.
+    String { a: BString, b: BString },
+    /// Both values are floats (or elements of float arrays) which are not
+    /// bitwise equal.
+    Float { a: f64, b: f64 },
+    /// Both values are blocks whose contents are not scanned by the GC
+    /// (custom blocks, abstract blocks, or closures), and the word at the
+    /// given index differs.
+    Word { index: usize, a: usize, b: usize },
+}
+
+/// A difference between two values, found by `diff`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    /// The path from the roots of the compared values to the values which
+    /// differ.
+    pub path: Vec<PathSegment>,
+    pub mismatch: Mismatch,
+}
+
+/// Compare the given values structurally, and return a description of each
+/// place in which they differ, in depth-first order.
+///
+/// Where both values are lists, their elements are compared pairwise (and
+/// appear in paths as `[i]`) rather than comparing each cons cell as a pair.
+/// Since this is determined from the representation alone, a pair whose
+/// second component is a list (or `[]`) is treated as a list, too.
+/// Floats are compared bitwise, so `nan` is equal to itself and `0.0` is not
+/// equal to `-0.0`.
+///
+/// If the same pair of blocks is reachable along more than one path (or
+/// along a cycle), they are only compared once.
+pub fn diff(a: Value<'_>, b: Value<'_>) -> Vec<Difference> {
+    let mut differences = vec![];
+    let mut visited = HashSet::new();
+    // Each entry records whether `a` and `b` might both be lists. They cannot
+    // be if they are the tails of cons cells which were not both lists, since
+    // every tail of a value which is not a list is not a list either.
+    let mut stack = vec![(a, b, vec![], true)];
+    while let Some((a, b, path, maybe_list)) = stack.pop() {
+        if a == b {
+            continue;
+        }
+        let mut report = |mismatch| {
+            differences.push(Difference {
+                path: path.clone(),
+                mismatch,
+            })
+        };
+        let (block_a, block_b) = match (a.as_block(), b.as_block()) {
+            (Some(block_a), Some(block_b)) => (block_a, block_b),
+            (None, None) => {
+                report(Mismatch::Int {
+                    a: a.as_int().unwrap(),
+                    b: b.as_int().unwrap(),
+                });
+                continue;
+            }
+            _ => {
+                report(Mismatch::IntAndBlock {
+                    a: a.as_int(),
+                    b: b.as_int(),
+                });
+                continue;
+            }
+        };
+        if !visited.insert((a.to_bits(), b.to_bits())) {
+            continue;
+        }
+        if maybe_list && let (Some(elems_a), Some(elems_b)) = (list_elements(a), list_elements(b)) {
+            if elems_a.len() != elems_b.len() {
+                report(Mismatch::ListLength {
+                    a: elems_a.len(),
+                    b: elems_b.len(),
+                });
+            }
+            let elems = elems_a.into_iter().zip(elems_b).enumerate();
+            for (i, (a, b)) in elems.rev() {
+                let path = with_segment(&path, PathSegment::Element(i));
+                stack.push((a, b, path, true));
+            }
+            continue;
+        }
+        if block_a.tag() != block_b.tag() {
+            report(Mismatch::Tag {
+                a: block_a.tag(),
+                b: block_b.tag(),
+            });
+            continue;
+        }
+        if block_a.size() != block_b.size() {
+            report(Mismatch::Size {
+                a: block_a.size(),
+                b: block_b.size(),
+            });
+            continue;
+        }
+        match block_a.tag() {
+            block::STRING_TAG => {
+                let bytes_a = a.as_byte_string().unwrap();
+                let bytes_b = b.as_byte_string().unwrap();
+                if bytes_a != bytes_b {
+                    report(Mismatch::String {
+                        a: bytes_a.into(),
+                        b: bytes_b.into(),
+                    });
+                }
+            }
+            block::DOUBLE_TAG => {
+                let (a, b) = (a.as_float().unwrap(), b.as_float().unwrap());
+                if a.to_bits() != b.to_bits() {
+                    report(Mismatch::Float { a, b });
+                }
+            }
+            block::DOUBLE_ARRAY_TAG => {
+                let floats_a = a.as_double_array().unwrap();
+                let floats_b = b.as_double_array().unwrap();
+                for (i, (&a, &b)) in floats_a.iter().zip(floats_b).enumerate() {
+                    if a.to_bits() != b.to_bits() {
+                        differences.push(Difference {
+                            path: with_segment(&path, PathSegment::Element(i)),
+                            mismatch: Mismatch::Float { a, b },
+                        });
+                    }
+                }
+            }
+            tag if tag >= block::NO_SCAN_TAG
+                || tag == block::CLOSURE_TAG
+                || tag == block::INFIX_TAG =>
+            {
+                let words_a = block_a.as_int_slice();
+                let words_b = block_b.as_int_slice();
+                for (index, (&a, &b)) in words_a.iter().zip(words_b).enumerate() {
+                    if a != b {
+                        report(Mismatch::Word { index, a, b });
+                    }
+                }
+            }
+            _ => {
+                let fields_a = block_a.as_values().unwrap();
+                let fields_b = block_b.as_values().unwrap();
+                let is_cons = block_a.tag() == 0 && block_a.size() == 2;
+                let fields = fields_a.iter().zip(fields_b).enumerate();
+                for (i, (&a, &b)) in fields.rev() {
+                    let path = with_segment(&path, PathSegment::Field(i));
+                    stack.push((a, b, path, !(is_cons && i == 1)));
+                }
+            }
+        }
+    }
+    differences
+}
+
+/// If the given value looks like a list (a chain of tag-0 pairs terminated by
+/// `[]`), return its elements.
+fn list_elements(value: Value<'_>) -> Option<Vec<Value<'_>>> {
+    let mut elements = vec![];
+    let mut cells = HashSet::new();
+    let mut hd = value;
+    while let Some(block) = hd.as_block() {
+        // A cyclic chain of pairs is not a list.
+        if block.tag() != 0 || block.size() != 2 || !cells.insert(hd.to_bits()) {
+            return None;
+        }
+        elements.push(block[0]);
+        hd = block[1];
+    }
+    (hd.as_int() == Some(0) && !elements.is_empty()).then_some(elements)
+}
+
+fn with_segment(path: &[PathSegment], segment: PathSegment) -> Vec<PathSegment> {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
+
+/// Format a list of differences (as returned by `diff`) for display, showing
+/// at most `max` of them.
+pub fn format_differences(differences: &[Difference], max: usize) -> String {
+    use std::fmt::Write;
+    let mut s = String::new();
+    for difference in differences.iter().take(max) {
+        let _ = writeln!(s, "  {difference}");
+    }
+    if differences.len() > max {
+        let _ = writeln!(s, "  ... and {} more", differences.len() - max);
+    }
+    s
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Field(i) => write!(f, "{i}"),
+            PathSegment::Element(i) => write!(f, "[{i}]"),
+        }
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Mismatch::*;
+        match self {
+            Int { a, b } => write!(f, "{a} != {b}"),
+            IntAndBlock { a: Some(a), .. } => write!(f, "int {a} != block"),
+            IntAndBlock { b, .. } => write!(f, "block != int {}", b.unwrap_or_default()),
+            Tag { a, b } => write!(f, "tag {a} != tag {b}"),
+            Size { a, b } => write!(f, "size {a} != size {b}"),
+            ListLength { a, b } => write!(f, "list length {a} != list length {b}"),
+            String { a, b } => write!(f, "{a:?} != {b:?}"),
+            Float { a, b } => write!(f, "{a:?} != {b:?}"),
+            Word { index, a, b } => write!(f, "word {index}: {a:#x} != {b:#x}"),
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "(root)")?;
+        }
+        for (i, segment) in self.path.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{segment}")?;
+        }
+        write!(f, ": {}", self.mismatch)
+    }
+}
+
+/// Assert that two `Value`s are structurally equal (as determined by
+/// `ocamlrep::diff`). On failure, panics with a message listing the first
+/// differences (10 by default, or the number given as the third argument).
+#[macro_export]
+macro_rules! assert_values_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::assert_values_eq!($left, $right, 10)
+    };
+    ($left:expr, $right:expr, $max:expr $(,)?) => {{
+        let differences = $crate::diff($left, $right);
+        if !differences.is_empty() {
+            panic!(
+                "assertion `{} == {}` failed: {} difference(s)\n{}",
+                stringify!($left),
+                stringify!($right),
+                differences.len(),
+                $crate::diff::format_differences(&differences, $max),
+            );
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Allocator;
+    use crate::Arena;
+
+    #[test]
+    fn equal_values_have_no_differences() {
+        let arena = Arena::new();
+        let value = (vec![1isize, 2], "abc", 1.5f64);
+        let copy = value.clone();
+        assert_eq!(diff(arena.add(&value), arena.add(&copy)), []);
+        assert_values_eq!(arena.add(&value), arena.add(&copy));
+    }
+
+    #[test]
+    fn paths_through_lists_and_fields() {
+        let arena = Arena::new();
+        let a = vec![
+            (1isize, vec![1isize], true),
+            (2, vec![2], true),
+            (3, vec![3], true),
+        ];
+        let b = vec![
+            (1isize, vec![1isize], true),
+            (2, vec![2], true),
+            (3, vec![4], true),
+        ];
+        let differences = diff(arena.add(&a), arena.add(&b));
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].to_string(), "[2].1.[0]: 3 != 4");
+    }
+
+    #[test]
+    fn mismatch_kinds() {
+        let arena = Arena::new();
+        let a = (Some(1isize), "abc", 1.5f64, vec![1isize, 2], None::<isize>);
+        let b = (None::<isize>, "abd", 2.5f64, vec![1isize], Some(1isize));
+        let differences: Vec<String> = diff(arena.add(&a), arena.add(&b))
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            differences,
+            [
+                "0: block != int 0",
+                "1: \"abc\" != \"abd\"",
+                "2: 1.5 != 2.5",
+                "3: list length 2 != list length 1",
+                "4: int 0 != block",
+            ]
+        );
+        let differences = diff(
+            arena.add(&(1isize, 2isize)),
+            arena.add(&Ok::<_, ()>(1isize)),
+        );
+        assert_eq!(differences[0].mismatch, Mismatch::Size { a: 2, b: 1 });
+        let differences = diff(
+            arena.add(&Ok::<_, ()>(1isize)),
+            arena.add(&Err::<(), _>(1isize)),
+        );
+        assert_eq!(differences[0].mismatch, Mismatch::Tag { a: 0, b: 1 });
+    }
+
+    #[test]
+    fn format_limits_differences() {
+        let arena = Arena::new();
+        let a: Vec<isize> = (0..5).collect();
+        let b: Vec<isize> = (10..15).collect();
+        let differences = diff(arena.add(&a), arena.add(&b));
+        assert_eq!(
+            format_differences(&differences, 2),
+            "  [0]: 0 != 10\n  [1]: 1 != 11\n  ... and 3 more\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "1 difference(s)\n  [1]: 2 != 3")]
+    fn assert_values_eq_panics() {
+        let arena = Arena::new();
+        assert_values_eq!(arena.add(&vec![1isize, 2]), arena.add(&vec![1isize, 3]));
+    }
+
+    #[test]
+    fn long_lists() {
+        let arena = Arena::new();
+        let a: Vec<isize> = (0..100_000).collect();
+        let mut b = a.clone();
+        b[99_999] = 0;
+        let differences = diff(arena.add(&a), arena.add(&b));
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].path, [PathSegment::Element(99_999)]);
+    }
+
+    #[test]
+    fn list_and_improper_list() {
+        let arena = Arena::new();
+        let len = 10_000;
+        let a: Vec<isize> = (0..len).collect();
+        // The same chain of cons cells, but terminated by 1 rather than [].
+        let mut b = Value::int(1);
+        for i in (0..len).rev() {
+            let mut cell = arena.block_with_size(2);
+            arena.set_field(&mut cell, 0, Value::int(i));
+            arena.set_field(&mut cell, 1, b);
+            b = cell.build();
+        }
+        let differences = diff(arena.add(&a), b);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(
+            differences[0].path,
+            vec![PathSegment::Field(1); len as usize]
+        );
+        assert_eq!(differences[0].mismatch, Mismatch::Int { a: 0, b: 1 });
+    }
+}
@@ -7,6 +7,7 @@ use std::error::Error;
 use std::fmt;
 use std::num::TryFromIntError;
 use std::str::Utf8Error;
+use std::time::TryFromFloatSecsError;
 
 /// Returned by
 /// [`OcamlRep::from_ocamlrep`](trait.OcamlRep.html#tymethod.from_ocamlrep) when
@@ -16,7 +17,9 @@ use std::str::Utf8Error;
 pub enum FromError {
     BadUtf8(Utf8Error),
     BlockTagOutOfRange { max: u8, actual: u8 },
+    DurationOutOfRange(TryFromFloatSecsError),
     ErrorInField(usize, Box<FromError>),
+    ErrorInNamedField(FieldName, Box<FromError>),
     ExpectedBlock(isize),
     ExpectedBlockTag { expected: u8, actual: u8 },
     ExpectedBool(isize),
@@ -27,16 +30,45 @@ pub enum FromError {
     ExpectedZeroTag(u8),
     IntOutOfRange(TryFromIntError),
     NullaryVariantTagOutOfRange { max: usize, actual: isize },
+    UnforcedLazy(u8),
     WrongBlockSize { expected: usize, actual: usize },
+    WrongListLength { expected: usize, actual: usize },
     UnexpectedCustomOps { expected: usize, actual: usize },
 }
 
+/// The Rust name of a field which failed to convert, recorded by derived
+/// implementations of `FromOcamlRep` and `FromOcamlRepIn`.
+///
+/// Tuple fields are named by their index (e.g., `Orange.0`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldName {
+    pub type_name: &'static str,
+    pub variant_name: Option<&'static str>,
+    pub field_name: &'static str,
+}
+
+impl fmt::Display for FieldName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.", self.type_name)?;
+        if let Some(variant_name) = self.variant_name {
+            write!(f, "{variant_name}.")?;
+        }
+        write!(f, "{}", self.field_name)
+    }
+}
+
 impl std::convert::From<TryFromIntError> for FromError {
     fn from(error: TryFromIntError) -> Self {
         FromError::IntOutOfRange(error)
     }
 }
 
+impl std::convert::From<TryFromFloatSecsError> for FromError {
+    fn from(error: TryFromFloatSecsError) -> Self {
+        FromError::DurationOutOfRange(error)
+    }
+}
+
 impl std::convert::From<Utf8Error> for FromError {
     fn from(error: Utf8Error) -> Self {
         FromError::BadUtf8(error)
@@ -51,7 +83,8 @@ impl fmt::Display for FromError {
             BlockTagOutOfRange { max, actual } => {
                 write!(f, "Expected tag value <= {max}, but got {actual}")
             }
-            ErrorInField(idx, _) => write!(f, "Failed to convert field {idx}"),
+            DurationOutOfRange(_) => write!(f, "Duration out of range"),
+            ErrorInField(..) | ErrorInNamedField(..) => fmt_field_path(self, f),
             ExpectedBlock(x) => write!(f, "Expected block, but got integer value {x}"),
             ExpectedBlockTag { expected, actual } => {
                 write!(f, "Expected block with tag {expected}, but got {actual}",)
@@ -75,10 +108,18 @@ impl fmt::Display for FromError {
                 f,
                 "Expected nullary variant tag, where 0 <= tag <= {max}, but got {actual}",
             ),
+            UnforcedLazy(tag) => write!(
+                f,
+                "Expected forced lazy value, but got unforced lazy block with tag {tag}",
+            ),
             WrongBlockSize { expected, actual } => write!(
                 f,
                 "Expected block of size {expected}, but got size {actual}",
             ),
+            WrongListLength { expected, actual } => write!(
+                f,
+                "Expected list of length {expected}, but got length {actual}",
+            ),
             UnexpectedCustomOps { expected, actual } => write!(
                 f,
                 "Expected custom operations struct address 0x{expected:x}, but got address 0x{actual:x}",
@@ -87,12 +128,41 @@ impl fmt::Display for FromError {
     }
 }
 
+/// Render a chain of `ErrorInField` and `ErrorInNamedField` errors as a path
+/// to the innermost error, e.g. `Expr.Binop.rhs -> [3] -> Lit.String.0:
+/// Invalid UTF-8`.
+fn fmt_field_path(mut err: &FromError, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut first = true;
+    loop {
+        let inner = match err {
+            FromError::ErrorInField(idx, inner) => {
+                if !first {
+                    write!(f, " -> ")?;
+                }
+                write!(f, "[{idx}]")?;
+                inner
+            }
+            FromError::ErrorInNamedField(name, inner) => {
+                if !first {
+                    write!(f, " -> ")?;
+                }
+                write!(f, "{name}")?;
+                inner
+            }
+            _ => return write!(f, ": {err}"),
+        };
+        first = false;
+        err = inner;
+    }
+}
+
 impl Error for FromError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         use FromError::*;
         match self {
             BadUtf8(err) => Some(err),
-            ErrorInField(_, err) => Some(err),
+            DurationOutOfRange(err) => Some(err),
+            ErrorInField(_, err) | ErrorInNamedField(_, err) => Some(err),
             IntOutOfRange(err) => Some(err),
             BlockTagOutOfRange { .. }
             | ExpectedBlock(..)
@@ -104,8 +174,32 @@ impl Error for FromError {
             | ExpectedUnit(..)
             | ExpectedZeroTag(..)
             | NullaryVariantTagOutOfRange { .. }
+            | UnforcedLazy(..)
             | WrongBlockSize { .. }
+            | WrongListLength { .. }
             | UnexpectedCustomOps { .. } => None,
         }
     }
 }
+
+/// Returned by fallible [`Allocator`](trait.Allocator.html) methods (e.g.,
+/// `try_add`) when the allocator does not have enough space remaining.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError {
+    /// The size of the allocation which failed (including the block header).
+    pub requested_bytes: usize,
+    /// The space remaining in the allocator when the allocation failed.
+    pub available_bytes: usize,
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to allocate {} bytes ({} bytes available)",
+            self.requested_bytes, self.available_bytes
+        )
+    }
+}
+
+impl Error for AllocError {}
@@ -0,0 +1,324 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::cell::Cell;
+use std::cell::OnceCell;
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use crate::AllocError;
+use crate::Allocator;
+use crate::Arena;
+use crate::BlockBuilder;
+use crate::MemoizationCache;
+use crate::ToOcamlRep;
+use crate::Value;
+use crate::block::Header;
+use crate::value::is_ocaml_int;
+
+// See the comment on `NEXT_GENERATION` in 'arena.rs'.
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(usize::MAX / 4 * 3);
+
+/// An [`Allocator`](trait.Allocator.html) which builds values in a
+/// caller-provided buffer of fixed size (e.g., a preallocated region of shared
+/// memory).
+///
+/// When the buffer is full, `try_add` and `try_block_with_size_and_tag` return
+/// an `AllocError`, while the infallible `Allocator` methods panic.
+pub struct FixedBufferAllocator<'buf> {
+    generation: Cell<usize>,
+    ptr: *mut Value<'static>,
+    len: usize,
+    index: Cell<usize>,
+    /// Set while converting a value in `try_add`. If the buffer fills up
+    /// during the conversion, we record the error and allocate the remainder
+    /// of the value in `overflow` (so that the conversion can run to
+    /// completion), then discard the result. `overflow` is dropped when the
+    /// outermost `try_add` returns, so failed conversions do not accumulate.
+    in_try_add: Cell<bool>,
+    error: Cell<Option<AllocError>>,
+    overflow: UnsafeCell<OnceCell<Arena>>,
+    cache: MemoizationCache,
+    _buffer: PhantomData<&'buf mut [Value<'static>]>,
+}
+
+impl<'buf> FixedBufferAllocator<'buf> {
+    /// Create a new FixedBufferAllocator which allocates values in `buffer`.
+    pub fn new(buffer: &'buf mut [Value<'_>]) -> Self {
+        Self {
+            generation: Cell::new(NEXT_GENERATION.fetch_add(1, Ordering::SeqCst)),
+            ptr: buffer.as_mut_ptr().cast(),
+            len: buffer.len(),
+            index: Cell::new(0),
+            in_try_add: Cell::new(false),
+            error: Cell::new(None),
+            overflow: UnsafeCell::new(OnceCell::new()),
+            cache: MemoizationCache::new(),
+            _buffer: PhantomData,
+        }
+    }
+
+    /// Create a new FixedBufferAllocator which allocates values in the
+    /// word-aligned portion of `buffer`.
+    pub fn from_bytes(buffer: &'buf mut [u8]) -> Self {
+        // SAFETY: Any bit pattern is a valid `Value`, and no `Value` in the
+        // buffer is observed before the allocator writes it.
+        let (_, words, _) = unsafe { buffer.align_to_mut::<Value<'static>>() };
+        Self::new(words)
+    }
+
+    /// The number of bytes allocated in the buffer (including block headers).
+    pub fn allocated_bytes(&self) -> usize {
+        self.index.get() * std::mem::size_of::<Value<'_>>()
+    }
+
+    /// The size of the (word-aligned portion of the) buffer, in bytes.
+    pub fn capacity(&self) -> usize {
+        self.len * std::mem::size_of::<Value<'_>>()
+    }
+
+    #[inline(always)]
+    pub fn add<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
+        value.to_ocamlrep(self)
+    }
+
+    #[inline(always)]
+    pub fn add_root<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
+        Allocator::add_root(self, value)
+    }
+
+    #[inline(always)]
+    pub fn try_add<'a, T: ToOcamlRep + ?Sized>(
+        &'a self,
+        value: &'a T,
+    ) -> Result<Value<'a>, AllocError> {
+        Allocator::try_add(self, value)
+    }
+}
+
+impl Allocator for FixedBufferAllocator<'_> {
+    #[inline(always)]
+    fn generation(&self) -> usize {
+        self.generation.get()
+    }
+
+    fn block_with_size_and_tag(&self, size: usize, tag: u8) -> BlockBuilder<'_> {
+        match self.try_block_with_size_and_tag(size, tag) {
+            Ok(block) => block,
+            Err(err) => {
+                if !self.in_try_add.get() {
+                    panic!("FixedBufferAllocator: {err}");
+                }
+                if self.error.get().is_none() {
+                    self.error.set(Some(err));
+                }
+                // SAFETY: `overflow` is only replaced by the outermost
+                // `try_add`, after the conversion using it has finished.
+                let overflow = unsafe { &*self.overflow.get() };
+                let overflow = overflow.get_or_init(|| Arena::with_capacity(0));
+                overflow.block_with_size_and_tag(size, tag)
+            }
+        }
+    }
+
+    fn try_block_with_size_and_tag(
+        &self,
+        size: usize,
+        tag: u8,
+    ) -> Result<BlockBuilder<'_>, AllocError> {
+        let start = self.index.get();
+        let end = size
+            .checked_add(1)
+            .and_then(|words| start.checked_add(words))
+            .filter(|&end| end <= self.len)
+            .ok_or(AllocError {
+                requested_bytes: size
+                    .saturating_add(1)
+                    .saturating_mul(std::mem::size_of::<Value<'_>>()),
+                available_bytes: (self.len - start) * std::mem::size_of::<Value<'_>>(),
+            })?;
+        self.index.set(end);
+        // SAFETY: `start..end` is in bounds of the buffer, and does not overlap
+        // with any previously allocated block.
+        let block: &mut [Value<'_>] =
+            unsafe { std::slice::from_raw_parts_mut(self.ptr.add(start).cast(), end - start) };
+        block[0] = unsafe { Value::from_bits(Header::new(size, tag).to_bits()) };
+        Ok(BlockBuilder::new(&mut block[1..]))
+    }
+
+    #[inline(always)]
+    fn set_field<'a>(&self, block: &mut BlockBuilder<'a>, index: usize, value: Value<'a>) {
+        assert!(index < block.size());
+        unsafe { *self.block_ptr_mut(block).add(index) = value }
+    }
+
+    unsafe fn block_ptr_mut<'a>(&self, block: &mut BlockBuilder<'a>) -> *mut Value<'a> {
+        block.address() as *mut _
+    }
+
+    fn memoized<'a>(
+        &'a self,
+        ptr: usize,
+        size: usize,
+        type_id: std::any::TypeId,
+        f: impl FnOnce(&'a Self) -> Value<'a>,
+    ) -> Value<'a> {
+        let bits = self
+            .cache
+            .memoized(ptr, size, type_id, || f(self).to_bits());
+        // SAFETY: The only memoized values in the cache are those computed in
+        // the closure on the previous line. Since f returns Value<'a>, any
+        // cached bits must represent a valid Value<'a>,
+        unsafe { Value::from_bits(bits) }
+    }
+
+    fn add_root<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
+        self.cache.with_cache(|| value.to_ocamlrep(self))
+    }
+
+    /// Convert the given value into the buffer. If the buffer fills up, the
+    /// space used by the partially-converted value is made available again,
+    /// and an error is returned.
+    ///
+    /// Since blocks converted during the failed call may have been cached
+    /// (e.g., in an `RcOc`), the allocator is assigned a new generation number
+    /// when this happens, so values converted earlier will not be shared with
+    /// values converted later.
+    fn try_add<'a, T: ToOcamlRep + ?Sized>(
+        &'a self,
+        value: &'a T,
+    ) -> Result<Value<'a>, AllocError> {
+        let start = self.index.get();
+        let was_in_try_add = self.in_try_add.replace(true);
+        let value = value.to_ocamlrep(self);
+        self.in_try_add.set(was_in_try_add);
+        match self.error.get() {
+            Some(err) if !was_in_try_add => {
+                self.error.set(None);
+                self.index.set(start);
+                // Forget any cached results which might point into the space
+                // we are handing back (or into `overflow`), so that they are
+                // not returned once that space has been overwritten.
+                let kept = self.ptr as usize..self.ptr.wrapping_add(start) as usize;
+                (self.cache).retain(|output| is_ocaml_int(output) || kept.contains(&output));
+                (self.generation).set(NEXT_GENERATION.fetch_add(1, Ordering::SeqCst));
+                // SAFETY: Nothing allocated in `overflow` is reachable now that
+                // the conversion has been discarded and the cache cleaned.
+                unsafe { *self.overflow.get() = OnceCell::new() };
+                Err(err)
+            }
+            _ => Ok(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FromOcamlRep;
+
+    const WORD_SIZE: usize = std::mem::size_of::<Value<'_>>();
+
+    #[test]
+    fn values_are_allocated_in_buffer() {
+        let mut buffer = vec![Value::int(0); 16];
+        let range = buffer.as_ptr_range();
+        let range = range.start as usize..range.end as usize;
+        let alloc = FixedBufferAllocator::new(&mut buffer);
+        let list = vec![1isize, 2, 3];
+        let value = alloc.try_add(&list).unwrap();
+        assert!(range.contains(&value.to_bits()));
+        assert_eq!(alloc.allocated_bytes(), 9 * WORD_SIZE);
+        assert_eq!(alloc.capacity(), 16 * WORD_SIZE);
+        assert_eq!(<Vec<isize>>::from_ocamlrep(value), Ok(list));
+    }
+
+    #[test]
+    fn try_add_failure_releases_space() {
+        let mut buffer = vec![Value::int(0); 10];
+        let alloc = FixedBufferAllocator::new(&mut buffer);
+        let short = vec![1isize, 2];
+        let long = vec![1isize, 2, 3, 4];
+        alloc.try_add(&short).unwrap();
+        assert_eq!(
+            alloc.try_add(&long),
+            Err(AllocError {
+                requested_bytes: 3 * WORD_SIZE,
+                available_bytes: WORD_SIZE,
+            })
+        );
+        assert_eq!(alloc.allocated_bytes(), 6 * WORD_SIZE);
+        // Space is still available for smaller values.
+        let value = alloc.try_add(&Some(1isize)).unwrap();
+        assert_eq!(<Option<isize>>::from_ocamlrep(value), Ok(Some(1)));
+    }
+
+    #[test]
+    fn try_add_failure_forgets_cached_values() {
+        let mut buffer = vec![Value::int(0); 12];
+        let alloc = FixedBufferAllocator::new(&mut buffer);
+        let shared = crate::rc::RcOc::new(String::from("shared"));
+        // The tuple and the string fit, but the list does not.
+        let long = (shared.clone(), vec![1isize, 2, 3]);
+        assert!(alloc.try_add(&long).is_err());
+        assert_eq!(alloc.allocated_bytes(), 0);
+        // Overwrite the space in which the string was converted.
+        let pair = (1isize, 2isize);
+        alloc.try_add(&pair).unwrap();
+        alloc.try_add("overwritten").unwrap();
+        let value = alloc.try_add(&shared).unwrap();
+        assert_eq!(value.as_str().unwrap(), "shared");
+    }
+
+    #[test]
+    fn try_add_failure_frees_overflow() {
+        let mut buffer = vec![Value::int(0); 4];
+        let alloc = FixedBufferAllocator::new(&mut buffer);
+        let long = vec![1isize; 100];
+        for _ in 0..3 {
+            assert!(alloc.try_add(&long).is_err());
+            assert!(unsafe { &*alloc.overflow.get() }.get().is_none());
+        }
+        assert_eq!(alloc.allocated_bytes(), 0);
+    }
+
+    #[test]
+    fn try_block_with_size_and_tag() {
+        let mut buffer = vec![Value::int(0); 4];
+        let alloc = FixedBufferAllocator::new(&mut buffer);
+        assert!(alloc.try_block_with_size_and_tag(4, 0).is_err());
+        assert!(alloc.try_block_with_size_and_tag(3, 0).is_ok());
+        assert!(alloc.try_block_with_size_and_tag(1, 0).is_err());
+        assert!(alloc.try_block_with_size_and_tag(usize::MAX, 0).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "FixedBufferAllocator: Failed to allocate")]
+    fn add_panics_when_full() {
+        let mut buffer = vec![Value::int(0); 4];
+        let alloc = FixedBufferAllocator::new(&mut buffer);
+        alloc.add(&vec![1isize, 2]);
+    }
+
+    #[test]
+    fn from_bytes() {
+        let mut buffer = [0u8; 8 * WORD_SIZE + 3];
+        let alloc = FixedBufferAllocator::from_bytes(&mut buffer[3..]);
+        assert!(alloc.capacity() >= 7 * WORD_SIZE);
+        let value = alloc.try_add("hello").unwrap();
+        assert_eq!(value.as_str().unwrap(), "hello");
+        assert!(alloc.try_add(&"x".repeat(100)).is_err());
+    }
+
+    #[test]
+    fn default_try_add_never_fails() {
+        let arena = Arena::with_capacity(0);
+        let list = vec![1isize; 100];
+        let value = arena.try_add(&list).unwrap();
+        assert_eq!(<Vec<isize>>::from_ocamlrep(value), Ok(list));
+    }
+}
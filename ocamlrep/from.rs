@@ -3,15 +3,18 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
-//! Helpers for implementing `FromOcamlRep::from_ocamlrep` or
-//! `FromOcamlRepIn::from_ocamlrep_in`.
+//! Helpers for implementing `FromOcamlRep::from_ocamlrep`,
+//! `FromOcamlRepIn::from_ocamlrep_in`, or `FromOcamlRepRef::from_ocamlrep_ref`.
 
 use bumpalo::Bump;
 
 use crate::Block;
+use crate::DOUBLE_ARRAY_TAG;
+use crate::FieldName;
 use crate::FromError;
 use crate::FromOcamlRep;
 use crate::FromOcamlRepIn;
+use crate::FromOcamlRepRef;
 use crate::Value;
 
 pub fn expect_int(value: Value<'_>) -> Result<isize, FromError> {
@@ -78,6 +81,18 @@ pub fn expect_tuple(value: Value<'_>, size: usize) -> Result<Block<'_>, FromErro
     Ok(block)
 }
 
+/// Return the contents of an OCaml `float array` (a block with tag
+/// `DOUBLE_ARRAY_TAG`, or the empty array).
+pub fn expect_float_array(value: Value<'_>) -> Result<&[f64], FromError> {
+    let block = expect_block(value)?;
+    // The empty float array is represented by an empty block with tag 0.
+    if block.size() == 0 {
+        return Ok(&[]);
+    }
+    expect_block_tag(block, DOUBLE_ARRAY_TAG)?;
+    Ok(value.as_double_array().unwrap())
+}
+
 pub fn field<T: FromOcamlRep>(block: Block<'_>, field: usize) -> Result<T, FromError> {
     T::from_ocamlrep(block[field]).map_err(|e| FromError::ErrorInField(field, Box::new(e)))
 }
@@ -90,3 +105,42 @@ pub fn field_in<'a, T: FromOcamlRepIn<'a>>(
     T::from_ocamlrep_in(block[field], alloc)
         .map_err(|e| FromError::ErrorInField(field, Box::new(e)))
 }
+
+pub fn field_ref<'v, T: FromOcamlRepRef<'v>>(
+    block: Block<'v>,
+    field: usize,
+) -> Result<T, FromError> {
+    T::from_ocamlrep_ref(block[field]).map_err(|e| FromError::ErrorInField(field, Box::new(e)))
+}
+
+/// Like `field`, but records the Rust name of the field in the returned error
+/// (for use in derived implementations of `FromOcamlRep`).
+pub fn named_field<T: FromOcamlRep>(
+    block: Block<'_>,
+    field: usize,
+    name: FieldName,
+) -> Result<T, FromError> {
+    T::from_ocamlrep(block[field]).map_err(|e| FromError::ErrorInNamedField(name, Box::new(e)))
+}
+
+/// Like `field_in`, but records the Rust name of the field in the returned
+/// error (for use in derived implementations of `FromOcamlRepIn`).
+pub fn named_field_in<'a, T: FromOcamlRepIn<'a>>(
+    block: Block<'_>,
+    field: usize,
+    name: FieldName,
+    alloc: &'a Bump,
+) -> Result<T, FromError> {
+    T::from_ocamlrep_in(block[field], alloc)
+        .map_err(|e| FromError::ErrorInNamedField(name, Box::new(e)))
+}
+
+/// Like `field_ref`, but records the Rust name of the field in the returned
+/// error (for use in derived implementations of `FromOcamlRepRef`).
+pub fn named_field_ref<'v, T: FromOcamlRepRef<'v>>(
+    block: Block<'v>,
+    field: usize,
+    name: FieldName,
+) -> Result<T, FromError> {
+    T::from_ocamlrep_ref(block[field]).map_err(|e| FromError::ErrorInNamedField(name, Box::new(e)))
+}
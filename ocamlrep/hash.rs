@@ -0,0 +1,342 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A port of the OCaml runtime's structural hash function (`caml_hash` in
+//! 'hash.c'), which backs `Hashtbl.hash` and friends.
+//!
+//! Values built in Rust (e.g., in an `Arena`) can be hashed with these
+//! functions, producing the same result the OCaml runtime would produce for an
+//! equal OCaml value. This makes it possible to compute lookup keys for OCaml
+//! `Hashtbl`s without calling into the runtime.
+//!
+//! Pointers are always assumed to point to valid blocks (as in OCaml 5, or
+//! OCaml 4 configured with `--disable-naked-pointers`).
+
+use crate::Value;
+use crate::block;
+
+/// The maximum number of values to examine (`HASH_QUEUE_SIZE` in 'hash.c').
+const HASH_QUEUE_SIZE: usize = 256;
+
+/// The maximum number of `Forward_tag` links followed before giving up on an
+/// object (`MAX_FORWARD_DEREFERENCE` in 'hash.c').
+const MAX_FORWARD_DEREFERENCE: usize = 1000;
+
+/// Equivalent to OCaml's `Hashtbl.hash`: hash the given value, examining at
+/// most 10 meaningful values and 100 values in total.
+pub fn hash(value: Value<'_>) -> isize {
+    seeded_hash_param(10, 100, 0, value)
+}
+
+/// Equivalent to OCaml's `Hashtbl.seeded_hash`.
+pub fn seeded_hash(seed: isize, value: Value<'_>) -> isize {
+    seeded_hash_param(10, 100, seed, value)
+}
+
+/// Equivalent to OCaml's `Hashtbl.hash_param`.
+///
+/// Traversal of the value stops after `meaningful` meaningful values
+/// (integers, floats, strings, etc.) have been encountered, or after `total`
+/// values (meaningful or not) have been encountered.
+pub fn hash_param(meaningful: usize, total: usize, value: Value<'_>) -> isize {
+    seeded_hash_param(meaningful, total, 0, value)
+}
+
+/// Equivalent to OCaml's `Hashtbl.seeded_hash_param`.
+pub fn seeded_hash_param(meaningful: usize, total: usize, seed: isize, value: Value<'_>) -> isize {
+    let mut queue = [Value::int(0); HASH_QUEUE_SIZE];
+    let sz = std::cmp::min(total, HASH_QUEUE_SIZE);
+    let mut num = meaningful as isize;
+    let mut h = seed as u32;
+    queue[0] = value;
+    let mut rd = 0;
+    let mut wr = 1;
+
+    while rd < wr && num > 0 {
+        let mut v = queue[rd];
+        rd += 1;
+        // Loop only to implement the `goto again` for Infix_tag and
+        // Forward_tag in 'hash.c'.
+        loop {
+            let block = match v.as_block() {
+                None => {
+                    h = mix_intnat(h, v.to_bits() as isize);
+                    num -= 1;
+                    break;
+                }
+                Some(block) => block,
+            };
+            match block.tag() {
+                block::STRING_TAG => {
+                    h = mix_string(h, v.as_byte_string().unwrap());
+                    num -= 1;
+                }
+                block::DOUBLE_TAG => {
+                    h = mix_double(h, v.as_float().unwrap());
+                    num -= 1;
+                }
+                block::DOUBLE_ARRAY_TAG => {
+                    for &d in v.as_double_array().unwrap() {
+                        h = mix_double(h, d);
+                        num -= 1;
+                        if num <= 0 {
+                            break;
+                        }
+                    }
+                }
+                block::ABSTRACT_TAG => {
+                    // Block contents unknown. Do nothing.
+                }
+                block::INFIX_TAG => {
+                    // Mix in the offset to distinguish different functions
+                    // from the same mutually-recursive definition.
+                    let offset = block.size() * std::mem::size_of::<Value<'_>>();
+                    h = mix_u32(h, offset as u32);
+                    v = unsafe { Value::from_bits(v.to_bits() - offset) };
+                    continue;
+                }
+                block::FORWARD_TAG => {
+                    // We can have a loop here, so limit the number of
+                    // Forward_tag links being followed.
+                    let mut next = None;
+                    let mut fwd = v;
+                    for _ in 0..MAX_FORWARD_DEREFERENCE {
+                        fwd = fwd.as_block().unwrap()[0];
+                        match fwd.as_block() {
+                            Some(b) if b.tag() == block::FORWARD_TAG => {}
+                            _ => {
+                                next = Some(fwd);
+                                break;
+                            }
+                        }
+                    }
+                    if let Some(next) = next {
+                        v = next;
+                        continue;
+                    }
+                    // Give up on this object and move to the next.
+                }
+                block::OBJECT_TAG => {
+                    h = mix_intnat(h, block[1].as_int().unwrap_or(0));
+                    num -= 1;
+                }
+                block::CUSTOM_TAG => {
+                    // If no hashing function provided, do nothing. Only use
+                    // low 32 bits of custom hash, for 32/64 compatibility.
+                    if let Some(hash) = block.custom_operations().and_then(|ops| ops.hash) {
+                        h = mix_u32(h, hash(v.to_bits()) as u32);
+                        num -= 1;
+                    }
+                }
+                block::CLOSURE_TAG => {
+                    // Mix in the tag and size, but do not count this towards
+                    // `num`.
+                    h = mix_u32(h, clean_header(block));
+                    // Mix the code pointers, closure info fields, and infix
+                    // headers.
+                    let fields = block.as_values().unwrap();
+                    // A closure too small to hold its closure info is
+                    // malformed; mix all of its words rather than guessing
+                    // which of them are values.
+                    let start_env = block
+                        .closure_start_env()
+                        .unwrap_or(fields.len())
+                        .min(fields.len());
+                    for field in &fields[..start_env] {
+                        h = mix_intnat(h, field.to_bits() as isize);
+                        num -= 1;
+                    }
+                    // Copy environment fields into queue, not exceeding the
+                    // total size `sz`.
+                    for &field in &fields[start_env..] {
+                        if wr >= sz {
+                            break;
+                        }
+                        queue[wr] = field;
+                        wr += 1;
+                    }
+                }
+                block::CONT_TAG => {
+                    // All continuations hash to the same value, since we have
+                    // no idea how to distinguish them.
+                }
+                _ => {
+                    // Mix in the tag and size, but do not count this towards
+                    // `num`.
+                    h = mix_u32(h, clean_header(block));
+                    // Copy fields into queue, not exceeding the total size
+                    // `sz`.
+                    for &field in block.as_values().unwrap_or(&[]) {
+                        if wr >= sz {
+                            break;
+                        }
+                        queue[wr] = field;
+                        wr += 1;
+                    }
+                }
+            }
+            break;
+        }
+    }
+    // Final mixing of bits, then fold the result to the range [0, 2^30-1] so
+    // that it is a nonnegative OCaml integer both on 32 and 64-bit platforms.
+    (final_mix(h) & 0x3FFF_FFFF) as isize
+}
+
+/// The block's header with its color bits cleared (like `Cleanhd_hd`),
+/// truncated to 32 bits. Any reserved bits are kept, as in `caml_hash`.
+#[inline]
+fn clean_header(block: block::Block<'_>) -> u32 {
+    (block.header().to_bits() & !(block::Color::Black as usize)) as u32
+}
+
+#[inline]
+fn mix_u32(mut h: u32, mut d: u32) -> u32 {
+    d = d.wrapping_mul(0xcc9e2d51);
+    d = d.rotate_left(15);
+    d = d.wrapping_mul(0x1b873593);
+    h ^= d;
+    h = h.rotate_left(13);
+    h.wrapping_mul(5).wrapping_add(0xe6546b64)
+}
+
+#[inline]
+fn mix_intnat(h: u32, d: isize) -> u32 {
+    #[cfg(target_pointer_width = "64")]
+    let n = ((d >> 32) ^ (d >> 63) ^ d) as u32;
+    #[cfg(not(target_pointer_width = "64"))]
+    let n = d as u32;
+    mix_u32(h, n)
+}
+
+#[inline]
+fn mix_double(h: u32, d: f64) -> u32 {
+    let bits = d.to_bits();
+    let mut hi = (bits >> 32) as u32;
+    let mut lo = bits as u32;
+    if (hi & 0x7FF00000) == 0x7FF00000 && (lo | (hi & 0xFFFFF)) != 0 {
+        // Normalize NaNs.
+        hi = 0x7FF00001;
+        lo = 0;
+    } else if hi == 0x80000000 && lo == 0 {
+        // Normalize -0 into +0.
+        hi = 0;
+    }
+    mix_u32(mix_u32(h, lo), hi)
+}
+
+#[inline]
+fn mix_string(mut h: u32, s: &[u8]) -> u32 {
+    // Mix by 32-bit blocks (little-endian).
+    let mut chunks = s.chunks_exact(4);
+    for chunk in &mut chunks {
+        h = mix_u32(h, u32::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    // Finish with up to 3 bytes.
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        let mut w = 0u32;
+        for (i, &b) in rest.iter().enumerate() {
+            w |= (b as u32) << (8 * i);
+        }
+        h = mix_u32(h, w);
+    }
+    // Finally, mix in the length. Ignore the upper 32 bits, generally 0.
+    h ^ s.len() as u32
+}
+
+#[inline]
+fn final_mix(mut h: u32) -> u32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^= h >> 16;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Allocator;
+    use crate::Arena;
+
+    #[test]
+    fn ints() {
+        assert_eq!(hash(Value::int(0)), 129913994);
+        assert_eq!(hash(Value::int(0)), seeded_hash(0, Value::int(0)));
+        assert_ne!(hash(Value::int(0)), seeded_hash(1, Value::int(0)));
+        assert_ne!(hash(Value::int(0)), hash(Value::int(1)));
+    }
+
+    #[test]
+    fn strings() {
+        let arena = Arena::new();
+        assert_eq!(hash(arena.add("")), 0);
+        assert_eq!(
+            hash(arena.add("abc")),
+            hash(arena.add(&String::from("abc")))
+        );
+        assert_ne!(hash(arena.add("abcd")), hash(arena.add("abce")));
+    }
+
+    #[test]
+    fn doubles() {
+        let arena = Arena::new();
+        assert_eq!(hash(arena.add(&0.0f64)), hash(arena.add(&-0.0f64)));
+        assert_eq!(hash(arena.add(&f64::NAN)), hash(arena.add(&-f64::NAN)));
+        assert_ne!(hash(arena.add(&1.0f64)), hash(arena.add(&2.0f64)));
+    }
+
+    #[test]
+    fn meaningful_limit() {
+        let arena = Arena::new();
+        // Only the first 10 elements of a list contribute to its hash.
+        let a: Vec<isize> = (0..20).collect();
+        let mut b = a.clone();
+        b[15] = 100;
+        assert_eq!(hash(arena.add(&a)), hash(arena.add(&b)));
+        b[5] = 100;
+        assert_ne!(hash(arena.add(&a)), hash(arena.add(&b)));
+        assert_eq!(hash_param(5, 100, arena.add(&a)), {
+            let mut c = a.clone();
+            c[5] = 100;
+            hash_param(5, 100, arena.add(&c))
+        });
+    }
+
+    #[test]
+    fn total_limit() {
+        let arena = Arena::new();
+        let a = (1, (2, (3, 4)));
+        let b = (1, (2, (3, 5)));
+        assert_ne!(hash(arena.add(&a)), hash(arena.add(&b)));
+        assert_eq!(
+            hash_param(10, 4, arena.add(&a)),
+            hash_param(10, 4, arena.add(&b))
+        );
+    }
+
+    #[test]
+    fn forward_blocks() {
+        let arena = Arena::new();
+        let mut fwd = arena.block_with_size_and_tag(1, block::FORWARD_TAG);
+        arena.set_field(&mut fwd, 0, arena.add("lazy"));
+        assert_eq!(hash(fwd.build()), hash(arena.add("lazy")));
+    }
+
+    #[test]
+    fn undersized_closures() {
+        let arena = Arena::new();
+        let closure = |code| {
+            let mut closure = arena.block_with_size_and_tag(1, block::CLOSURE_TAG);
+            arena.set_field(&mut closure, 0, Value::int(code));
+            closure.build()
+        };
+        // Closures without a closure info field don't panic, and their code
+        // pointer is mixed in.
+        assert_ne!(hash(closure(1)), hash(closure(2)));
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Helpers for `Allocator::add_hashconsed`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::Allocator;
+use crate::Arena;
+use crate::ToOcamlRep;
+use crate::Value;
+use crate::block;
+use crate::block::Header;
+
+thread_local! {
+    /// The arena in which `add_hashconsed` converts values before copying
+    /// them, kept between calls so that its memory can be reused.
+    static SCRATCH: RefCell<Arena> = RefCell::new(Arena::new());
+}
+
+/// Convert `value` in the scratch arena, then copy it into `alloc` with
+/// `clone_hashconsed`.
+pub(crate) fn add_hashconsed<'a, A: Allocator, T: ToOcamlRep + ?Sized>(
+    alloc: &'a A,
+    value: &T,
+) -> Value<'a> {
+    SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+        Ok(mut scratch) => {
+            let copy = clone_hashconsed(alloc, scratch.add_root(value));
+            scratch.reset();
+            copy
+        }
+        // A nested call (made by a `ToOcamlRep` implementation while an outer
+        // call converts its value) needs an arena of its own.
+        Err(_) => clone_hashconsed(alloc, Arena::new().add_root(value)),
+    })
+}
+
+/// Copy the given value into `alloc`, allocating only one copy of each set of
+/// structurally equal blocks (blocks with the same tag, size, and contents,
+/// where the contents of pointer fields are compared by the identity of their
+/// interned copies).
+///
+/// Custom blocks are copied, but never shared.
+///
+/// The given value must be acyclic (as are all values produced by
+/// `ToOcamlRep` implementations).
+pub(crate) fn clone_hashconsed<'a, A: Allocator>(alloc: &'a A, value: Value<'_>) -> Value<'a> {
+    // Maps the address of each block in `value` to its interned copy.
+    let mut copies: HashMap<usize, Value<'a>> = HashMap::new();
+    // Maps the contents of each interned copy (its header followed by its
+    // fields, with pointer fields referring to interned copies) to the copy.
+    let mut interned: HashMap<Vec<usize>, Value<'a>> = HashMap::new();
+    // Copy blocks in post-order, so that each block's fields have been
+    // interned before the block itself.
+    let mut stack = vec![(value, false)];
+    while let Some((value, fields_copied)) = stack.pop() {
+        let block = match value.as_block() {
+            None => continue,
+            Some(block) => block,
+        };
+        if copies.contains_key(&value.to_bits()) {
+            continue;
+        }
+        let fields = block.as_values();
+        if !fields_copied {
+            stack.push((value, true));
+            for &field in fields.unwrap_or(&[]).iter().rev() {
+                if field.is_block() && !copies.contains_key(&field.to_bits()) {
+                    stack.push((field, false));
+                }
+            }
+            continue;
+        }
+        let mut contents = Vec::with_capacity(block.size() + 1);
+        contents.push(Header::new(block.size(), block.tag()).to_bits());
+        match fields {
+            Some(fields) => contents.extend(fields.iter().map(|field| match field.as_block() {
+                None => field.to_bits(),
+                Some(_) => copies[&field.to_bits()].to_bits(),
+            })),
+            None => contents.extend_from_slice(block.as_int_slice()),
+        }
+        let copy = if block.tag() == block::CUSTOM_TAG {
+            copy_block(alloc, &contents)
+        } else {
+            match interned.get(&contents) {
+                Some(&copy) => copy,
+                None => {
+                    let copy = copy_block(alloc, &contents);
+                    interned.insert(contents, copy);
+                    copy
+                }
+            }
+        };
+        copies.insert(value.to_bits(), copy);
+    }
+    match value.as_block() {
+        None => unsafe { Value::from_bits(value.to_bits()) },
+        Some(_) => copies[&value.to_bits()],
+    }
+}
+
+/// Allocate a block with the given header and fields (whose pointer fields, if
+/// any, must refer to blocks allocated by `alloc`).
+fn copy_block<'a, A: Allocator>(alloc: &'a A, contents: &[usize]) -> Value<'a> {
+    let header = Header::from_bits(contents[0]);
+    let mut block = alloc.block_with_size_and_tag(header.size(), header.tag());
+    // Safety: `block` has `header.size()` fields, and `contents` has one word
+    // for each of them following the header. Pointer fields in `contents`
+    // refer to blocks allocated by `alloc`, as required by the caller.
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            contents[1..].as_ptr(),
+            alloc.block_ptr_mut(&mut block) as *mut usize,
+            header.size(),
+        )
+    }
+    block.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Arena;
+    use crate::FromOcamlRep;
+
+    /// Name, kind, and position
+    type Decl = (String, String, (isize, isize));
+
+    fn decl(name: &str, kind: &str) -> Decl {
+        (name.to_string(), kind.to_string(), (1, 2))
+    }
+
+    #[test]
+    fn equal_strings_are_shared() {
+        let arena = Arena::new();
+        let decls = vec![decl("foo", "class"), decl("bar", "class")];
+        let value = arena.add_hashconsed(&decls);
+        assert_eq!(<Vec<Decl>>::from_ocamlrep(value), Ok(decls.clone()));
+        // Two list cells, two decls, three distinct strings, and one position
+        assert_eq!(value.stats().blocks, 8);
+        let first = value.field(0).unwrap();
+        let second = value.field(1).unwrap().field(0).unwrap();
+        assert_eq!(first.field(1), second.field(1));
+        assert_eq!(first.field(2), second.field(2));
+        assert_ne!(first.field(0), second.field(0));
+    }
+
+    #[test]
+    fn equal_records_are_shared() {
+        let arena = Arena::new();
+        let decls = vec![decl("foo", "class"), decl("foo", "class")];
+        let value = arena.add_hashconsed(&decls);
+        assert_eq!(<Vec<Decl>>::from_ocamlrep(value), Ok(decls.clone()));
+        assert_eq!(value.field(0), value.field(1).unwrap().field(0));
+        // Two list cells (which have different tails), one decl, two strings,
+        // and one position
+        assert_eq!(value.stats().blocks, 2 + 1 + 2 + 1);
+    }
+
+    #[test]
+    fn floats_are_compared_bitwise() {
+        let arena = Arena::new();
+        let floats = (0.0f64, -0.0f64, 0.0f64);
+        let value = arena.add_hashconsed(&floats);
+        assert_eq!(value.field(0), value.field(2));
+        assert_ne!(value.field(0), value.field(1));
+        assert_eq!(<(f64, f64, f64)>::from_ocamlrep(value), Ok(floats));
+    }
+
+    #[test]
+    fn scratch_arena_is_reused() {
+        let arena = Arena::new();
+        let decls = vec![decl("foo", "class"); 100];
+        let first = arena.add_hashconsed(&decls);
+        let capacity = super::SCRATCH.with(|scratch| scratch.borrow().capacity());
+        let second = arena.add_hashconsed(&decls);
+        assert_eq!(
+            super::SCRATCH.with(|scratch| scratch.borrow().capacity()),
+            capacity
+        );
+        assert_eq!(
+            super::SCRATCH.with(|scratch| scratch.borrow().allocated_bytes()),
+            0
+        );
+        assert_eq!(<Vec<Decl>>::from_ocamlrep(first), Ok(decls.clone()));
+        assert_eq!(<Vec<Decl>>::from_ocamlrep(second), Ok(decls));
+    }
+
+    #[test]
+    fn immediate_values() {
+        let arena = Arena::new();
+        assert_eq!(arena.add_hashconsed(&42isize).as_int(), Some(42));
+        assert_eq!(arena.add_hashconsed(&None::<String>).as_int(), Some(0));
+    }
+}
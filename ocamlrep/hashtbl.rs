@@ -0,0 +1,339 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Conversion of maps to and from OCaml hash tables (`Hashtbl.t`).
+//!
+//! A `('k, 'v) Hashtbl.t` is a record:
+//!
+//! ```ocaml
+//! type ('a, 'b) t = {
+//!   mutable size: int;
+//!   mutable data: ('a, 'b) bucketlist array;
+//!   seed: int;
+//!   mutable initial_size: int;
+//! }
+//! and ('a, 'b) bucketlist =
+//!   | Empty
+//!   | Cons of { mutable key: 'a; mutable data: 'b; mutable next: ('a, 'b) bucketlist }
+//! ```
+//!
+//! A binding is stored in the bucket at index `seeded_hash_param 10 100 seed
+//! key land (Array.length data - 1)`. Since `ocamlrep::hash` computes the same
+//! hash as the OCaml runtime, the tables built here can be used by OCaml
+//! directly, without rehashing.
+//!
+//! Tables are built with seed 0 (as by `Hashtbl.create` when randomization is
+//! disabled), so they should not be passed to OCaml code which relies on
+//! `Hashtbl.randomize`. As with any value built outside the OCaml heap (e.g.,
+//! in an `Arena`), a table must be copied into the OCaml heap (e.g., with
+//! `Hashtbl.copy`) before OCaml code mutates it.
+
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+use bumpalo::Bump;
+
+use crate::Allocator;
+use crate::AssocPair;
+use crate::FromError;
+use crate::FromOcamlRep;
+use crate::FromOcamlRepIn;
+use crate::ToOcamlRep;
+use crate::Value;
+use crate::from;
+use crate::hash;
+use crate::iter::ArrayIter;
+
+/// The minimum number of buckets in a table (as in `Hashtbl.create`).
+const MIN_BUCKETS: usize = 16;
+
+/// The index of the bucket for the given key in a table with `num_buckets`
+/// buckets (`key_index` in 'hashtbl.ml').
+fn key_index(seed: isize, num_buckets: usize, key: Value<'_>) -> usize {
+    (hash::seeded_hash_param(10, 100, seed, key) as usize) & (num_buckets - 1)
+}
+
+/// Build an OCaml `Hashtbl.t` containing the key-value pairs emitted by the
+/// given iterator (e.g., `&map` for any map type).
+///
+/// The table is laid out as if it were created with `Hashtbl.create n` (where
+/// `n` is the number of pairs), and each pair were added with `Hashtbl.add` in
+/// iteration order. The iterator should emit each key only once; if it emits a
+/// key more than once, the last binding shadows the others (as with
+/// `Hashtbl.add`).
+pub fn iter_to_ocaml_hashtbl<'a, A: Allocator, P: AssocPair<'a>>(
+    iter: impl IntoIterator<Item = P>,
+    alloc: &'a A,
+) -> Value<'a> {
+    let pairs: Vec<(Value<'a>, Value<'a>)> = iter
+        .into_iter()
+        .map(|pair| {
+            let (key, value) = pair.into_pair();
+            (alloc.add(key), alloc.add(value))
+        })
+        .collect();
+    let size = pairs.len();
+    let num_buckets = size.max(MIN_BUCKETS).next_power_of_two();
+    let seed = 0;
+
+    let mut buckets = vec![Value::int(0); num_buckets];
+    for (key, value) in pairs {
+        let bucket = &mut buckets[key_index(seed, num_buckets, key)];
+        let mut cons = alloc.block_with_size(3);
+        alloc.set_field(&mut cons, 0, key);
+        alloc.set_field(&mut cons, 1, value);
+        alloc.set_field(&mut cons, 2, *bucket);
+        *bucket = cons.build();
+    }
+    let mut data = alloc.block_with_size(num_buckets);
+    for (i, bucket) in buckets.into_iter().enumerate() {
+        alloc.set_field(&mut data, i, bucket);
+    }
+
+    let mut table = alloc.block_with_size(4);
+    alloc.set_field(&mut table, 0, Value::int(size as isize));
+    alloc.set_field(&mut table, 1, data.build());
+    alloc.set_field(&mut table, 2, Value::int(seed));
+    alloc.set_field(&mut table, 3, Value::int(num_buckets as isize));
+    table.build()
+}
+
+/// Call `f` with each binding in the given OCaml `Hashtbl.t`, in the order
+/// `Hashtbl.iter` would visit them (so for a key with multiple bindings, the
+/// current binding comes first).
+fn iter_ocaml_hashtbl<'a>(
+    value: Value<'a>,
+    mut f: impl FnMut(Value<'a>, Value<'a>) -> Result<(), FromError>,
+) -> Result<(), FromError> {
+    let table = from::expect_tuple(value, 4)?;
+    for bucket in ArrayIter::new(table[1])? {
+        let mut bucket = bucket;
+        while !bucket.is_int() {
+            let cons = from::expect_tuple(bucket, 3)?;
+            f(cons[0], cons[1])?;
+            bucket = cons[2];
+        }
+        let _ = from::expect_nullary_variant(bucket, 0)?;
+    }
+    Ok(())
+}
+
+/// Return all bindings in the given OCaml `Hashtbl.t`, in the order
+/// `Hashtbl.iter` would visit them.
+pub fn vec_from_ocaml_hashtbl<K: FromOcamlRep, V: FromOcamlRep>(
+    value: Value<'_>,
+) -> Result<Vec<(K, V)>, FromError> {
+    let mut vec = vec![];
+    iter_ocaml_hashtbl(value, |key, value| {
+        let convert = || Ok((K::from_ocamlrep(key)?, V::from_ocamlrep(value)?));
+        let pair = convert().map_err(|e| FromError::ErrorInField(vec.len(), Box::new(e)))?;
+        vec.push(pair);
+        Ok(())
+    })?;
+    Ok(vec)
+}
+
+/// A `HashMap` which converts to and from an OCaml `Hashtbl.t`.
+///
+/// When converting from a table containing multiple bindings for a key, only
+/// the current binding (the one `Hashtbl.find` would return) is kept.
+#[derive(Clone)]
+pub struct OcamlHashtbl<K, V, S = RandomState>(HashMap<K, V, S>);
+
+impl<K, V> OcamlHashtbl<K, V> {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl<K, V, S> OcamlHashtbl<K, V, S> {
+    pub fn into_inner(self) -> HashMap<K, V, S> {
+        self.0
+    }
+}
+
+impl<K, V, S: Default> Default for OcamlHashtbl<K, V, S> {
+    fn default() -> Self {
+        Self(HashMap::default())
+    }
+}
+
+impl<K, V, S> Deref for OcamlHashtbl<K, V, S> {
+    type Target = HashMap<K, V, S>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, V, S> DerefMut for OcamlHashtbl<K, V, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<K: Eq + Hash, V: PartialEq, S: BuildHasher> PartialEq for OcamlHashtbl<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K: Eq + Hash, V: Eq, S: BuildHasher> Eq for OcamlHashtbl<K, V, S> {}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for OcamlHashtbl<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for OcamlHashtbl<K, V, S> {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self(map)
+    }
+}
+
+impl<K, V, S> From<OcamlHashtbl<K, V, S>> for HashMap<K, V, S> {
+    fn from(table: OcamlHashtbl<K, V, S>) -> Self {
+        table.0
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Default> FromIterator<(K, V)> for OcamlHashtbl<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<K, V, S> IntoIterator for OcamlHashtbl<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = std::collections::hash_map::IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<K: ToOcamlRep, V: ToOcamlRep, S> ToOcamlRep for OcamlHashtbl<K, V, S> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        iter_to_ocaml_hashtbl(&self.0, alloc)
+    }
+}
+
+impl<K, V, S> FromOcamlRep for OcamlHashtbl<K, V, S>
+where
+    K: FromOcamlRep + Eq + Hash,
+    V: FromOcamlRep,
+    S: BuildHasher + Default,
+{
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        let mut map = HashMap::default();
+        for (key, value) in vec_from_ocaml_hashtbl(value)? {
+            // The current binding for each key is visited first.
+            map.entry(key).or_insert(value);
+        }
+        Ok(Self(map))
+    }
+}
+
+impl<'a, K, V, S> FromOcamlRepIn<'a> for OcamlHashtbl<K, V, S>
+where
+    K: FromOcamlRep + Eq + Hash,
+    V: FromOcamlRep,
+    S: BuildHasher + Default,
+{
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &'a Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::*;
+    use crate::Arena;
+
+    #[test]
+    fn bindings_are_in_hashed_buckets() {
+        let arena = Arena::new();
+        let table: OcamlHashtbl<String, isize> = (0..40).map(|i| (format!("key{i}"), i)).collect();
+        let value = arena.add(&table);
+
+        let record = value.as_block().unwrap();
+        assert_eq!(record.size(), 4);
+        assert_eq!(record[0].as_int(), Some(40));
+        assert_eq!(record[2].as_int(), Some(0));
+        assert_eq!(record[3].as_int(), Some(64));
+        let data = record[1].as_block().unwrap();
+        assert_eq!(data.size(), 64);
+        let mut count = 0;
+        for (i, &bucket) in data.as_values().unwrap().iter().enumerate() {
+            let mut bucket = bucket;
+            while let Some(cons) = bucket.as_block() {
+                assert_eq!(key_index(0, 64, cons[0]), i);
+                count += 1;
+                bucket = cons[2];
+            }
+        }
+        assert_eq!(count, 40);
+
+        assert_eq!(OcamlHashtbl::from_ocamlrep(value), Ok(table));
+    }
+
+    #[test]
+    fn empty_table() {
+        let arena = Arena::new();
+        let table = OcamlHashtbl::<isize, isize>::new();
+        let value = arena.add(&table);
+        let record = value.as_block().unwrap();
+        assert_eq!(record[1].as_block().unwrap().size(), MIN_BUCKETS);
+        assert_eq!(
+            vec_from_ocaml_hashtbl::<isize, isize>(value),
+            Ok(Vec::new())
+        );
+    }
+
+    #[test]
+    fn current_binding_shadows_older_bindings() {
+        let arena = Arena::new();
+        // As with `Hashtbl.add`, later bindings shadow earlier ones.
+        let pairs = [(1isize, "old"), (2, "two"), (1, "new")];
+        let value = iter_to_ocaml_hashtbl(&pairs, &arena);
+        let bindings = vec_from_ocaml_hashtbl::<isize, String>(value).unwrap();
+        assert_eq!(bindings.len(), 3);
+        let new = bindings.iter().position(|(_, v)| v == "new").unwrap();
+        let old = bindings.iter().position(|(_, v)| v == "old").unwrap();
+        assert!(new < old);
+        let table = OcamlHashtbl::<isize, String>::from_ocamlrep(value).unwrap();
+        assert_eq!(table[&1], "new");
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn from_index_map() {
+        let arena = Arena::new();
+        let map: IndexMap<&str, bool> = [("a", true), ("b", false)].into_iter().collect();
+        let value = iter_to_ocaml_hashtbl(&map, &arena);
+        let table = OcamlHashtbl::<String, bool>::from_ocamlrep(value).unwrap();
+        assert_eq!(table.len(), 2);
+        assert!(table["a"]);
+        assert!(!table["b"]);
+    }
+
+    #[test]
+    fn bad_bucket() {
+        let arena = Arena::new();
+        let data = crate::OcamlArray::from(vec![1isize]);
+        let record = (0isize, data, 0isize, 16isize);
+        let value = arena.add(&record);
+        assert_eq!(
+            vec_from_ocaml_hashtbl::<isize, isize>(value),
+            Err(FromError::NullaryVariantTagOutOfRange { max: 0, actual: 1 })
+        );
+    }
+}
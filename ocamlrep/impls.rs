@@ -9,15 +9,23 @@ use std::cell::Cell;
 use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::LinkedList;
+use std::collections::VecDeque;
 use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::hash::BuildHasher;
 use std::hash::Hash;
 use std::mem::size_of;
+use std::num::NonZero;
+use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bstr::BStr;
 use bstr::BString;
@@ -31,10 +39,14 @@ use crate::Allocator;
 use crate::FromError;
 use crate::FromOcamlRep;
 use crate::FromOcamlRepIn;
+use crate::FromOcamlRepRef;
 use crate::ToOcamlRep;
 use crate::Value;
 use crate::block;
 use crate::from;
+use crate::iter;
+use crate::iter::ListIter;
+use crate::shared;
 
 macro_rules! trivial_from_in_impl {
     ($ty:ty) => {
@@ -46,6 +58,16 @@ macro_rules! trivial_from_in_impl {
     };
 }
 
+macro_rules! trivial_from_ref_impl {
+    ($ty:ty) => {
+        impl<'v> FromOcamlRepRef<'v> for $ty {
+            fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+                Self::from_ocamlrep(value)
+            }
+        }
+    };
+}
+
 impl ToOcamlRep for () {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
         Value::int(0)
@@ -62,6 +84,15 @@ impl FromOcamlRep for () {
 }
 
 trivial_from_in_impl!(());
+trivial_from_ref_impl!(());
+
+/// Borrows the value itself, for callers which want to inspect part of a value
+/// directly.
+impl<'v> FromOcamlRepRef<'v> for Value<'v> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        Ok(value)
+    }
+}
 
 /// Represents an integer in the range [-2^(n-2); 2^(n-2)[,
 /// which can be safely converted to OCaml int without changing
@@ -128,6 +159,7 @@ impl FromOcamlRep for isize {
 }
 
 trivial_from_in_impl!(isize);
+trivial_from_ref_impl!(isize);
 
 impl ToOcamlRep for usize {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
@@ -142,6 +174,7 @@ impl FromOcamlRep for usize {
 }
 
 trivial_from_in_impl!(usize);
+trivial_from_ref_impl!(usize);
 
 impl ToOcamlRep for i64 {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
@@ -156,6 +189,7 @@ impl FromOcamlRep for i64 {
 }
 
 trivial_from_in_impl!(i64);
+trivial_from_ref_impl!(i64);
 
 impl ToOcamlRep for u64 {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
@@ -170,6 +204,7 @@ impl FromOcamlRep for u64 {
 }
 
 trivial_from_in_impl!(u64);
+trivial_from_ref_impl!(u64);
 
 impl ToOcamlRep for i32 {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
@@ -184,6 +219,7 @@ impl FromOcamlRep for i32 {
 }
 
 trivial_from_in_impl!(i32);
+trivial_from_ref_impl!(i32);
 
 impl ToOcamlRep for u32 {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
@@ -198,6 +234,57 @@ impl FromOcamlRep for u32 {
 }
 
 trivial_from_in_impl!(u32);
+trivial_from_ref_impl!(u32);
+
+// NB: There is no impl for `u8`, since `Vec<u8>` and `[u8]` are converted to
+// OCaml bytes, and an impl for `u8` would overlap with the impls converting
+// `Vec<T>` and `[T]` to lists.
+//
+// `i128` and `u128` values which do not fit in an OCaml int are truncated to
+// their low-order bits (like `Int64.to_int`) rather than boxed, so that every
+// value converts without panicking. Truncated values are not recovered by
+// `from_ocamlrep`, which checks that the int is in range.
+macro_rules! int_impls {
+    ($($ty:ty),*) => {$(
+        impl ToOcamlRep for $ty {
+            fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
+                Value::int(*self as isize)
+            }
+        }
+
+        impl FromOcamlRep for $ty {
+            fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+                Ok(from::expect_int(value)?.try_into()?)
+            }
+        }
+
+        trivial_from_in_impl!($ty);
+        trivial_from_ref_impl!($ty);
+    )*};
+}
+
+int_impls!(i8, i16, u16, i128, u128);
+
+macro_rules! nonzero_impls {
+    ($($ty:ty),*) => {$(
+        impl ToOcamlRep for NonZero<$ty> {
+            fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+                alloc.add_copy(self.get())
+            }
+        }
+
+        impl FromOcamlRep for NonZero<$ty> {
+            fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+                Ok(NonZero::try_from(<$ty>::from_ocamlrep(value)?)?)
+            }
+        }
+
+        trivial_from_in_impl!(NonZero<$ty>);
+        trivial_from_ref_impl!(NonZero<$ty>);
+    )*};
+}
+
+nonzero_impls!(isize, usize, i8, i16, i32, i64, i128, u16, u32, u64, u128);
 
 impl ToOcamlRep for bool {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
@@ -216,6 +303,7 @@ impl FromOcamlRep for bool {
 }
 
 trivial_from_in_impl!(bool);
+trivial_from_ref_impl!(bool);
 
 impl ToOcamlRep for char {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> Value<'a> {
@@ -236,6 +324,7 @@ impl FromOcamlRep for char {
 }
 
 trivial_from_in_impl!(char);
+trivial_from_ref_impl!(char);
 
 impl ToOcamlRep for f64 {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
@@ -255,6 +344,24 @@ impl FromOcamlRep for f64 {
 }
 
 trivial_from_in_impl!(f64);
+trivial_from_ref_impl!(f64);
+
+impl ToOcamlRep for f32 {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.add_copy(f64::from(*self))
+    }
+}
+
+/// OCaml floats are double-precision, so the value is rounded to the nearest
+/// `f32`.
+impl FromOcamlRep for f32 {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(f64::from_ocamlrep(value)? as f32)
+    }
+}
+
+trivial_from_in_impl!(f32);
+trivial_from_ref_impl!(f32);
 
 impl<T: ToOcamlRep + Sized> ToOcamlRep for Box<T> {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
@@ -268,11 +375,18 @@ impl<T: FromOcamlRep + Sized> FromOcamlRep for Box<T> {
     }
 }
 
+impl<'v, T: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for Box<T> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        Ok(Box::new(T::from_ocamlrep_ref(value)?))
+    }
+}
+
 impl<T: ToOcamlRep + Sized> ToOcamlRep for &'_ T {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
         alloc.memoized(
             *self as *const T as *const usize as usize,
             size_of::<T>(),
+            crate::non_static_type_id::<T>(),
             |alloc| (**self).to_ocamlrep(alloc),
         )
     }
@@ -280,8 +394,10 @@ impl<T: ToOcamlRep + Sized> ToOcamlRep for &'_ T {
 
 impl<'a, T: FromOcamlRepIn<'a>> FromOcamlRepIn<'a> for &'a T {
     fn from_ocamlrep_in(value: Value<'_>, alloc: &'a Bump) -> Result<Self, FromError> {
-        // NB: We don't get any sharing this way.
-        Ok(alloc.alloc(T::from_ocamlrep_in(value, alloc)?))
+        // NB: We only get sharing this way in `from_ocamlrep_in_shared`.
+        shared::memoized_in(value, alloc, || {
+            Ok(alloc.alloc(T::from_ocamlrep_in(value, alloc)?))
+        })
     }
 }
 
@@ -290,6 +406,7 @@ impl<T: ToOcamlRep + Sized> ToOcamlRep for Rc<T> {
         alloc.memoized(
             self.as_ref() as *const T as usize,
             size_of::<T>(),
+            crate::non_static_type_id::<T>(),
             |alloc| alloc.add(self.as_ref()),
         )
     }
@@ -297,8 +414,8 @@ impl<T: ToOcamlRep + Sized> ToOcamlRep for Rc<T> {
 
 impl<T: FromOcamlRep> FromOcamlRep for Rc<T> {
     fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
-        // NB: We don't get any sharing this way.
-        Ok(Rc::new(T::from_ocamlrep(value)?))
+        // NB: We only get sharing this way in `from_ocamlrep_shared`.
+        shared::memoized(value, || Ok(Rc::new(T::from_ocamlrep(value)?)))
     }
 }
 
@@ -307,6 +424,7 @@ impl<T: ToOcamlRep + Sized> ToOcamlRep for Arc<T> {
         alloc.memoized(
             self.as_ref() as *const T as usize,
             size_of::<T>(),
+            crate::non_static_type_id::<T>(),
             |alloc| alloc.add(self.as_ref()),
         )
     }
@@ -314,8 +432,8 @@ impl<T: ToOcamlRep + Sized> ToOcamlRep for Arc<T> {
 
 impl<T: FromOcamlRep> FromOcamlRep for Arc<T> {
     fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
-        // NB: We don't get any sharing this way.
-        Ok(Arc::new(T::from_ocamlrep(value)?))
+        // NB: We only get sharing this way in `from_ocamlrep_shared`.
+        shared::memoized(value, || Ok(Arc::new(T::from_ocamlrep(value)?)))
     }
 }
 
@@ -419,6 +537,18 @@ impl<'a, T: FromOcamlRepIn<'a>> FromOcamlRepIn<'a> for Option<T> {
     }
 }
 
+impl<'v, T: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for Option<T> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        if value.is_int() {
+            let _ = from::expect_nullary_variant(value, 0)?;
+            Ok(None)
+        } else {
+            let block = from::expect_block_with_size_and_tag(value, 1, 0)?;
+            Ok(Some(from::field_ref(block, 0)?))
+        }
+    }
+}
+
 impl<T: ToOcamlRep, E: ToOcamlRep> ToOcamlRep for Result<T, E> {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
         match self {
@@ -458,17 +588,84 @@ impl<'a, T: FromOcamlRepIn<'a>, E: FromOcamlRepIn<'a>> FromOcamlRepIn<'a> for Re
     }
 }
 
+impl<'v, T: FromOcamlRepRef<'v>, E: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for Result<T, E> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let block = from::expect_block(value)?;
+        match block.tag() {
+            0 => Ok(Ok(from::field_ref(block, 0)?)),
+            1 => Ok(Err(from::field_ref(block, 0)?)),
+            t => Err(FromError::BlockTagOutOfRange { max: 1, actual: t }),
+        }
+    }
+}
+
+/// Converted to a pair `(start, end)`.
+impl<T: ToOcamlRep> ToOcamlRep for Range<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        let mut block = alloc.block_with_size(2);
+        alloc.set_field(&mut block, 0, alloc.add(&self.start));
+        alloc.set_field(&mut block, 1, alloc.add(&self.end));
+        block.build()
+    }
+}
+
+impl<T: FromOcamlRep> FromOcamlRep for Range<T> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        let (start, end) = <(T, T)>::from_ocamlrep(value)?;
+        Ok(start..end)
+    }
+}
+
+impl<'a, T: FromOcamlRepIn<'a>> FromOcamlRepIn<'a> for Range<T> {
+    fn from_ocamlrep_in(value: Value<'_>, alloc: &'a Bump) -> Result<Self, FromError> {
+        let (start, end) = <(T, T)>::from_ocamlrep_in(value, alloc)?;
+        Ok(start..end)
+    }
+}
+
+impl<'v, T: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for Range<T> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let (start, end) = <(T, T)>::from_ocamlrep_ref(value)?;
+        Ok(start..end)
+    }
+}
+
+/// Converted to a float number of seconds (as in OCaml's `Unix` module).
+impl ToOcamlRep for Duration {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.add_copy(self.as_secs_f64())
+    }
+}
+
+impl FromOcamlRep for Duration {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(Duration::try_from_secs_f64(f64::from_ocamlrep(value)?)?)
+    }
+}
+
+trivial_from_in_impl!(Duration);
+trivial_from_ref_impl!(Duration);
+
 impl<T: ToOcamlRep> ToOcamlRep for [T] {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
-        let mut hd = alloc.add(&());
-        for val in self.iter().rev() {
-            let mut block = alloc.block_with_size(2);
-            alloc.set_field(&mut block, 0, alloc.add(val));
-            alloc.set_field(&mut block, 1, hd);
-            hd = block.build();
-        }
-        hd
+        rev_iter_to_ocaml_list(self.iter().rev(), alloc)
+    }
+}
+
+/// Build an OCaml list containing the items emitted by the given iterator, in
+/// reverse order.
+fn rev_iter_to_ocaml_list<'a, A: Allocator, T: ToOcamlRep + 'a>(
+    iter: impl Iterator<Item = &'a T>,
+    alloc: &'a A,
+) -> Value<'a> {
+    let mut hd = alloc.add(&());
+    for val in iter {
+        let mut block = alloc.block_with_size(2);
+        alloc.set_field(&mut block, 0, alloc.add(val));
+        alloc.set_field(&mut block, 1, hd);
+        hd = block.build();
     }
+    hd
 }
 
 impl<T: ToOcamlRep> ToOcamlRep for &'_ [T] {
@@ -476,6 +673,7 @@ impl<T: ToOcamlRep> ToOcamlRep for &'_ [T] {
         alloc.memoized(
             self.as_ptr() as usize,
             std::mem::size_of_val(*self),
+            crate::non_static_type_id::<[T]>(),
             |alloc| (**self).to_ocamlrep(alloc),
         )
     }
@@ -483,29 +681,50 @@ impl<T: ToOcamlRep> ToOcamlRep for &'_ [T] {
 
 impl<'a, T: FromOcamlRepIn<'a>> FromOcamlRepIn<'a> for &'a [T] {
     fn from_ocamlrep_in(value: Value<'_>, alloc: &'a Bump) -> Result<Self, FromError> {
-        let mut len = 0usize;
-        let mut hd = value;
-        while !hd.is_int() {
-            let block = from::expect_tuple(hd, 2)?;
-            len += 1;
-            hd = block[1];
-        }
-        let hd = hd.as_int().unwrap();
-        if hd != 0 {
-            return Err(FromError::ExpectedUnit(hd));
-        }
-
+        // Validate the list and count its elements first, so that we allocate
+        // exactly enough space in the arena.
+        let len = ListIter::new(value).try_fold(0usize, |len, hd| hd.map(|_| len + 1))?;
         let mut vec = bumpalo::collections::Vec::with_capacity_in(len, alloc);
-        let mut hd = value;
-        while !hd.is_int() {
-            let block = from::expect_tuple(hd, 2).unwrap();
-            vec.push(from::field_in(block, 0, alloc)?);
-            hd = block[1];
+        for (idx, hd) in ListIter::new(value).enumerate() {
+            vec.push(
+                T::from_ocamlrep_in(hd?, alloc)
+                    .map_err(|e| FromError::ErrorInField(idx, Box::new(e)))?,
+            );
         }
         Ok(vec.into_bump_slice())
     }
 }
 
+/// Converted like `[T]`, so `Cow<[u8]>` is converted to OCaml bytes, and other
+/// slices to lists.
+impl<T: Clone> ToOcamlRep for Cow<'_, [T]>
+where
+    [T]: ToOcamlRep,
+{
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        let slice: &[T] = self.borrow();
+        alloc.add(slice)
+    }
+}
+
+impl<T: Clone> FromOcamlRep for Cow<'_, [T]>
+where
+    Vec<T>: FromOcamlRep,
+{
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(Cow::Owned(Vec::from_ocamlrep(value)?))
+    }
+}
+
+impl<'a, T: Clone> FromOcamlRepIn<'a> for Cow<'_, [T]>
+where
+    Vec<T>: FromOcamlRep,
+{
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &'a Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
 impl<T: ToOcamlRep> ToOcamlRep for Box<[T]> {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
         (**self).to_ocamlrep(alloc)
@@ -527,18 +746,7 @@ impl<T: ToOcamlRep> ToOcamlRep for Vec<T> {
 
 impl<T: FromOcamlRep> FromOcamlRep for Vec<T> {
     fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
-        let mut vec = vec![];
-        let mut hd = value;
-        while !hd.is_int() {
-            let block = from::expect_tuple(hd, 2)?;
-            vec.push(from::field(block, 0)?);
-            hd = block[1];
-        }
-        let hd = hd.as_int().unwrap();
-        if hd != 0 {
-            return Err(FromError::ExpectedUnit(hd));
-        }
-        Ok(vec)
+        iter::list_iter(value).collect()
     }
 }
 
@@ -548,6 +756,120 @@ impl<'a, T: FromOcamlRep> FromOcamlRepIn<'a> for Vec<T> {
     }
 }
 
+impl<'v, T: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for Vec<T> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        iter::list_iter_ref(value).collect()
+    }
+}
+
+impl<T: ToOcamlRep, const N: usize> ToOcamlRep for [T; N] {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.add(self.as_slice())
+    }
+}
+
+impl<T: FromOcamlRep, const N: usize> FromOcamlRep for [T; N] {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        array_from_ocaml_list(value, T::from_ocamlrep)
+    }
+}
+
+impl<'a, T: FromOcamlRepIn<'a>, const N: usize> FromOcamlRepIn<'a> for [T; N] {
+    fn from_ocamlrep_in(value: Value<'_>, alloc: &'a Bump) -> Result<Self, FromError> {
+        array_from_ocaml_list(value, |value| T::from_ocamlrep_in(value, alloc))
+    }
+}
+
+impl<'v, T: FromOcamlRepRef<'v>, const N: usize> FromOcamlRepRef<'v> for [T; N] {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        array_from_ocaml_list(value, T::from_ocamlrep_ref)
+    }
+}
+
+fn array_from_ocaml_list<'v, T, const N: usize>(
+    value: Value<'v>,
+    convert: impl Fn(Value<'v>) -> Result<T, FromError>,
+) -> Result<[T; N], FromError> {
+    let vec = ListIter::new(value)
+        .enumerate()
+        .map(|(idx, hd)| convert(hd?).map_err(|e| FromError::ErrorInField(idx, Box::new(e))))
+        .collect::<Result<Vec<T>, FromError>>()?;
+    let actual = vec.len();
+    vec.try_into().map_err(|_| FromError::WrongListLength {
+        expected: N,
+        actual,
+    })
+}
+
+impl<T: ToOcamlRep> ToOcamlRep for VecDeque<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        rev_iter_to_ocaml_list(self.iter().rev(), alloc)
+    }
+}
+
+impl<T: FromOcamlRep> FromOcamlRep for VecDeque<T> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        iter::list_iter(value).collect()
+    }
+}
+
+impl<'a, T: FromOcamlRep> FromOcamlRepIn<'a> for VecDeque<T> {
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &'a Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
+impl<'v, T: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for VecDeque<T> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        iter::list_iter_ref(value).collect()
+    }
+}
+
+impl<T: ToOcamlRep> ToOcamlRep for LinkedList<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        rev_iter_to_ocaml_list(self.iter().rev(), alloc)
+    }
+}
+
+impl<T: FromOcamlRep> FromOcamlRep for LinkedList<T> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        iter::list_iter(value).collect()
+    }
+}
+
+impl<'a, T: FromOcamlRep> FromOcamlRepIn<'a> for LinkedList<T> {
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &'a Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
+impl<'v, T: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for LinkedList<T> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        iter::list_iter_ref(value).collect()
+    }
+}
+
+/// Converted to a list sorted in ascending order.
+impl<T: ToOcamlRep + Ord> ToOcamlRep for BinaryHeap<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        let mut vec: Vec<&'a T> = self.iter().collect();
+        vec.sort_unstable();
+        rev_iter_to_ocaml_list(vec.into_iter().rev(), alloc)
+    }
+}
+
+impl<T: FromOcamlRep + Ord> FromOcamlRep for BinaryHeap<T> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        iter::list_iter(value).collect()
+    }
+}
+
+impl<'a, T: FromOcamlRep + Ord> FromOcamlRepIn<'a> for BinaryHeap<T> {
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &'a Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
 impl<K: ToOcamlRep + Ord, V: ToOcamlRep> ToOcamlRep for BTreeMap<K, V> {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
         if self.is_empty() {
@@ -774,20 +1096,28 @@ impl<K: ToOcamlRep + Ord, V: ToOcamlRep, S: BuildHasher + Default> ToOcamlRep
     for IndexMap<K, V, S>
 {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
-        if self.is_empty() {
-            return Value::int(0);
-        }
-        let mut vec: Vec<(&'a K, &'a V)> = self.iter().collect();
-        vec.sort_unstable_by_key(|&(k, _)| k);
-        let len = vec.len();
-        let mut iter = vec.iter().map(|(k, v)| {
-            let k: &'a K = k;
-            let v: &'a V = v;
-            (k.to_ocamlrep(alloc), v.to_ocamlrep(alloc))
-        });
-        let (res, _) = sorted_iter_to_ocaml_map(&mut iter, alloc, len);
-        res
+        unsorted_iter_to_ocaml_map(self.iter(), alloc)
+    }
+}
+
+/// Build an OCaml Map containing the key-value pairs emitted by the given
+/// iterator, which may emit them in any order, but must emit each key only
+/// once.
+fn unsorted_iter_to_ocaml_map<'a, A: Allocator, K: ToOcamlRep + Ord + 'a, V: ToOcamlRep + 'a>(
+    iter: impl Iterator<Item = (&'a K, &'a V)>,
+    alloc: &'a A,
+) -> Value<'a> {
+    let mut vec: Vec<(&'a K, &'a V)> = iter.collect();
+    if vec.is_empty() {
+        return Value::int(0);
     }
+    vec.sort_unstable_by_key(|&(k, _)| k);
+    let len = vec.len();
+    let mut iter = vec
+        .into_iter()
+        .map(|(k, v)| (k.to_ocamlrep(alloc), v.to_ocamlrep(alloc)));
+    let (res, _) = sorted_iter_to_ocaml_map(&mut iter, alloc, len);
+    res
 }
 
 impl<K: FromOcamlRep + Ord + Hash, V: FromOcamlRep, S: BuildHasher + Default> FromOcamlRep
@@ -801,16 +1131,25 @@ impl<K: FromOcamlRep + Ord + Hash, V: FromOcamlRep, S: BuildHasher + Default> Fr
 
 impl<T: ToOcamlRep + Ord, S: BuildHasher + Default> ToOcamlRep for IndexSet<T, S> {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
-        if self.is_empty() {
-            return Value::int(0);
-        }
-        let mut vec: Vec<&'a T> = self.iter().collect();
-        vec.sort_unstable();
-        let len = vec.len();
-        let mut iter = vec.iter().copied().map(|x| x.to_ocamlrep(alloc));
-        let (res, _) = sorted_iter_to_ocaml_set(&mut iter, alloc, len);
-        res
+        unsorted_iter_to_ocaml_set(self.iter(), alloc)
+    }
+}
+
+/// Build an OCaml Set containing the items emitted by the given iterator, which
+/// may emit them in any order, but must emit each item only once.
+fn unsorted_iter_to_ocaml_set<'a, A: Allocator, T: ToOcamlRep + Ord + 'a>(
+    iter: impl Iterator<Item = &'a T>,
+    alloc: &'a A,
+) -> Value<'a> {
+    let mut vec: Vec<&'a T> = iter.collect();
+    if vec.is_empty() {
+        return Value::int(0);
     }
+    vec.sort_unstable();
+    let len = vec.len();
+    let mut iter = vec.into_iter().map(|x| x.to_ocamlrep(alloc));
+    let (res, _) = sorted_iter_to_ocaml_set(&mut iter, alloc, len);
+    res
 }
 
 impl<T: FromOcamlRep + Ord + Hash, S: BuildHasher + Default> FromOcamlRep for IndexSet<T, S> {
@@ -820,6 +1159,52 @@ impl<T: FromOcamlRep + Ord + Hash, S: BuildHasher + Default> FromOcamlRep for In
     }
 }
 
+/// Converted to an OCaml Map (like `BTreeMap`), so the keys must be `Ord`.
+impl<K: ToOcamlRep + Ord, V: ToOcamlRep, S> ToOcamlRep for HashMap<K, V, S> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        unsorted_iter_to_ocaml_map(self.iter(), alloc)
+    }
+}
+
+impl<K: FromOcamlRep + Eq + Hash, V: FromOcamlRep, S: BuildHasher + Default> FromOcamlRep
+    for HashMap<K, V, S>
+{
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        let vec = vec_from_ocaml_map(value)?;
+        Ok(vec.into_iter().collect())
+    }
+}
+
+impl<'a, K: FromOcamlRep + Eq + Hash, V: FromOcamlRep, S: BuildHasher + Default> FromOcamlRepIn<'a>
+    for HashMap<K, V, S>
+{
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &'a Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
+/// Converted to an OCaml Set (like `BTreeSet`), so the items must be `Ord`.
+impl<T: ToOcamlRep + Ord, S> ToOcamlRep for HashSet<T, S> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        unsorted_iter_to_ocaml_set(self.iter(), alloc)
+    }
+}
+
+impl<T: FromOcamlRep + Eq + Hash, S: BuildHasher + Default> FromOcamlRep for HashSet<T, S> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        let vec = vec_from_ocaml_set(value)?;
+        Ok(vec.into_iter().collect())
+    }
+}
+
+impl<'a, T: FromOcamlRep + Eq + Hash, S: BuildHasher + Default> FromOcamlRepIn<'a>
+    for HashSet<T, S>
+{
+    fn from_ocamlrep_in(value: Value<'_>, _alloc: &'a Bump) -> Result<Self, FromError> {
+        Self::from_ocamlrep(value)
+    }
+}
+
 #[cfg(unix)]
 impl ToOcamlRep for OsStr {
     // TODO: A Windows implementation would be nice, but what does the OCaml
@@ -848,6 +1233,14 @@ impl<'a> FromOcamlRepIn<'a> for &'a OsStr {
     }
 }
 
+#[cfg(unix)]
+impl<'v> FromOcamlRepRef<'v> for &'v OsStr {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(std::ffi::OsStr::from_bytes(bytes_from_ocamlrep(value)?))
+    }
+}
+
 #[cfg(unix)]
 impl ToOcamlRep for OsString {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
@@ -886,6 +1279,13 @@ impl<'a> FromOcamlRepIn<'a> for &'a Path {
     }
 }
 
+#[cfg(unix)]
+impl<'v> FromOcamlRepRef<'v> for &'v Path {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        Ok(Path::new(<&'v OsStr>::from_ocamlrep_ref(value)?))
+    }
+}
+
 #[cfg(unix)]
 impl ToOcamlRep for PathBuf {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
@@ -913,6 +1313,7 @@ impl FromOcamlRep for String {
 }
 
 trivial_from_in_impl!(String);
+trivial_from_ref_impl!(String);
 
 impl ToOcamlRep for Cow<'_, str> {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
@@ -927,6 +1328,63 @@ impl FromOcamlRep for Cow<'_, str> {
     }
 }
 
+impl<'v> FromOcamlRepRef<'v> for Cow<'v, str> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        Ok(Cow::Borrowed(str_from_ocamlrep(value)?))
+    }
+}
+
+impl ToOcamlRep for Box<str> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.add(&**self)
+    }
+}
+
+impl FromOcamlRep for Box<str> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(Box::from(str_from_ocamlrep(value)?))
+    }
+}
+
+trivial_from_in_impl!(Box<str>);
+trivial_from_ref_impl!(Box<str>);
+
+impl ToOcamlRep for Rc<str> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.memoized(
+            self.as_ptr() as usize,
+            self.len(),
+            crate::non_static_type_id::<str>(),
+            |alloc| alloc.add(&**self),
+        )
+    }
+}
+
+impl FromOcamlRep for Rc<str> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        // NB: We only get sharing this way in `from_ocamlrep_shared`.
+        shared::memoized(value, || Ok(Rc::from(str_from_ocamlrep(value)?)))
+    }
+}
+
+impl ToOcamlRep for Arc<str> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        alloc.memoized(
+            self.as_ptr() as usize,
+            self.len(),
+            crate::non_static_type_id::<str>(),
+            |alloc| alloc.add(&**self),
+        )
+    }
+}
+
+impl FromOcamlRep for Arc<str> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        // NB: We only get sharing this way in `from_ocamlrep_shared`.
+        shared::memoized(value, || Ok(Arc::from(str_from_ocamlrep(value)?)))
+    }
+}
+
 impl ToOcamlRep for str {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
         str_to_ocamlrep(self, alloc)
@@ -935,9 +1393,12 @@ impl ToOcamlRep for str {
 
 impl ToOcamlRep for &'_ str {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
-        alloc.memoized(self.as_bytes().as_ptr() as usize, self.len(), |alloc| {
-            (**self).to_ocamlrep(alloc)
-        })
+        alloc.memoized(
+            self.as_bytes().as_ptr() as usize,
+            self.len(),
+            crate::non_static_type_id::<str>(),
+            |alloc| (**self).to_ocamlrep(alloc),
+        )
     }
 }
 
@@ -947,6 +1408,12 @@ impl<'a> FromOcamlRepIn<'a> for &'a str {
     }
 }
 
+impl<'v> FromOcamlRepRef<'v> for &'v str {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        str_from_ocamlrep(value)
+    }
+}
+
 /// Allocate an OCaml string using the given allocator and copy the given string
 /// slice into it.
 pub fn str_to_ocamlrep<'a, A: Allocator>(s: &str, alloc: &'a A) -> Value<'a> {
@@ -991,9 +1458,12 @@ impl ToOcamlRep for BStr {
 
 impl ToOcamlRep for &'_ BStr {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
-        alloc.memoized(self.as_ptr() as usize, self.len(), |alloc| {
-            (**self).to_ocamlrep(alloc)
-        })
+        alloc.memoized(
+            self.as_ptr() as usize,
+            self.len(),
+            crate::non_static_type_id::<BStr>(),
+            |alloc| (**self).to_ocamlrep(alloc),
+        )
     }
 }
 
@@ -1004,6 +1474,12 @@ impl<'a> FromOcamlRepIn<'a> for &'a BStr {
     }
 }
 
+impl<'v> FromOcamlRepRef<'v> for &'v BStr {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        Ok(bytes_from_ocamlrep(value)?.into())
+    }
+}
+
 impl ToOcamlRep for [u8] {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
         bytes_to_ocamlrep(self, alloc)
@@ -1012,9 +1488,12 @@ impl ToOcamlRep for [u8] {
 
 impl ToOcamlRep for &'_ [u8] {
     fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
-        alloc.memoized(self.as_ptr() as usize, self.len(), |alloc| {
-            (**self).to_ocamlrep(alloc)
-        })
+        alloc.memoized(
+            self.as_ptr() as usize,
+            self.len(),
+            crate::non_static_type_id::<[u8]>(),
+            |alloc| (**self).to_ocamlrep(alloc),
+        )
     }
 }
 
@@ -1024,6 +1503,12 @@ impl<'a> FromOcamlRepIn<'a> for &'a [u8] {
     }
 }
 
+impl<'v> FromOcamlRepRef<'v> for &'v [u8] {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        bytes_from_ocamlrep(value)
+    }
+}
+
 /// Allocate an OCaml string using the given allocator and copy the given byte
 /// slice into it.
 pub fn bytes_to_ocamlrep<'a, A: Allocator>(bytes: &[u8], alloc: &'a A) -> Value<'a> {
@@ -1086,6 +1571,19 @@ where
     }
 }
 
+impl<'v, T0, T1> FromOcamlRepRef<'v> for (T0, T1)
+where
+    T0: FromOcamlRepRef<'v>,
+    T1: FromOcamlRepRef<'v>,
+{
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let block = from::expect_tuple(value, 2)?;
+        let f0: T0 = from::field_ref(block, 0)?;
+        let f1: T1 = from::field_ref(block, 1)?;
+        Ok((f0, f1))
+    }
+}
+
 impl<T0, T1, T2> ToOcamlRep for (T0, T1, T2)
 where
     T0: ToOcamlRep,
@@ -1131,6 +1629,21 @@ where
     }
 }
 
+impl<'v, T0, T1, T2> FromOcamlRepRef<'v> for (T0, T1, T2)
+where
+    T0: FromOcamlRepRef<'v>,
+    T1: FromOcamlRepRef<'v>,
+    T2: FromOcamlRepRef<'v>,
+{
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let block = from::expect_tuple(value, 3)?;
+        let f0: T0 = from::field_ref(block, 0)?;
+        let f1: T1 = from::field_ref(block, 1)?;
+        let f2: T2 = from::field_ref(block, 2)?;
+        Ok((f0, f1, f2))
+    }
+}
+
 impl<T0, T1, T2, T3> ToOcamlRep for (T0, T1, T2, T3)
 where
     T0: ToOcamlRep,
@@ -1182,6 +1695,23 @@ where
     }
 }
 
+impl<'v, T0, T1, T2, T3> FromOcamlRepRef<'v> for (T0, T1, T2, T3)
+where
+    T0: FromOcamlRepRef<'v>,
+    T1: FromOcamlRepRef<'v>,
+    T2: FromOcamlRepRef<'v>,
+    T3: FromOcamlRepRef<'v>,
+{
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let block = from::expect_tuple(value, 4)?;
+        let f0: T0 = from::field_ref(block, 0)?;
+        let f1: T1 = from::field_ref(block, 1)?;
+        let f2: T2 = from::field_ref(block, 2)?;
+        let f3: T3 = from::field_ref(block, 3)?;
+        Ok((f0, f1, f2, f3))
+    }
+}
+
 impl<T0, T1, T2, T3, T4> ToOcamlRep for (T0, T1, T2, T3, T4)
 where
     T0: ToOcamlRep,
@@ -1239,6 +1769,25 @@ where
     }
 }
 
+impl<'v, T0, T1, T2, T3, T4> FromOcamlRepRef<'v> for (T0, T1, T2, T3, T4)
+where
+    T0: FromOcamlRepRef<'v>,
+    T1: FromOcamlRepRef<'v>,
+    T2: FromOcamlRepRef<'v>,
+    T3: FromOcamlRepRef<'v>,
+    T4: FromOcamlRepRef<'v>,
+{
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let block = from::expect_tuple(value, 5)?;
+        let f0: T0 = from::field_ref(block, 0)?;
+        let f1: T1 = from::field_ref(block, 1)?;
+        let f2: T2 = from::field_ref(block, 2)?;
+        let f3: T3 = from::field_ref(block, 3)?;
+        let f4: T4 = from::field_ref(block, 4)?;
+        Ok((f0, f1, f2, f3, f4))
+    }
+}
+
 impl<T0, T1, T2, T3, T4, T5> ToOcamlRep for (T0, T1, T2, T3, T4, T5)
 where
     T0: ToOcamlRep,
@@ -1302,6 +1851,27 @@ where
     }
 }
 
+impl<'v, T0, T1, T2, T3, T4, T5> FromOcamlRepRef<'v> for (T0, T1, T2, T3, T4, T5)
+where
+    T0: FromOcamlRepRef<'v>,
+    T1: FromOcamlRepRef<'v>,
+    T2: FromOcamlRepRef<'v>,
+    T3: FromOcamlRepRef<'v>,
+    T4: FromOcamlRepRef<'v>,
+    T5: FromOcamlRepRef<'v>,
+{
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let block = from::expect_tuple(value, 6)?;
+        let f0: T0 = from::field_ref(block, 0)?;
+        let f1: T1 = from::field_ref(block, 1)?;
+        let f2: T2 = from::field_ref(block, 2)?;
+        let f3: T3 = from::field_ref(block, 3)?;
+        let f4: T4 = from::field_ref(block, 4)?;
+        let f5: T5 = from::field_ref(block, 5)?;
+        Ok((f0, f1, f2, f3, f4, f5))
+    }
+}
+
 impl<T0, T1, T2, T3, T4, T5, T6> ToOcamlRep for (T0, T1, T2, T3, T4, T5, T6)
 where
     T0: ToOcamlRep,
@@ -1371,6 +1941,29 @@ where
     }
 }
 
+impl<'v, T0, T1, T2, T3, T4, T5, T6> FromOcamlRepRef<'v> for (T0, T1, T2, T3, T4, T5, T6)
+where
+    T0: FromOcamlRepRef<'v>,
+    T1: FromOcamlRepRef<'v>,
+    T2: FromOcamlRepRef<'v>,
+    T3: FromOcamlRepRef<'v>,
+    T4: FromOcamlRepRef<'v>,
+    T5: FromOcamlRepRef<'v>,
+    T6: FromOcamlRepRef<'v>,
+{
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let block = from::expect_tuple(value, 7)?;
+        let f0: T0 = from::field_ref(block, 0)?;
+        let f1: T1 = from::field_ref(block, 1)?;
+        let f2: T2 = from::field_ref(block, 2)?;
+        let f3: T3 = from::field_ref(block, 3)?;
+        let f4: T4 = from::field_ref(block, 4)?;
+        let f5: T5 = from::field_ref(block, 5)?;
+        let f6: T6 = from::field_ref(block, 6)?;
+        Ok((f0, f1, f2, f3, f4, f5, f6))
+    }
+}
+
 impl<T0, T1, T2, T3, T4, T5, T6, T7> ToOcamlRep for (T0, T1, T2, T3, T4, T5, T6, T7)
 where
     T0: ToOcamlRep,
@@ -1445,3 +2038,28 @@ where
         Ok((f0, f1, f2, f3, f4, f5, f6, f7))
     }
 }
+
+impl<'v, T0, T1, T2, T3, T4, T5, T6, T7> FromOcamlRepRef<'v> for (T0, T1, T2, T3, T4, T5, T6, T7)
+where
+    T0: FromOcamlRepRef<'v>,
+    T1: FromOcamlRepRef<'v>,
+    T2: FromOcamlRepRef<'v>,
+    T3: FromOcamlRepRef<'v>,
+    T4: FromOcamlRepRef<'v>,
+    T5: FromOcamlRepRef<'v>,
+    T6: FromOcamlRepRef<'v>,
+    T7: FromOcamlRepRef<'v>,
+{
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        let block = from::expect_tuple(value, 8)?;
+        let f0: T0 = from::field_ref(block, 0)?;
+        let f1: T1 = from::field_ref(block, 1)?;
+        let f2: T2 = from::field_ref(block, 2)?;
+        let f3: T3 = from::field_ref(block, 3)?;
+        let f4: T4 = from::field_ref(block, 4)?;
+        let f5: T5 = from::field_ref(block, 5)?;
+        let f6: T6 = from::field_ref(block, 6)?;
+        let f7: T7 = from::field_ref(block, 7)?;
+        Ok((f0, f1, f2, f3, f4, f5, f6, f7))
+    }
+}
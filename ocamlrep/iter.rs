@@ -0,0 +1,257 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Lazy iterators over the elements of OCaml lists and arrays.
+//!
+//! These allow callers to consume a large OCaml list or array one element at a
+//! time (e.g., to insert each element into a `HashSet`, or send it over a
+//! channel) without first converting the whole list into a `Vec`.
+
+use bumpalo::Bump;
+
+use crate::FromError;
+use crate::FromOcamlRep;
+use crate::FromOcamlRepIn;
+use crate::FromOcamlRepRef;
+use crate::Value;
+use crate::from;
+
+/// An iterator over the elements of an OCaml list.
+///
+/// The list is validated as it is traversed: if a cons cell is malformed, or
+/// the list is not terminated by `[]`, the iterator yields an error and then
+/// terminates.
+#[derive(Clone, Debug)]
+pub struct ListIter<'a> {
+    next: Option<Value<'a>>,
+}
+
+impl<'a> ListIter<'a> {
+    pub fn new(list: Value<'a>) -> Self {
+        Self { next: Some(list) }
+    }
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = Result<Value<'a>, FromError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hd = self.next.take()?;
+        if let Some(hd) = hd.as_int() {
+            if hd != 0 {
+                return Some(Err(FromError::ExpectedUnit(hd)));
+            }
+            return None;
+        }
+        match from::expect_tuple(hd, 2) {
+            Ok(block) => {
+                self.next = Some(block[1]);
+                Some(Ok(block[0]))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl std::iter::FusedIterator for ListIter<'_> {}
+
+/// An iterator over the elements of an OCaml array (a block with tag 0).
+///
+/// Float arrays (blocks with tag `DOUBLE_ARRAY_TAG`) contain unboxed floats
+/// rather than values, so they are not supported by `ArrayIter`. Use
+/// `Value::as_double_array` instead.
+#[derive(Clone, Debug)]
+pub struct ArrayIter<'a> {
+    fields: std::slice::Iter<'a, Value<'a>>,
+}
+
+impl<'a> ArrayIter<'a> {
+    pub fn new(array: Value<'a>) -> Result<Self, FromError> {
+        let block = from::expect_block(array)?;
+        from::expect_block_tag(block, 0)?;
+        Ok(Self {
+            fields: block.as_values().unwrap().iter(),
+        })
+    }
+}
+
+impl<'a> Iterator for ArrayIter<'a> {
+    type Item = Value<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fields.next().copied()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.fields.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for ArrayIter<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.fields.next_back().copied()
+    }
+}
+
+impl ExactSizeIterator for ArrayIter<'_> {}
+
+impl std::iter::FusedIterator for ArrayIter<'_> {}
+
+/// Iterate over the elements of the given OCaml list, converting each to `T`.
+///
+/// Conversion errors are reported with the index of the offending element (as
+/// in `impl FromOcamlRep for Vec<T>`). After yielding an error, the iterator
+/// terminates.
+pub fn list_iter<'a, T: FromOcamlRep>(
+    list: Value<'a>,
+) -> impl Iterator<Item = Result<T, FromError>> {
+    convert_list(list, T::from_ocamlrep)
+}
+
+/// Like `list_iter`, but converts each element using `FromOcamlRepRef`, so
+/// that elements may borrow from the list.
+pub fn list_iter_ref<'a, T: FromOcamlRepRef<'a>>(
+    list: Value<'a>,
+) -> impl Iterator<Item = Result<T, FromError>> {
+    convert_list(list, T::from_ocamlrep_ref)
+}
+
+/// Like `list_iter`, but converts each element using `FromOcamlRepIn`, so that
+/// elements may be allocated in `alloc`.
+pub fn list_iter_in<'a, 'b, T: FromOcamlRepIn<'b>>(
+    list: Value<'a>,
+    alloc: &'b Bump,
+) -> impl Iterator<Item = Result<T, FromError>> {
+    convert_list(list, move |value| T::from_ocamlrep_in(value, alloc))
+}
+
+/// Iterate over the elements of the given OCaml array, converting each to `T`.
+///
+/// If the value is not an array, the iterator yields a single error.
+/// Conversion errors are reported with the index of the offending element.
+pub fn array_iter<'a, T: FromOcamlRep>(
+    array: Value<'a>,
+) -> impl Iterator<Item = Result<T, FromError>> {
+    let (iter, err) = match ArrayIter::new(array) {
+        Ok(iter) => (Some(iter), None),
+        Err(e) => (None, Some(Err(e))),
+    };
+    err.into_iter()
+        .chain(iter.into_iter().flatten().enumerate().map(|(idx, value)| {
+            T::from_ocamlrep(value).map_err(|e| FromError::ErrorInField(idx, Box::new(e)))
+        }))
+}
+
+/// Like `array_iter`, but converts each element using `FromOcamlRepRef`, so
+/// that elements may borrow from the array.
+pub fn array_iter_ref<'a, T: FromOcamlRepRef<'a>>(
+    array: Value<'a>,
+) -> impl Iterator<Item = Result<T, FromError>> {
+    let (iter, err) = match ArrayIter::new(array) {
+        Ok(iter) => (Some(iter), None),
+        Err(e) => (None, Some(Err(e))),
+    };
+    err.into_iter()
+        .chain(iter.into_iter().flatten().enumerate().map(|(idx, value)| {
+            T::from_ocamlrep_ref(value).map_err(|e| FromError::ErrorInField(idx, Box::new(e)))
+        }))
+}
+
+fn convert_list<'a, T>(
+    list: Value<'a>,
+    convert: impl Fn(Value<'a>) -> Result<T, FromError>,
+) -> impl Iterator<Item = Result<T, FromError>> {
+    let mut failed = false;
+    ListIter::new(list)
+        .enumerate()
+        .map_while(move |(idx, value)| {
+            if failed {
+                return None;
+            }
+            let result = value.and_then(|value| {
+                // Report the index of the list element rather than the index
+                // of the field in the cons cell.
+                convert(value).map_err(|e| FromError::ErrorInField(idx, Box::new(e)))
+            });
+            failed = result.is_err();
+            Some(result)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Allocator;
+    use crate::Arena;
+
+    #[test]
+    fn list_iter_yields_elements() {
+        let arena = Arena::new();
+        let list = vec![1isize, 2, 3];
+        let value = arena.add(&list);
+        let items: Result<Vec<isize>, _> = list_iter(value).collect();
+        assert_eq!(items, Ok(list.clone()));
+        assert_eq!(ListIter::new(value).count(), 3);
+        assert_eq!(ListIter::new(Value::int(0)).next(), None);
+    }
+
+    #[test]
+    fn list_iter_stops_after_error() {
+        let arena = Arena::new();
+        let list = vec![Some(1isize), None, Some(3)];
+        let value = arena.add(&list);
+        let items: Vec<Result<isize, FromError>> = list_iter(value).collect();
+        assert_eq!(
+            items,
+            [Err(FromError::ErrorInField(
+                0,
+                Box::new(FromError::ExpectedInt(value.field(0).unwrap().to_bits()))
+            ))]
+        );
+    }
+
+    #[test]
+    fn improper_list() {
+        let arena = Arena::new();
+        let mut cell = arena.block_with_size(2);
+        arena.set_field(&mut cell, 0, Value::int(1));
+        arena.set_field(&mut cell, 1, Value::int(42));
+        let value = cell.build();
+        let items: Vec<Result<Value<'_>, FromError>> = ListIter::new(value).collect();
+        assert_eq!(items, [Ok(Value::int(1)), Err(FromError::ExpectedUnit(42))]);
+    }
+
+    #[test]
+    fn array_iter_yields_elements() {
+        let arena = Arena::new();
+        let tuple = (1isize, 2isize, 3isize);
+        let value = arena.add(&tuple);
+        let iter = ArrayIter::new(value).unwrap();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(
+            iter.rev().collect::<Vec<_>>(),
+            [Value::int(3), Value::int(2), Value::int(1)]
+        );
+        let items: Result<Vec<isize>, _> = array_iter(value).collect();
+        assert_eq!(items, Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn array_iter_rejects_non_arrays() {
+        let arena = Arena::new();
+        assert!(ArrayIter::new(Value::int(0)).is_err());
+        let items: Vec<Result<isize, FromError>> = array_iter(arena.add("str")).collect();
+        assert_eq!(
+            items,
+            [Err(FromError::ExpectedBlockTag {
+                expected: 0,
+                actual: crate::STRING_TAG,
+            })]
+        );
+    }
+}
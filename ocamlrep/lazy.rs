@@ -0,0 +1,280 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Conversions for OCaml lazy values (`'a Lazy.t`).
+//!
+//! A forced lazy value is represented either as the value itself or as a block
+//! with tag `FORWARD_TAG` whose only field is the value. `Lazy.force` leaves a
+//! forward block behind, which the OCaml GC later short-circuits (replacing
+//! pointers to it with the value itself) unless the value is a float, a lazy
+//! value, or another forward block. `Lazy.from_val` makes the same choice up
+//! front, so either representation may cross the boundary.
+//!
+//! An unforced lazy value is a block with tag `LAZY_TAG` (or `FORCING_TAG`
+//! while it is being forced) holding a closure. Rust cannot run the closure, so
+//! converting one fails with `FromError::UnforcedLazy`; force it in OCaml
+//! first.
+
+use std::cell::LazyCell;
+use std::cell::OnceCell;
+use std::fmt;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::LazyLock;
+use std::sync::OnceLock;
+
+use bumpalo::Bump;
+
+use crate::Allocator;
+use crate::FromError;
+use crate::FromOcamlRep;
+use crate::FromOcamlRepIn;
+use crate::FromOcamlRepRef;
+use crate::ToOcamlRep;
+use crate::Value;
+use crate::block;
+
+/// A forced OCaml lazy value (`'a Lazy.t`).
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OcamlLazy<T>(T);
+
+impl<T> OcamlLazy<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for OcamlLazy<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for OcamlLazy<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OcamlLazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T> From<T> for OcamlLazy<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+/// Convert `value` to an OCaml lazy value which has already been forced,
+/// wrapping it in a forward block when the value itself could be mistaken for
+/// a lazy value or a float (mirroring `Lazy.from_val`).
+fn forced_lazy_to_ocamlrep<'a, A: Allocator>(
+    value: &'a impl ToOcamlRep,
+    alloc: &'a A,
+) -> Value<'a> {
+    let value = alloc.add(value);
+    match value.as_block().map(|b| b.tag()) {
+        Some(block::FORWARD_TAG | block::LAZY_TAG | block::FORCING_TAG | block::DOUBLE_TAG) => {
+            let mut forward = alloc.block_with_size_and_tag(1, block::FORWARD_TAG);
+            alloc.set_field(&mut forward, 0, value);
+            forward.build()
+        }
+        _ => value,
+    }
+}
+
+/// Return the result of a forced OCaml lazy value, following its forward
+/// block (if any).
+fn forced_lazy_value(value: Value<'_>) -> Result<Value<'_>, FromError> {
+    match value.as_block() {
+        Some(block) => match block.tag() {
+            block::FORWARD_TAG => Ok(block[0]),
+            tag @ (block::LAZY_TAG | block::FORCING_TAG) => Err(FromError::UnforcedLazy(tag)),
+            _ => Ok(value),
+        },
+        None => Ok(value),
+    }
+}
+
+impl<T: ToOcamlRep> ToOcamlRep for OcamlLazy<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        forced_lazy_to_ocamlrep(&self.0, alloc)
+    }
+}
+
+impl<T: FromOcamlRep> FromOcamlRep for OcamlLazy<T> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(Self(T::from_ocamlrep(forced_lazy_value(value)?)?))
+    }
+}
+
+impl<'a, T: FromOcamlRepIn<'a>> FromOcamlRepIn<'a> for OcamlLazy<T> {
+    fn from_ocamlrep_in(value: Value<'_>, alloc: &'a Bump) -> Result<Self, FromError> {
+        Ok(Self(T::from_ocamlrep_in(forced_lazy_value(value)?, alloc)?))
+    }
+}
+
+impl<'v, T: FromOcamlRepRef<'v>> FromOcamlRepRef<'v> for OcamlLazy<T> {
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError> {
+        Ok(Self(T::from_ocamlrep_ref(forced_lazy_value(value)?)?))
+    }
+}
+
+/// Forces the cell, then converts it to a forced OCaml lazy value.
+impl<T: ToOcamlRep, F: FnOnce() -> T> ToOcamlRep for LazyCell<T, F> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        forced_lazy_to_ocamlrep(LazyCell::force(self), alloc)
+    }
+}
+
+/// Forces the cell, then converts it to a forced OCaml lazy value.
+impl<T: ToOcamlRep, F: FnOnce() -> T> ToOcamlRep for LazyLock<T, F> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        forced_lazy_to_ocamlrep(LazyLock::force(self), alloc)
+    }
+}
+
+/// Converts to a forced OCaml lazy value.
+///
+/// Panics if the cell is uninitialized, since there is no closure with which
+/// to build an unforced lazy value.
+impl<T: ToOcamlRep> ToOcamlRep for OnceCell<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        let value = self.get().expect("cannot convert uninitialized OnceCell");
+        forced_lazy_to_ocamlrep(value, alloc)
+    }
+}
+
+/// Converts to a forced OCaml lazy value.
+///
+/// Panics if the cell is uninitialized, since there is no closure with which
+/// to build an unforced lazy value.
+impl<T: ToOcamlRep> ToOcamlRep for OnceLock<T> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        let value = self.get().expect("cannot convert uninitialized OnceLock");
+        forced_lazy_to_ocamlrep(value, alloc)
+    }
+}
+
+impl<T: FromOcamlRep> FromOcamlRep for OnceCell<T> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(Self::from(OcamlLazy::<T>::from_ocamlrep(value)?.0))
+    }
+}
+
+impl<T: FromOcamlRep> FromOcamlRep for OnceLock<T> {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(Self::from(OcamlLazy::<T>::from_ocamlrep(value)?.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Arena;
+
+    fn forward<'a>(arena: &'a Arena, value: Value<'a>) -> Value<'a> {
+        let mut forward = arena.block_with_size_and_tag(1, block::FORWARD_TAG);
+        arena.set_field(&mut forward, 0, value);
+        forward.build()
+    }
+
+    #[test]
+    fn forced_values_are_unwrapped_when_unambiguous() {
+        let arena = Arena::new();
+        let int = OcamlLazy::new(5isize);
+        let value = arena.add(&int);
+        assert_eq!(value, Value::int(5));
+        let pair = OcamlLazy::new((1isize, String::from("a")));
+        let value = arena.add(&pair);
+        assert_eq!(value.as_block().unwrap().tag(), 0);
+        assert_eq!(OcamlLazy::from_ocamlrep(value), Ok(pair));
+    }
+
+    #[test]
+    fn floats_and_nested_lazies_are_forwarded() {
+        let arena = Arena::new();
+        let float = OcamlLazy::new(1.5f64);
+        let value = arena.add(&float);
+        let block = value.as_block().unwrap();
+        assert_eq!((block.tag(), block.size()), (block::FORWARD_TAG, 1));
+        assert_eq!(block[0].as_float(), Some(1.5));
+        assert_eq!(OcamlLazy::from_ocamlrep(value), Ok(float));
+
+        let nested = OcamlLazy::new(OcamlLazy::new(2.5f64));
+        let value = arena.add(&nested);
+        let inner = value.as_block().unwrap()[0].as_block().unwrap();
+        assert_eq!(inner.tag(), block::FORWARD_TAG);
+        assert_eq!(OcamlLazy::from_ocamlrep(value), Ok(nested));
+    }
+
+    #[test]
+    fn forward_blocks_are_followed() {
+        let arena = Arena::new();
+        let s = arena.add("hello");
+        let value = forward(&arena, s);
+        assert_eq!(
+            OcamlLazy::<String>::from_ocamlrep(value).map(OcamlLazy::into_inner),
+            Ok(String::from("hello"))
+        );
+        assert_eq!(
+            OcamlLazy::<&str>::from_ocamlrep_ref(value).map(OcamlLazy::into_inner),
+            Ok("hello")
+        );
+        let bump = Bump::new();
+        assert_eq!(
+            OcamlLazy::<&str>::from_ocamlrep_in(value, &bump).map(OcamlLazy::into_inner),
+            Ok("hello")
+        );
+    }
+
+    #[test]
+    fn unforced_lazies_are_rejected() {
+        let arena = Arena::new();
+        for tag in [block::LAZY_TAG, block::FORCING_TAG] {
+            let mut lazy = arena.block_with_size_and_tag(1, tag);
+            arena.set_field(&mut lazy, 0, Value::int(0));
+            assert_eq!(
+                OcamlLazy::<isize>::from_ocamlrep(lazy.build()),
+                Err(FromError::UnforcedLazy(tag))
+            );
+        }
+    }
+
+    #[test]
+    fn cells() {
+        let arena = Arena::new();
+        let cell = LazyCell::new(|| 3.5f64);
+        let value = arena.add(&cell);
+        assert_eq!(value.as_block().unwrap().tag(), block::FORWARD_TAG);
+        assert_eq!(
+            OnceCell::<f64>::from_ocamlrep(value).unwrap().get(),
+            Some(&3.5)
+        );
+
+        let lock = LazyLock::new(|| 7isize);
+        let value = arena.add(&lock);
+        assert_eq!(value, Value::int(7));
+        assert_eq!(
+            OnceLock::<isize>::from_ocamlrep(value).unwrap().get(),
+            Some(&7)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "uninitialized OnceCell")]
+    fn uninitialized_once_cell() {
+        let arena = Arena::new();
+        arena.add(&OnceCell::<isize>::new());
+    }
+}
@@ -301,17 +301,41 @@ corresponding type in the OCaml source file.
 */
 
 mod arena;
+mod array;
+mod assoc_list;
 mod block;
 mod cache;
 mod error;
+mod fixed_buffer;
+mod hashcons;
+mod hashtbl;
 mod impls;
+mod lazy;
+mod owned;
+mod validate;
 mod value;
 
+pub mod compare;
+pub mod diff;
 pub mod from;
+pub mod hash;
+pub mod iter;
 pub mod ptr;
 pub mod rc;
+pub mod shared;
+pub mod slab;
+pub mod static_value;
+pub mod text;
 
 pub use arena::Arena;
+pub use arena::ChunkGrowth;
+pub use array::FloatArray;
+pub use array::FloatArraySlice;
+pub use array::OcamlArray;
+pub use array::OcamlArraySlice;
+pub use assoc_list::AssocList;
+pub use assoc_list::AssocPair;
+pub use assoc_list::iter_to_ocaml_assoc_list;
 pub use block::ABSTRACT_TAG;
 pub use block::Block;
 pub use block::BlockBuilder;
@@ -332,7 +356,15 @@ pub use block::OBJECT_TAG;
 pub use block::STRING_TAG;
 pub use bumpalo::Bump;
 pub use cache::MemoizationCache;
+pub use cache::non_static_type_id;
+pub use diff::diff;
+pub use error::AllocError;
+pub use error::FieldName;
 pub use error::FromError;
+pub use fixed_buffer::FixedBufferAllocator;
+pub use hashtbl::OcamlHashtbl;
+pub use hashtbl::iter_to_ocaml_hashtbl;
+pub use hashtbl::vec_from_ocaml_hashtbl;
 pub use impls::OCamlInt;
 pub use impls::bytes_from_ocamlrep;
 pub use impls::bytes_to_ocamlrep;
@@ -344,24 +376,67 @@ pub use impls::vec_from_ocaml_map;
 pub use impls::vec_from_ocaml_map_in;
 pub use impls::vec_from_ocaml_set;
 pub use impls::vec_from_ocaml_set_in;
+pub use lazy::OcamlLazy;
 pub use ocamlrep_derive::FromOcamlRep;
 pub use ocamlrep_derive::FromOcamlRepIn;
+pub use ocamlrep_derive::FromOcamlRepRef;
 pub use ocamlrep_derive::ToOcamlRep;
+pub use owned::OwnedValue;
+pub use owned::SendOwnedValue;
+pub use shared::from_ocamlrep_in_shared;
+pub use shared::from_ocamlrep_shared;
+pub use validate::ValidationError;
+pub use validate::validate;
 pub use value::Value;
+pub use value::ValueStats;
 
 // 'mlvalues.h'
 pub const DOUBLE_WOSIZE: usize = std::mem::size_of::<f64>() / std::mem::size_of::<usize>();
+pub const MAX_WOSIZE: usize = (1 << (usize::BITS - 10 - HEADER_RESERVED_BITS)) - 1;
+
+/// The number of high bits of each block header which the OCaml runtime
+/// ocamlrep was built for reserves for other uses (see
+/// `--enable-reserved-header-bits`), and which therefore do not hold the size
+/// of the block. Detected by ocamlrep's build script, or specified with the
+/// `OCAMLREP_HEADER_RESERVED_BITS` environment variable. Usually 0.
+pub const HEADER_RESERVED_BITS: u32 = match option_env!("OCAMLREP_HEADER_RESERVED_BITS") {
+    Some(bits) => parse_reserved_bits(bits),
+    None => 0,
+};
+
+const fn parse_reserved_bits(bits: &str) -> u32 {
+    let bytes = bits.as_bytes();
+    let mut value = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(bytes[i].is_ascii_digit(), "invalid reserved header bits");
+        value = value * 10 + (bytes[i] - b'0') as u32;
+        i += 1;
+    }
+    // OCaml's configure script accepts at most 31.
+    assert!(value <= 31, "too many reserved header bits");
+    value
+}
 
-// 'gc.h'
+// 'gc.h' (OCaml 4)
 pub const CAML_WHITE: usize = 0 << 8;
 pub const CAML_GRAY: usize = 1 << 8;
 pub const CAML_BLUE: usize = 2 << 8;
 pub const CAML_BLACK: usize = 3 << 8;
 
+// 'shared_heap.h' (OCaml 5)
+pub const NOT_MARKABLE: usize = 3 << 8;
+
+/// True if ocamlrep was built for an OCaml 5 runtime (as detected by its build
+/// script, or specified with the `OCAMLREP_OCAML_VERSION` environment
+/// variable). Affects the color given to headers by `Header::new`.
+pub const OCAML5: bool = cfg!(ocaml5);
+
 /// A data structure that can be converted to an OCaml value.
 ///
 /// Types which implement both `ToOcamlRep` and `FromOcamlRep` (or
-/// `FromOcamlRepIn`) should provide compatible implementations thereof.
+/// `FromOcamlRepIn`, or `FromOcamlRepRef`) should provide compatible
+/// implementations thereof.
 /// In other words, it is expected that for any value with type `T`,
 /// `T::from_ocamlrep(value.to_ocamlrep(alloc)) == Ok(value)`.
 pub trait ToOcamlRep {
@@ -412,6 +487,21 @@ pub trait Allocator: Sized {
         self.block_with_size_and_tag(size, 0u8)
     }
 
+    /// Like `block_with_size_and_tag`, but return an error if the allocator
+    /// does not have enough space remaining for the block.
+    ///
+    /// The default implementation calls `block_with_size_and_tag`, and never
+    /// returns an error (i.e., it is appropriate for allocators which grow as
+    /// needed, and abort if they cannot).
+    #[inline(always)]
+    fn try_block_with_size_and_tag(
+        &self,
+        size: usize,
+        tag: u8,
+    ) -> Result<BlockBuilder<'_>, AllocError> {
+        Ok(self.block_with_size_and_tag(size, tag))
+    }
+
     /// Convert the given data structure to an OCaml value. Structural sharing
     /// (via references or `Rc`) will not be preserved unless `add` is invoked
     /// within an outer invocation of `add_root`.
@@ -424,6 +514,36 @@ pub trait Allocator: Sized {
         value.to_ocamlrep(self)
     }
 
+    /// Like `add`, but return an error if the allocator runs out of space
+    /// while converting the value.
+    ///
+    /// The default implementation calls `add`, and never returns an error.
+    #[inline(always)]
+    fn try_add<'a, T: ToOcamlRep + ?Sized>(
+        &'a self,
+        value: &'a T,
+    ) -> Result<Value<'a>, AllocError> {
+        Ok(self.add(value))
+    }
+
+    /// Convert the given data structure to an OCaml value, allocating only one
+    /// copy of each set of structurally equal blocks ("hash-consing"). Blocks
+    /// are compared by tag, size, and contents, so (for instance) equal strings
+    /// from different Rust allocations will be represented by a single OCaml
+    /// block. Custom blocks are never shared.
+    ///
+    /// This is appropriate only for values which OCaml code will not mutate.
+    ///
+    /// The value is first converted in a scratch `Arena`, then copied into
+    /// this allocator, so only the deduplicated blocks are allocated here.
+    /// While the copy is made, peak memory use is that of the value *without*
+    /// deduplication, plus the deduplicated copy and a table indexing its
+    /// blocks. The scratch arena is reused by later calls on the same thread
+    /// (retaining its largest chunk between calls).
+    fn add_hashconsed<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
+        hashcons::add_hashconsed(self, value)
+    }
+
     /// Convert the given `Copy` data structure to an OCaml value.
     #[inline(always)]
     fn add_copy<'a, T: ToOcamlRep + Copy + 'static>(&'a self, value: T) -> Value<'a> {
@@ -434,10 +554,11 @@ pub trait Allocator: Sized {
         self.add(unsafe { std::mem::transmute::<&'_ T, &'a T>(value_ref) })
     }
 
-    /// Given the address and size of some value, and a function to convert the
-    /// value to OCaml (e.g., a closure `|alloc| (*slice).to_ocamlrep(alloc)`),
-    /// either execute the function and return its result, or return a cached
-    /// result for that address.
+    /// Given the address, size, and type of some value (the latter from
+    /// `non_static_type_id`), and a function to convert the value to OCaml
+    /// (e.g., a closure `|alloc| (*slice).to_ocamlrep(alloc)`), either execute
+    /// the function and return its result, or return a cached result for that
+    /// address, size, and type.
     ///
     /// If `memoized` is invoked without an outer invocation of `add_root`, it
     /// must never return a cached result. If `memoized` is invoked within an
@@ -447,6 +568,7 @@ pub trait Allocator: Sized {
         &'a self,
         ptr: usize,
         size_in_bytes: usize,
+        type_id: std::any::TypeId,
         f: impl FnOnce(&'a Self) -> Value<'a>,
     ) -> Value<'a>;
 
@@ -454,26 +576,28 @@ pub trait Allocator: Sized {
     /// (via references or `Rc`) will be preserved.
     ///
     /// Note that sharing is preserved using a memoization cache keyed off of
-    /// address and size only. If the given value contains multiple references
-    /// or slices pointing to equal-sized views of the same data, but with
-    /// different OCaml representations, e.g.:
+    /// address, size, and type. Equal-sized views of the same data with
+    /// different types, e.g.:
     ///
     /// ```
     /// let x: &(u32, u32) = &(0u32, 1u32);
     /// let y: &u64 = unsafe { std::mem::transmute(x) };
     /// let value = (x, y);
-    /// alloc.add_root(&value) // PROBLEM!
+    /// alloc.add_root(&value)
     /// ```
     ///
-    /// Then the converted OCaml value will not have the intended type (i.e.,
-    /// the one which would be produced by `Allocator::add`). In this example,
-    /// the allocator will memoize the result of converting `x` (an OCaml
-    /// tuple), and use it for both `x` and `y` in `value` (since they point to
-    /// equal-sized types at the same address).
-    ///
-    /// # Panics
+    /// are converted separately, producing the same OCaml value as
+    /// `Allocator::add` would. Likewise, a struct and its first field (e.g., a
+    /// `#[repr(transparent)]` wrapper and the value it wraps) are converted
+    /// separately. Type IDs do not include lifetimes, so views whose types differ
+    /// only in lifetimes are still shared.
     ///
-    /// `add_root` is not re-entrant, and panics upon attempts to do so.
+    /// Invocations of `add_root` may be nested (e.g., a `ToOcamlRep`
+    /// implementation may invoke `add_root` to preserve sharing within its own
+    /// value, and still be converted within an outer invocation of
+    /// `add_root`). A nested invocation reuses values memoized by the
+    /// enclosing invocations, but the values it memoizes itself are forgotten
+    /// when it returns.
     fn add_root<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a>;
 
     /// Allocate a block with tag `STRING_TAG` and enough space for a string of
@@ -526,3 +650,27 @@ pub trait FromOcamlRepIn<'a>: Sized {
     /// the given arena.
     fn from_ocamlrep_in(value: Value<'_>, arena: &'a Bump) -> Result<Self, FromError>;
 }
+
+/// A type which can be reconstructed from an OCaml value by borrowing from it,
+/// rather than copying.
+///
+/// Implementations for reference types point directly into the OCaml value:
+/// `&'v str` and `&'v [u8]` refer to the contents of an OCaml string, and
+/// `&'v FloatArraySlice` to the contents of a float array. There is no impl for
+/// `&'v [f64]`, since a `[f64]` converts to an OCaml list; `FloatArraySlice`
+/// takes its place (and derived impls borrow a `&'v [f64]` field from a float
+/// array when it is marked `#[ocamlrep(float_array)]`).
+///
+/// This makes `FromOcamlRepRef` a good fit for FFI entry points which only read
+/// their arguments, since the OCaml GC cannot move or free the arguments while
+/// a Rust function which does not allocate on the OCaml heap is running (see
+/// `ocaml_ffi_ref!` in the `ocamlrep_ocamlpool` crate).
+///
+/// Types which implement both `ToOcamlRep` and `FromOcamlRepRef` should provide
+/// compatible implementations thereof. In other words, it is expected that for
+/// any value, `T::from_ocamlrep_ref(value.to_ocamlrep(alloc)) == Ok(value)`.
+pub trait FromOcamlRepRef<'v>: Sized {
+    /// Convert the given ocamlrep Value to a value of type `Self`, which may
+    /// borrow from the value.
+    fn from_ocamlrep_ref(value: Value<'v>) -> Result<Self, FromError>;
+}
@@ -0,0 +1,218 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use crate::Allocator;
+use crate::Arena;
+use crate::FromError;
+use crate::FromOcamlRep;
+use crate::ToOcamlRep;
+use crate::Value;
+use crate::block::CUSTOM_TAG;
+
+/// An OCaml value together with the `Arena` which owns its blocks.
+///
+/// Unlike a `Value<'a>`, which borrows the allocator it was built with, an
+/// `OwnedValue` has no lifetime parameter, so it can be stored in a struct or
+/// cached in a map.
+///
+/// Moving an `OwnedValue` does not move the blocks themselves (which live in
+/// the arena's heap-allocated chunks), so `OwnedValue::value` remains valid
+/// after a move. An `OwnedValue` is neither `Send` nor `Sync`, since custom
+/// blocks are copied verbatim, and may refer to thread-local data (e.g., the
+/// `Rc` held by an `ocamlrep_custom::Custom`). Use `into_send` to move a value
+/// without custom blocks to another thread.
+pub struct OwnedValue {
+    arena: Arena,
+    /// The bits of the root value. Blocks reachable from the root are either
+    /// allocated in `arena` or static.
+    root: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+/// An `OwnedValue` which may be moved to another thread, returned by
+/// `OwnedValue::into_send` and `OwnedValue::assume_send`.
+pub struct SendOwnedValue(OwnedValue);
+
+// SAFETY: `SendOwnedValue` is only constructed for values which contain no
+// custom blocks (or which the caller has promised are safe to send). All
+// other blocks are plain data owned by the arena.
+unsafe impl Send for SendOwnedValue {}
+
+impl SendOwnedValue {
+    pub fn into_inner(self) -> OwnedValue {
+        self.0
+    }
+
+    #[inline]
+    pub fn value(&self) -> Value<'_> {
+        self.0.value()
+    }
+}
+
+impl Debug for SendOwnedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl OwnedValue {
+    /// Convert `value` to an OCaml value (preserving sharing, as in
+    /// `Allocator::add_root`) in a new arena.
+    pub fn from_rep<T: ToOcamlRep + ?Sized>(value: &T) -> Self {
+        let arena = Arena::new();
+        let root = arena.add_root(value).to_bits();
+        Self::new(arena, root)
+    }
+
+    /// Copy the blocks reachable from `value` into a new arena (preserving
+    /// sharing, as in `Value::clone_with_allocator`).
+    pub fn from_value(value: Value<'_>) -> Self {
+        let arena = Arena::new();
+        let root = value.clone_with_allocator(&arena).to_bits();
+        Self::new(arena, root)
+    }
+
+    fn new(arena: Arena, root: usize) -> Self {
+        Self {
+            arena,
+            root,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Allow this value to be moved to another thread, if no custom blocks are
+    /// reachable from it. Otherwise, return the value unchanged.
+    pub fn into_send(self) -> Result<SendOwnedValue, Self> {
+        if (self.value().stats().blocks_by_tag).contains_key(&CUSTOM_TAG) {
+            return Err(self);
+        }
+        Ok(SendOwnedValue(self))
+    }
+
+    /// Allow this value to be moved to another thread without checking for
+    /// custom blocks.
+    ///
+    /// # Safety
+    ///
+    /// Every custom block reachable from the value must be safe to use from
+    /// another thread (e.g., it must not hold an `Rc`).
+    pub unsafe fn assume_send(self) -> SendOwnedValue {
+        SendOwnedValue(self)
+    }
+
+    /// Return the root value.
+    #[inline]
+    pub fn value(&self) -> Value<'_> {
+        // SAFETY: `root` was allocated in `self.arena` (or is an immediate
+        // value), and the arena is never reset, so its blocks live as long as
+        // `self`.
+        unsafe { Value::from_bits(self.root) }
+    }
+
+    /// The number of bytes allocated by the arena owning this value.
+    pub fn allocated_bytes(&self) -> usize {
+        self.arena.allocated_bytes()
+    }
+}
+
+impl Clone for OwnedValue {
+    fn clone(&self) -> Self {
+        Self::from_value(self.value())
+    }
+}
+
+impl Debug for OwnedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value().fmt(f)
+    }
+}
+
+impl ToOcamlRep for OwnedValue {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> Value<'a> {
+        self.value().clone_with_allocator(alloc)
+    }
+}
+
+impl FromOcamlRep for OwnedValue {
+    fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
+        Ok(Self::from_value(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_outlives_conversion() {
+        let owned = {
+            let source = (String::from("hello"), vec![1isize, 2, 3]);
+            OwnedValue::from_rep(&source)
+        };
+        let moved = Box::new(owned);
+        assert_eq!(
+            <(String, Vec<isize>)>::from_ocamlrep(moved.value()),
+            Ok((String::from("hello"), vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn clone_is_deep_and_preserves_sharing() {
+        let s = String::from("shared");
+        let owned = OwnedValue::from_rep(&(&s, &s));
+        let clone = owned.clone();
+        drop(owned);
+        let value = clone.value();
+        assert_eq!(value.field(0), value.field(1));
+        assert_eq!(value.field(0).unwrap().as_str().as_deref(), Some("shared"));
+        assert_eq!(crate::validate(value).map(|stats| stats.blocks), Ok(2));
+    }
+
+    #[test]
+    fn send_to_another_thread() {
+        let owned = OwnedValue::from_rep(&Some(42isize)).into_send().unwrap();
+        let handle = std::thread::spawn(move || Option::<isize>::from_ocamlrep(owned.value()));
+        assert_eq!(handle.join().unwrap(), Ok(Some(42)));
+    }
+
+    #[test]
+    fn custom_blocks_are_not_send() {
+        let arena = Arena::new();
+        let mut custom = arena.block_with_size_and_tag(2, CUSTOM_TAG);
+        arena.set_field(&mut custom, 0, Value::int(0));
+        arena.set_field(&mut custom, 1, Value::int(0));
+        let custom = custom.build();
+        let mut pair = arena.block_with_size(2);
+        arena.set_field(&mut pair, 0, Value::int(1));
+        arena.set_field(&mut pair, 1, custom);
+        let owned = OwnedValue::from_value(pair.build());
+        let owned = owned.into_send().unwrap_err();
+        assert_eq!(owned.value().field(0), Some(Value::int(1)));
+    }
+
+    #[test]
+    fn immediate_root() {
+        let owned = OwnedValue::from_rep(&7isize);
+        assert_eq!(owned.value(), Value::int(7));
+        assert_eq!(owned.clone().value(), Value::int(7));
+    }
+
+    #[test]
+    fn round_trip_through_allocator() {
+        let owned = OwnedValue::from_rep(&vec!["a", "b"]);
+        let arena = Arena::new();
+        let value = arena.add(&owned);
+        assert_eq!(
+            <Vec<String>>::from_ocamlrep(value),
+            Ok(vec![String::from("a"), String::from("b")])
+        );
+        let owned = OwnedValue::from_ocamlrep(value).unwrap();
+        assert_eq!(format!("{owned:?}"), format!("{value:?}"));
+    }
+}
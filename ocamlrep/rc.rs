@@ -283,8 +283,8 @@ impl<T: ToOcamlRep> ToOcamlRep for RcOc<T> {
 
 impl<T: FromOcamlRep> FromOcamlRep for RcOc<T> {
     fn from_ocamlrep(value: Value<'_>) -> Result<Self, FromError> {
-        // NB: We don't get any sharing this way.
-        Ok(RcOc::new(T::from_ocamlrep(value)?))
+        // NB: We only get sharing this way in `from_ocamlrep_shared`.
+        crate::shared::memoized(value, || Ok(RcOc::new(T::from_ocamlrep(value)?)))
     }
 }
 
@@ -0,0 +1,270 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Sharing-preserving conversion from OCaml values.
+//!
+//! By default, `FromOcamlRep` and `FromOcamlRepIn` convert every occurrence of
+//! a physically shared OCaml block separately, so a DAG-shaped OCaml value
+//! becomes a tree in Rust (which may be exponentially larger). The functions in
+//! this module convert a value while caching the results of converting blocks
+//! to shared pointers (`Rc<T>`, `Arc<T>`, `RcOc<T>`, and arena-allocated
+//! `&'a T`), keyed by block address. Each block shared in the OCaml value is
+//! then converted only once, and maps to a single shared pointer in Rust. This
+//! is the inverse of the sharing preserved by `Allocator::add_root`.
+//!
+//! Types other than these pointer types are converted as usual, so a shared
+//! block is only converted once if it is converted to a pointer type.
+
+use std::any::TypeId;
+use std::cell::RefCell;
+
+use bumpalo::Bump;
+
+use crate::FromError;
+use crate::FromOcamlRep;
+use crate::FromOcamlRepIn;
+use crate::Value;
+
+type HashMap<K, V> = rustc_hash::FxHashMap<K, V>;
+
+/// (block_address, type_id)
+type Key = (usize, TypeId);
+
+struct Entry {
+    ptr: usize,
+    /// Drops the cached pointer when the cache is cleared, if it owns one.
+    release: Option<unsafe fn(usize)>,
+}
+
+struct DecodeCache {
+    entries: HashMap<Key, Entry>,
+    /// The address of the `Bump` passed to `from_ocamlrep_in_shared`, if any.
+    /// Arena-allocated references are only cached when converting into this
+    /// arena, since it is known to outlive the cache.
+    bump: Option<usize>,
+}
+
+impl Drop for DecodeCache {
+    fn drop(&mut self) {
+        for (_, entry) in self.entries.drain() {
+            if let Some(release) = entry.release {
+                // SAFETY: `release` was stored alongside the pointer it
+                // releases, and each entry is released exactly once.
+                unsafe { release(entry.ptr) }
+            }
+        }
+    }
+}
+
+thread_local! {
+    static CACHE: RefCell<Option<DecodeCache>> = const { RefCell::new(None) };
+}
+
+/// Convert the given OCaml value to a value of type `T`, converting each block
+/// which is physically shared in `value` to a shared pointer (e.g., `Rc<T>`)
+/// at most once.
+pub fn from_ocamlrep_shared<T: FromOcamlRep>(value: Value<'_>) -> Result<T, FromError> {
+    with_cache(None, || T::from_ocamlrep(value))
+}
+
+/// Convert the given OCaml value to a value of type `T` allocated in `alloc`,
+/// converting each block which is physically shared in `value` to a shared
+/// pointer (e.g., `&'a T`) at most once.
+pub fn from_ocamlrep_in_shared<'a, T: FromOcamlRepIn<'a>>(
+    value: Value<'_>,
+    alloc: &'a Bump,
+) -> Result<T, FromError> {
+    with_cache(Some(alloc as *const Bump as usize), || {
+        T::from_ocamlrep_in(value, alloc)
+    })
+}
+
+/// Run `f` with an active cache. Nested invocations reuse the cache of the
+/// outermost invocation, which clears the cache when it returns (or panics).
+fn with_cache<T>(bump: Option<usize>, f: impl FnOnce() -> T) -> T {
+    if CACHE.with(|cache| cache.borrow().is_some()) {
+        return f();
+    }
+    CACHE.with(|cache| {
+        cache.replace(Some(DecodeCache {
+            entries: HashMap::default(),
+            bump,
+        }))
+    });
+    struct ClearOnDrop;
+    impl Drop for ClearOnDrop {
+        fn drop(&mut self) {
+            // Take the cache before dropping it, so that the cached pointers
+            // are not dropped while the cache is borrowed.
+            let cache = CACHE.with(|cache| cache.take());
+            drop(cache);
+        }
+    }
+    let _guard = ClearOnDrop;
+    f()
+}
+
+/// Look up the pointer cached for the given key, cloning it if present.
+fn lookup<P>(key: Key, clone: impl FnOnce(usize) -> P) -> Option<P> {
+    CACHE.with(|cache| {
+        let cache = cache.borrow();
+        let ptr = cache.as_ref()?.entries.get(&key)?.ptr;
+        Some(clone(ptr))
+    })
+}
+
+fn is_active() -> bool {
+    CACHE.with(|cache| cache.borrow().is_some())
+}
+
+/// Convert `value` to a shared pointer of type `P` using `convert`, or, if a
+/// cache is active and `value` is a block which has already been converted to
+/// a `P`, return a clone of the pointer converted earlier.
+pub(crate) fn memoized<P: Clone>(
+    value: Value<'_>,
+    convert: impl FnOnce() -> Result<P, FromError>,
+) -> Result<P, FromError> {
+    if value.is_int() || !is_active() {
+        return convert();
+    }
+    let key = (value.to_bits(), crate::non_static_type_id::<P>());
+    // SAFETY: Entries with this key store a `Box<P>` (see below). Type IDs do
+    // not include lifetimes, but `P` is converted with `FromOcamlRep`, so it
+    // does not borrow from the value (or anything else in this scope).
+    if let Some(ptr) = lookup(key, |ptr| unsafe { (*(ptr as *const P)).clone() }) {
+        return Ok(ptr);
+    }
+    // `convert` may re-enter the cache, so we must not hold a borrow of it
+    // here.
+    let ptr = convert()?;
+    unsafe fn release<P>(ptr: usize) {
+        drop(unsafe { Box::from_raw(ptr as *mut P) });
+    }
+    let entry = Entry {
+        ptr: Box::into_raw(Box::new(ptr.clone())) as usize,
+        release: Some(release::<P>),
+    };
+    CACHE.with(|cache| {
+        // The cache cannot have been cleared by `convert`: only the outermost
+        // invocation of `with_cache` clears it, and only after `f` returns.
+        let mut cache = cache.borrow_mut();
+        let old = cache.as_mut().unwrap().entries.insert(key, entry);
+        if let Some(Entry {
+            ptr,
+            release: Some(release),
+        }) = old
+        {
+            unsafe { release(ptr) }
+        }
+    });
+    Ok(ptr)
+}
+
+/// Like `memoized`, but for references to values allocated in `alloc`.
+pub(crate) fn memoized_in<'a, T>(
+    value: Value<'_>,
+    alloc: &'a Bump,
+    convert: impl FnOnce() -> Result<&'a T, FromError>,
+) -> Result<&'a T, FromError> {
+    let bump = alloc as *const Bump as usize;
+    let active = CACHE.with(|cache| {
+        cache
+            .borrow()
+            .as_ref()
+            .is_some_and(|cache| cache.bump == Some(bump))
+    });
+    if value.is_int() || !active {
+        return convert();
+    }
+    let key = (value.to_bits(), crate::non_static_type_id::<&T>());
+    // SAFETY: Entries with this key store a `&'a T` allocated in `alloc`,
+    // which outlives the cache. Type IDs do not include lifetimes, but the
+    // cache is only active for a single `alloc`, so every `&T` it holds has
+    // the lifetime `'a`.
+    if let Some(reference) = lookup(key, |ptr| unsafe { &*(ptr as *const T) }) {
+        return Ok(reference);
+    }
+    let reference = convert()?;
+    let entry = Entry {
+        ptr: reference as *const T as usize,
+        release: None,
+    };
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.as_mut().unwrap().entries.insert(key, entry);
+    });
+    Ok(reference)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::Arena;
+    use crate::rc::RcOc;
+
+    #[test]
+    fn shared_blocks_become_shared_pointers() {
+        let arena = Arena::new();
+        let s = Rc::new(String::from("shared"));
+        let tuple = (s.clone(), s.clone(), String::from("other"));
+        let value = arena.add_root(&tuple);
+
+        let (a, b, c) =
+            from_ocamlrep_shared::<(Rc<String>, Rc<String>, Rc<String>)>(value).unwrap();
+        assert!(Rc::ptr_eq(&a, &b));
+        assert!(!Rc::ptr_eq(&a, &c));
+        // The cache's references have been released.
+        assert_eq!(Rc::strong_count(&a), 2);
+
+        // Without the cache, no sharing is preserved.
+        let (a, b, _) = <(Rc<String>, Rc<String>, Rc<String>)>::from_ocamlrep(value).unwrap();
+        assert!(!Rc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn pointer_types_are_cached_separately() {
+        let arena = Arena::new();
+        let s = Rc::new(Some(5isize));
+        let tuple = (s.clone(), s.clone(), s.clone(), s);
+        let value = arena.add_root(&tuple);
+        type Tuple = (
+            Arc<Option<isize>>,
+            Arc<Option<isize>>,
+            RcOc<Option<isize>>,
+            RcOc<Option<isize>>,
+        );
+        let (a, b, c, d) = from_ocamlrep_shared::<Tuple>(value).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(RcOc::ptr_eq(&c, &d));
+        assert_eq!(*c, Some(5));
+    }
+
+    #[test]
+    fn arena_references() {
+        let arena = Arena::new();
+        let s = Rc::new(vec![1isize, 2]);
+        let list = vec![s.clone(), s.clone(), s];
+        let value = arena.add_root(&list);
+        let bump = Bump::new();
+        let refs = from_ocamlrep_in_shared::<&[&Vec<isize>]>(value, &bump).unwrap();
+        assert_eq!(refs.len(), 3);
+        assert!(std::ptr::eq(refs[0], refs[1]));
+        assert!(std::ptr::eq(refs[1], refs[2]));
+        assert_eq!(*refs[0], [1, 2]);
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let arena = Arena::new();
+        let s = Rc::new(String::from("not an int"));
+        let tuple = (s.clone(), s);
+        let value = arena.add_root(&tuple);
+        assert!(from_ocamlrep_shared::<(Rc<isize>, Rc<String>)>(value).is_err());
+        assert!(CACHE.with(|cache| cache.borrow().is_none()));
+    }
+}
@@ -0,0 +1,654 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Provides `SlabAllocator` and `Slab`, for building OCaml values once and
+//! storing them as position-independent blobs (e.g., in a cache or a shared
+//! memory segment), to be handed to OCaml later without converting them again.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use crate::Allocator;
+use crate::BlockBuilder;
+use crate::MemoizationCache;
+use crate::ToOcamlRep;
+use crate::Value;
+use crate::block;
+use crate::block::Color;
+use crate::block::Header;
+use crate::value::is_ocaml_int;
+
+const WORD_SIZE: usize = std::mem::size_of::<usize>();
+
+/// The number of words preceding the first block in a slab: the base address
+/// which the slab's pointers are relative to, and the root value.
+const SLAB_HEADER_WORDS: usize = 2;
+
+// See the comment on `NEXT_GENERATION` in 'arena.rs'.
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(usize::MAX / 4);
+
+/// An [`Allocator`](trait.Allocator.html) which writes values directly into
+/// the buffer of a [`Slab`](struct.Slab.html).
+///
+/// Blocks are allocated contiguously, in the same layout as a `Slab`. Memory
+/// which has been handed out cannot move while a conversion is in progress, so
+/// when the buffer is full, allocation continues in a new (larger) chunk; the
+/// chunks are joined and their pointers rebased by `SlabAllocator::build`.
+/// When the whole value fits in the initial capacity, the buffer becomes the
+/// slab without being copied.
+pub struct SlabAllocator {
+    generation: usize,
+    /// The chunks of the slab under construction, in allocation order. The
+    /// first chunk begins with the slab header.
+    chunks: RefCell<Vec<Chunk>>,
+    cache: MemoizationCache,
+}
+
+struct Chunk {
+    data: Box<[usize]>,
+    index: usize,
+}
+
+impl Chunk {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            data: vec![Value::int(0).to_bits(); capacity].into_boxed_slice(),
+            index: 0,
+        }
+    }
+
+    fn can_fit(&self, requested_size: usize) -> bool {
+        self.index + requested_size <= self.data.len()
+    }
+
+    /// The range of addresses occupied by the allocated part of this chunk.
+    fn addresses(&self) -> std::ops::Range<usize> {
+        let start = self.data.as_ptr() as usize;
+        start..start + self.index * WORD_SIZE
+    }
+}
+
+impl Default for SlabAllocator {
+    /// Create a new SlabAllocator with 4KB of capacity preallocated.
+    fn default() -> Self {
+        SlabAllocator::new()
+    }
+}
+
+impl SlabAllocator {
+    /// Create a new SlabAllocator with 4KB of capacity preallocated.
+    pub fn new() -> Self {
+        Self::with_capacity(1024 * 4)
+    }
+
+    /// Create a new SlabAllocator with `capacity_in_bytes` preallocated.
+    pub fn with_capacity(capacity_in_bytes: usize) -> Self {
+        let capacity = (capacity_in_bytes / WORD_SIZE).max(SLAB_HEADER_WORDS + 2);
+        let mut chunk = Chunk::with_capacity(capacity);
+        chunk.index = SLAB_HEADER_WORDS;
+        Self {
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::SeqCst),
+            chunks: RefCell::new(vec![chunk]),
+            cache: MemoizationCache::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn add<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
+        value.to_ocamlrep(self)
+    }
+
+    #[inline(always)]
+    pub fn add_root<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
+        Allocator::add_root(self, value)
+    }
+
+    /// Convert `value` to an OCaml value with the given function (typically
+    /// `|alloc, value| alloc.add_root(value)`), then turn this allocator's
+    /// buffer into a `Slab` whose root is the result.
+    ///
+    /// Every block allocated by this allocator is included in the slab, even
+    /// if it is not reachable from the root (use `SlabAllocator::to_slab` to
+    /// copy only the reachable blocks). Blocks which are reachable from the
+    /// root but were not allocated here (e.g., the static atom for `[||]`) are
+    /// copied into the slab.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value contains closures or objects (which cannot be
+    /// produced by `ToOcamlRep` implementations).
+    pub fn build<T: ?Sized>(
+        self,
+        value: &T,
+        f: impl for<'a> FnOnce(&'a SlabAllocator, &'a T) -> Value<'a>,
+    ) -> Slab {
+        let root = f(&self, value).to_bits();
+        let chunks = self.chunks.into_inner();
+
+        // Map the address range of each chunk to the index (in words) at which
+        // its contents will begin in the slab.
+        let mut ranges = Vec::with_capacity(chunks.len());
+        let mut len = 0;
+        for chunk in &chunks {
+            ranges.push((chunk.addresses(), len));
+            len += chunk.index;
+        }
+        ranges.sort_unstable_by_key(|(addresses, _)| addresses.start);
+
+        // Join the chunks, reusing the first one's buffer.
+        let mut chunks = chunks.into_iter();
+        let first = chunks.next().unwrap();
+        let mut words = first.data.into_vec();
+        words.truncate(first.index);
+        words.reserve_exact(len - words.len());
+        for chunk in chunks {
+            words.extend_from_slice(&chunk.data[..chunk.index]);
+        }
+
+        // Replace each pointer with its offset (in bytes) from the start of
+        // the slab, i.e., rebase the slab to address 0. Foreign blocks are
+        // appended to the slab as we encounter pointers to them, and their
+        // fields relocated when the loop reaches them.
+        let mut foreign = HashMap::new();
+        let mut relocate = |words: &mut Vec<usize>, bits: usize| {
+            if is_ocaml_int(bits) {
+                return bits;
+            }
+            let i = ranges.partition_point(|(addresses, _)| addresses.start <= bits);
+            if let Some((addresses, start)) = i.checked_sub(1).map(|i| &ranges[i])
+                && addresses.contains(&bits)
+            {
+                return start * WORD_SIZE + (bits - addresses.start);
+            }
+            *foreign.entry(bits).or_insert_with(|| {
+                let block = unsafe { Value::from_bits(bits) }.as_block().unwrap();
+                check_tag(block.tag());
+                // The block may have been copied from the OCaml heap, where its
+                // color belongs to the GC. The slab lives outside the heap.
+                words.push(block.header().recolor(Color::DEFAULT).to_bits());
+                let offset = words.len() * WORD_SIZE;
+                words.extend_from_slice(block.as_int_slice());
+                offset
+            })
+        };
+        words[0] = 0;
+        words[1] = relocate(&mut words, root);
+        let mut index = SLAB_HEADER_WORDS;
+        while index < words.len() {
+            let header = Header::from_bits(words[index]);
+            check_tag(header.tag());
+            let fields = index + 1..index + 1 + header.size();
+            if header.tag() < block::NO_SCAN_TAG {
+                for i in fields.clone() {
+                    let bits = words[i];
+                    words[i] = relocate(&mut words, bits);
+                }
+            }
+            index = fields.end;
+        }
+
+        let mut slab = Slab {
+            words: words.into_boxed_slice(),
+        };
+        slab.rebase(slab.words.as_ptr() as usize);
+        slab
+    }
+
+    /// Copy the blocks reachable from `root` into a new `Slab`. Structural
+    /// sharing is preserved, and blocks which are not reachable from `root`
+    /// are not copied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `root` contains closures or objects (which cannot be
+    /// produced by `ToOcamlRep` implementations).
+    pub fn to_slab(&self, root: Value<'_>) -> Slab {
+        Slab::from_value(root)
+    }
+
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn alloc<'a>(&'a self, requested_size: usize) -> &'a mut [Value<'a>] {
+        let mut chunks = self.chunks.borrow_mut();
+        let chunk = chunks.last_mut().unwrap();
+        if !chunk.can_fit(requested_size) {
+            let capacity = (chunk.data.len() * 2).max(requested_size * 2);
+            chunks.push(Chunk::with_capacity(capacity));
+        }
+        let chunk = chunks.last_mut().unwrap();
+        let start = chunk.index;
+        chunk.index += requested_size;
+        let slice = &mut chunk.data[start..chunk.index];
+        // SAFETY: As in `Arena::alloc`, the blocks handed out are
+        // non-overlapping, and chunks are neither moved nor freed until
+        // `SlabAllocator::build` consumes the allocator (and with it every
+        // `Value` and `BlockBuilder` borrowing from it). `Value` is
+        // `repr(transparent)` over `usize`.
+        unsafe {
+            std::slice::from_raw_parts_mut(slice.as_mut_ptr() as *mut Value<'a>, requested_size)
+        }
+    }
+}
+
+fn check_tag(tag: u8) {
+    match tag {
+        block::CLOSURE_TAG | block::INFIX_TAG | block::OBJECT_TAG => {
+            panic!("Slab: cannot copy block with tag {tag}")
+        }
+        _ => {}
+    }
+}
+
+impl Allocator for SlabAllocator {
+    #[inline(always)]
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn block_with_size_and_tag(&self, size: usize, tag: u8) -> BlockBuilder<'_> {
+        let block = self.alloc(size + 1);
+        block[0] = unsafe { Value::from_bits(Header::new(size, tag).to_bits()) };
+        BlockBuilder::new(&mut block[1..])
+    }
+
+    #[inline(always)]
+    fn set_field<'a>(&self, block: &mut BlockBuilder<'a>, index: usize, value: Value<'a>) {
+        unsafe { *self.block_ptr_mut(block).add(index) = value }
+    }
+
+    unsafe fn block_ptr_mut<'a>(&self, block: &mut BlockBuilder<'a>) -> *mut Value<'a> {
+        block.address() as *mut _
+    }
+
+    fn memoized<'a>(
+        &'a self,
+        ptr: usize,
+        size: usize,
+        type_id: std::any::TypeId,
+        f: impl FnOnce(&'a Self) -> Value<'a>,
+    ) -> Value<'a> {
+        let bits = self
+            .cache
+            .memoized(ptr, size, type_id, || f(self).to_bits());
+        // SAFETY: The only memoized values in the cache are those computed in
+        // the closure on the previous line. Since f returns Value<'a>, any
+        // cached bits must represent a valid Value<'a>,
+        unsafe { Value::from_bits(bits) }
+    }
+
+    fn add_root<'a, T: ToOcamlRep + ?Sized>(&'a self, value: &'a T) -> Value<'a> {
+        self.cache.with_cache(|| value.to_ocamlrep(self))
+    }
+}
+
+/// A contiguous, relocatable buffer containing an OCaml value and every block
+/// reachable from it.
+///
+/// Pointers within the slab are relative to its *base* address: the address
+/// at which the first byte of the slab must be located for those pointers to
+/// be valid. `Slab::rebase` rewrites the pointers for a new base address,
+/// after which `Slab::as_bytes` can be copied to that address (e.g., in a
+/// shared memory segment) and read with `Slab::value_in`.
+///
+/// Custom blocks are copied verbatim, so their custom operations pointers are
+/// only valid within the process which created the slab.
+pub struct Slab {
+    /// Word 0 is the base address, word 1 is the root value, and the
+    /// remaining words are the headers and fields of blocks.
+    words: Box<[usize]>,
+}
+
+impl Slab {
+    /// Convert `value` to an OCaml value (preserving sharing, as in
+    /// `Allocator::add_root`) and store it in a new `Slab`.
+    pub fn new<T: ToOcamlRep + ?Sized>(value: &T) -> Self {
+        SlabAllocator::new().build(value, |alloc, value| alloc.add_root(value))
+    }
+
+    /// Copy the blocks reachable from `root` into a new `Slab` based at its own
+    /// address (so that `Slab::as_value` returns `Some`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `root` contains closures or objects.
+    pub fn from_value(root: Value<'_>) -> Self {
+        // Assign each reachable block its offset (in words) in the slab.
+        let mut offsets = HashMap::new();
+        let mut blocks = vec![];
+        let mut size = SLAB_HEADER_WORDS;
+        let mut stack = vec![root];
+        while let Some(value) = stack.pop() {
+            let block = match value.as_block() {
+                None => continue,
+                Some(block) => block,
+            };
+            if offsets.contains_key(&value.to_bits()) {
+                continue;
+            }
+            match block.tag() {
+                block::CLOSURE_TAG | block::INFIX_TAG | block::OBJECT_TAG => {
+                    panic!("Slab: cannot copy block with tag {}", block.tag())
+                }
+                _ => {}
+            }
+            // Offset of the first field (just after the header)
+            offsets.insert(value.to_bits(), size + 1);
+            blocks.push(block);
+            size += block.size() + 1;
+            if let Some(fields) = block.as_values() {
+                stack.extend(fields.iter().rev());
+            }
+        }
+
+        let mut words = vec![0; size].into_boxed_slice();
+        let base = words.as_ptr() as usize;
+        let relocate = |value: Value<'_>| match value.as_block() {
+            None => value.to_bits(),
+            Some(_) => base + offsets[&value.to_bits()] * WORD_SIZE,
+        };
+        words[0] = base;
+        words[1] = relocate(root);
+        let mut index = SLAB_HEADER_WORDS;
+        for block in blocks {
+            // The block may have been copied from the OCaml heap, where its
+            // color belongs to the GC. The slab lives outside the heap.
+            words[index] = block.header().recolor(Color::DEFAULT).to_bits();
+            index += 1;
+            match block.as_values() {
+                Some(fields) => {
+                    for &field in fields {
+                        words[index] = relocate(field);
+                        index += 1;
+                    }
+                }
+                None => {
+                    let data = block.as_int_slice();
+                    words[index..index + data.len()].copy_from_slice(data);
+                    index += data.len();
+                }
+            }
+        }
+        Self { words }
+    }
+
+    /// Copy a slab previously written out with `Slab::as_bytes`, and rebase it
+    /// to its new address. Returns `None` if the length of `bytes` is not a
+    /// multiple of the word size, or is too short to contain a slab.
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must have been produced by `Slab::as_bytes` (in this process, if
+    /// the slab contains custom blocks).
+    pub unsafe fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if !bytes.len().is_multiple_of(WORD_SIZE) || bytes.len() < SLAB_HEADER_WORDS * WORD_SIZE {
+            return None;
+        }
+        let words: Box<[usize]> = bytes
+            .chunks_exact(WORD_SIZE)
+            .map(|chunk| usize::from_ne_bytes(chunk.try_into().unwrap()))
+            .collect();
+        let mut slab = Self { words };
+        slab.rebase(slab.words.as_ptr() as usize);
+        Some(slab)
+    }
+
+    /// The address which pointers in this slab are relative to.
+    pub fn base(&self) -> usize {
+        self.words[0]
+    }
+
+    /// The size of this slab in bytes.
+    pub fn len(&self) -> usize {
+        self.words.len() * WORD_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The contents of this slab, which can be written out and later read with
+    /// `Slab::from_bytes`, or copied to the slab's base address and read with
+    /// `Slab::value_in`.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.words.as_ptr() as *const u8, self.len()) }
+    }
+
+    /// Rewrite every pointer in this slab so that it is valid when the slab is
+    /// located at `new_base`. The base address must be word-aligned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_base` is not word-aligned.
+    pub fn rebase(&mut self, new_base: usize) {
+        assert!(
+            new_base.is_multiple_of(WORD_SIZE),
+            "Slab base address must be word-aligned"
+        );
+        let old_base = self.words[0];
+        let relocate = |bits: usize| {
+            if is_ocaml_int(bits) {
+                bits
+            } else {
+                bits - old_base + new_base
+            }
+        };
+        self.words[0] = new_base;
+        self.words[1] = relocate(self.words[1]);
+        let mut index = SLAB_HEADER_WORDS;
+        while index < self.words.len() {
+            let header = Header::from_bits(self.words[index]);
+            let fields = index + 1..index + 1 + header.size();
+            if header.tag() < block::NO_SCAN_TAG {
+                for field in &mut self.words[fields.clone()] {
+                    *field = relocate(*field);
+                }
+            }
+            index = fields.end;
+        }
+    }
+
+    /// Return the root value of this slab, if the slab is based at its own
+    /// address (i.e., it has not been rebased elsewhere).
+    pub fn as_value(&self) -> Option<Value<'_>> {
+        if self.base() != self.words.as_ptr() as usize {
+            return None;
+        }
+        Some(unsafe { Value::from_bits(self.words[1]) })
+    }
+
+    /// Return the root value of the slab stored in `bytes`, if the slab is
+    /// based at the address of `bytes` (i.e., it was rebased to that address
+    /// before being copied there).
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must contain a slab produced by `Slab::as_bytes`, and must not
+    /// be modified while the returned value is in use.
+    pub unsafe fn value_in(bytes: &[u8]) -> Option<Value<'_>> {
+        let ptr = bytes.as_ptr() as *const usize;
+        if bytes.len() < SLAB_HEADER_WORDS * WORD_SIZE || !ptr.is_aligned() {
+            return None;
+        }
+        unsafe {
+            if *ptr != ptr as usize {
+                return None;
+            }
+            Some(Value::from_bits(*ptr.add(1)))
+        }
+    }
+}
+
+impl Clone for Slab {
+    /// Copy this slab, rebasing the copy to its own address.
+    fn clone(&self) -> Self {
+        let mut slab = Self {
+            words: self.words.clone(),
+        };
+        slab.rebase(slab.words.as_ptr() as usize);
+        slab
+    }
+}
+
+impl std::fmt::Debug for Slab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.as_value() {
+            Some(value) => f.debug_tuple("Slab").field(&value).finish(),
+            None => f
+                .debug_struct("Slab")
+                .field("base", &self.base())
+                .field("len", &self.len())
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FromOcamlRep;
+
+    #[test]
+    fn round_trip() {
+        let value = (vec![Some(1.5f64), None], String::from("hello"), 42isize);
+        let slab = Slab::new(&value);
+        let root = slab.as_value().unwrap();
+        assert_eq!(
+            <(Vec<Option<f64>>, String, isize)>::from_ocamlrep(root),
+            Ok(value)
+        );
+        // A tuple, two list cells, an option, a float, and a string
+        assert_eq!(crate::validate(root).map(|stats| stats.blocks), Ok(6));
+    }
+
+    #[test]
+    fn headers_are_recolored() {
+        // Simulate a block copied from the OCaml heap mid-collection.
+        let words = [
+            Header::with_color(1, 0, Color::Gray).to_bits(),
+            Value::int(7).to_bits(),
+        ];
+        let value = unsafe { Value::from_bits(words.as_ptr().add(1) as usize) };
+        let slab = Slab::from_value(value);
+        let header = slab.as_value().unwrap().as_block().unwrap().header();
+        assert_eq!(header.color(), Color::DEFAULT);
+        assert_eq!((header.size(), header.tag()), (1, 0));
+    }
+
+    #[test]
+    fn immediate_root() {
+        let slab = Slab::new(&5isize);
+        assert_eq!(slab.as_value().unwrap().as_int(), Some(5));
+        assert_eq!(slab.len(), SLAB_HEADER_WORDS * WORD_SIZE);
+    }
+
+    #[test]
+    fn sharing_is_preserved() {
+        let alloc = SlabAllocator::new();
+        let s = alloc.add("shared");
+        let mut block = alloc.block_with_size(2);
+        alloc.set_field(&mut block, 0, s);
+        alloc.set_field(&mut block, 1, s);
+        // Unreachable blocks are not copied.
+        alloc.add("garbage");
+        let slab = alloc.to_slab(block.build());
+        let root = slab.as_value().unwrap().as_block().unwrap();
+        assert_eq!(root[0], root[1]);
+        assert_eq!(slab.len(), (SLAB_HEADER_WORDS + 3 + 2) * WORD_SIZE);
+    }
+
+    #[test]
+    fn rebase_and_copy() {
+        let value = vec![String::from("a"), String::from("b")];
+        let mut slab = Slab::new(&value);
+        assert_eq!(slab.base(), slab.as_bytes().as_ptr() as usize);
+
+        let mut dest = vec![0usize; slab.len() / WORD_SIZE];
+        slab.rebase(dest.as_ptr() as usize);
+        assert!(slab.as_value().is_none());
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                slab.as_bytes().as_ptr(),
+                dest.as_mut_ptr() as *mut u8,
+                slab.len(),
+            );
+        }
+        let dest_bytes =
+            unsafe { std::slice::from_raw_parts(dest.as_ptr() as *const u8, slab.len()) };
+        let root = unsafe { Slab::value_in(dest_bytes) }.unwrap();
+        assert_eq!(<Vec<String>>::from_ocamlrep(root), Ok(value.clone()));
+
+        // The copy in `dest` is still valid after the original is dropped.
+        drop(slab);
+        let root = unsafe { Slab::value_in(dest_bytes) }.unwrap();
+        assert_eq!(<Vec<String>>::from_ocamlrep(root), Ok(value));
+    }
+
+    #[test]
+    fn from_bytes() {
+        let value = (String::from("x"), vec![1isize, 2, 3]);
+        let bytes = Slab::new(&value).as_bytes().to_vec();
+        let slab = unsafe { Slab::from_bytes(&bytes) }.unwrap();
+        assert_eq!(
+            <(String, Vec<isize>)>::from_ocamlrep(slab.as_value().unwrap()),
+            Ok(value.clone())
+        );
+        let clone = slab.clone();
+        drop(slab);
+        assert_eq!(
+            <(String, Vec<isize>)>::from_ocamlrep(clone.as_value().unwrap()),
+            Ok(value)
+        );
+        assert!(unsafe { Slab::from_bytes(&bytes[1..]) }.is_none());
+    }
+
+    #[test]
+    fn values_spanning_chunks() {
+        let value: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+        let slab = SlabAllocator::with_capacity(64).build(&value, |alloc, value| alloc.add(value));
+        let root = slab.as_value().unwrap();
+        assert_eq!(<Vec<String>>::from_ocamlrep(root), Ok(value.clone()));
+        let stats = crate::validate(root).unwrap();
+        assert_eq!(slab.len(), (SLAB_HEADER_WORDS + stats.words) * WORD_SIZE);
+
+        let clone = unsafe { Slab::from_bytes(slab.as_bytes()) }.unwrap();
+        drop(slab);
+        assert_eq!(
+            <Vec<String>>::from_ocamlrep(clone.as_value().unwrap()),
+            Ok(value)
+        );
+    }
+
+    #[test]
+    fn foreign_blocks_are_copied() {
+        // `[||]` is a static atom, which is not allocated in the slab.
+        let value = (crate::OcamlArray::<isize>::new(), 1isize);
+        let slab = Slab::new(&value);
+        // Atoms have no fields, so check where their headers are.
+        let in_slab = |slab: &Slab, value: Value<'_>| {
+            let header = (value.to_bits() - WORD_SIZE) as *const u8;
+            slab.as_bytes().as_ptr_range().contains(&header)
+        };
+        let root = slab.as_value().unwrap();
+        let empty = root.as_block().unwrap()[0];
+        assert!(in_slab(&slab, empty));
+        assert_eq!(empty.as_block().unwrap().size(), 0);
+        assert_eq!(
+            crate::OcamlArray::from_ocamlrep(root.field(0).unwrap()),
+            Ok(value.0)
+        );
+
+        let slab = Slab::new(&crate::OcamlArray::<isize>::new());
+        assert!(in_slab(&slab, slab.as_value().unwrap()));
+    }
+
+    #[test]
+    fn generation() {
+        let alloc = SlabAllocator::new();
+        assert!(alloc.generation() >= usize::MAX / 4);
+        assert!(alloc.generation() < usize::MAX / 2);
+    }
+}
@@ -0,0 +1,420 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Support for OCaml values stored in `static`s (see `ocaml_static!`).
+//!
+//! The types in this module describe the memory layout of OCaml blocks so that
+//! they can be built by const evaluation. They are public only so that they
+//! can be named by the expansion of `ocaml_static!`; there should be no need
+//! to use them directly.
+
+use crate::Value;
+use crate::block::Color;
+use crate::block::Header;
+
+const WORD_SIZE: usize = std::mem::size_of::<usize>();
+
+/// A word of a static OCaml value: either an immediate value, or a pointer to
+/// the first field of another static block.
+#[derive(Clone, Copy)]
+pub union StaticWord {
+    bits: usize,
+    ptr: *const usize,
+}
+
+// SAFETY: A `StaticWord` only ever points to immutable `static` data.
+unsafe impl Sync for StaticWord {}
+
+impl StaticWord {
+    /// The OCaml integer `value`.
+    pub const fn int(value: isize) -> Self {
+        StaticWord {
+            bits: ((value as usize) << 1) | 1,
+        }
+    }
+
+    /// A pointer to the given block.
+    pub const fn block<const N: usize>(block: &'static StaticBlock<N>) -> Self {
+        StaticWord {
+            ptr: &block.fields as *const [StaticWord; N] as *const usize,
+        }
+    }
+
+    /// A pointer to the given block.
+    pub const fn bytes<const N: usize>(block: &'static StaticBytes<N>) -> Self {
+        StaticWord {
+            ptr: &block.words as *const [usize; N] as *const usize,
+        }
+    }
+
+    #[inline]
+    pub fn to_value(self) -> Value<'static> {
+        // SAFETY: Every `StaticWord` is either an OCaml integer or a pointer to
+        // a `'static` block with a valid header.
+        unsafe {
+            if self.bits & 1 == 1 {
+                Value::from_bits(self.bits)
+            } else {
+                Value::from_bits(self.ptr as usize)
+            }
+        }
+    }
+}
+
+/// The header of a static block. Static blocks live outside of the OCaml heap
+/// (possibly in read-only memory), so they are always `NOT_MARKABLE`,
+/// regardless of OCaml version.
+const fn static_header(size: usize, tag: u8) -> usize {
+    Header::with_color(size, tag, Color::NOT_MARKABLE).to_bits()
+}
+
+/// The headers of the empty blocks ("atoms") with each tag, laid out like
+/// OCaml's `caml_atom_table`: the atom with tag `t` points just past the header
+/// at index `t`.
+static ATOMS: [usize; 256] = {
+    let mut headers = [0; 256];
+    let mut tag = 0;
+    while tag < 256 {
+        headers[tag] = static_header(0, tag as u8);
+        tag += 1;
+    }
+    headers
+};
+
+/// The empty block with the given tag. Like `[||]`, such blocks are never
+/// allocated; OCaml shares a single static atom for each tag.
+pub(crate) fn atom(tag: u8) -> Value<'static> {
+    let ptr = ATOMS.as_ptr().wrapping_add(tag as usize + 1);
+    // SAFETY: `ptr` points just past a header in a `'static` array (or just
+    // past its end), and the block it describes has no fields.
+    unsafe { Value::from_bits(ptr as usize) }
+}
+
+/// A static block containing `N` values.
+#[repr(C)]
+pub struct StaticBlock<const N: usize> {
+    header: usize,
+    fields: [StaticWord; N],
+}
+
+impl<const N: usize> StaticBlock<N> {
+    pub const fn new(tag: u8, fields: [StaticWord; N]) -> Self {
+        Self {
+            header: static_header(N, tag),
+            fields,
+        }
+    }
+}
+
+impl StaticBlock<2> {
+    /// The cons cells of a list containing `elements`, to be stored in the
+    /// static `cells` (so that each cell can point to the next).
+    pub const fn list<const N: usize>(
+        elements: [StaticWord; N],
+        cells: &'static [StaticBlock<2>; N],
+    ) -> [StaticBlock<2>; N] {
+        let mut list = [const { StaticBlock::new(0, [StaticWord::int(0); 2]) }; N];
+        let mut i = 0;
+        while i < N {
+            let tail = if i + 1 < N {
+                StaticWord::block(&cells[i + 1])
+            } else {
+                StaticWord::int(0)
+            };
+            list[i] = StaticBlock::new(0, [elements[i], tail]);
+            i += 1;
+        }
+        list
+    }
+}
+
+/// A static block containing `N` words of raw (unscanned) data, such as a
+/// string or float.
+#[repr(C)]
+pub struct StaticBytes<const N: usize> {
+    header: usize,
+    words: [usize; N],
+}
+
+impl<const N: usize> StaticBytes<N> {
+    /// A string block containing `bytes`. `N` must be `string_words(bytes.len())`.
+    pub const fn string(bytes: &[u8]) -> Self {
+        assert!(N == string_words(bytes.len()));
+        let mut words = [0; N];
+        copy_bytes(&mut words, bytes);
+        // The final byte of the block holds the number of padding bytes, as in
+        // `Allocator::byte_string_with_len`.
+        let length = N * WORD_SIZE;
+        let padding = (length - bytes.len() - 1) as u8;
+        let mut last = words[N - 1].to_ne_bytes();
+        last[WORD_SIZE - 1] = padding;
+        words[N - 1] = usize::from_ne_bytes(last);
+        Self {
+            header: static_header(N, crate::STRING_TAG),
+            words,
+        }
+    }
+
+    /// A boxed float.
+    pub const fn float(value: f64) -> Self {
+        assert!(N == crate::DOUBLE_WOSIZE);
+        let mut words = [0; N];
+        copy_bytes(&mut words, &value.to_ne_bytes());
+        Self {
+            header: static_header(N, crate::DOUBLE_TAG),
+            words,
+        }
+    }
+}
+
+/// The number of words in a string block containing `len` bytes (including
+/// padding and the trailing NUL).
+pub const fn string_words(len: usize) -> usize {
+    (len + 1).div_ceil(WORD_SIZE)
+}
+
+const fn copy_bytes(words: &mut [usize], bytes: &[u8]) {
+    let mut i = 0;
+    while i < bytes.len() {
+        let mut word = words[i / WORD_SIZE].to_ne_bytes();
+        word[i % WORD_SIZE] = bytes[i];
+        words[i / WORD_SIZE] = usize::from_ne_bytes(word);
+        i += 1;
+    }
+}
+
+/// Build an OCaml value in `static` memory at compile time, and evaluate to a
+/// `Value<'static>` pointing to it. No allocation is performed at runtime, so
+/// this is a cheap way to hand large constant tables to OCaml.
+///
+/// The value is described with the following syntax, which mirrors the
+/// representation produced by the `ToOcamlRep` impls for the corresponding
+/// Rust types:
+///
+/// | Syntax                     | OCaml value                          |
+/// |----------------------------|--------------------------------------|
+/// | `()`                       | `()`                                 |
+/// | `true`, `false`            | `true`, `false`                      |
+/// | `int(e)`                   | the integer `e` (a const `isize`)    |
+/// | `float(e)`                 | the boxed float `e` (a const `f64`)  |
+/// | `"literal"`                | a string                             |
+/// | `str(e)`, `bytes(e)`       | a string (`e` a const `&str`/`&[u8]`)|
+/// | `None`, `Some(x)`          | an option                            |
+/// | `(x, y, ...)`              | a tuple                              |
+/// | `[x, y, ...]`              | a list                               |
+/// | `array[x, y, ...]`         | an array                             |
+/// | `block(tag; x, y, ...)`    | a block with the given const tag     |
+///
+/// Nullary constructors of variant types can be written with `int(n)`, and
+/// constructors with arguments with `block(n; ...)`.
+///
+/// ```ignore
+/// let keywords: Value<'static> = ocaml_static!([
+///     ("if", int(0)),
+///     ("else", int(1)),
+/// ]);
+/// ```
+///
+/// Values built this way are not shared: each occurrence of a string or block
+/// in the description produces a separate block.
+#[macro_export]
+macro_rules! ocaml_static {
+    (@value ()) => { $crate::static_value::StaticWord::int(0) };
+    (@value false) => { $crate::static_value::StaticWord::int(0) };
+    (@value true) => { $crate::static_value::StaticWord::int(1) };
+    (@value None) => { $crate::static_value::StaticWord::int(0) };
+    (@value int($value:expr)) => { $crate::static_value::StaticWord::int($value) };
+    (@value float($value:expr)) => {{
+        static FLOAT: $crate::static_value::StaticBytes<{ $crate::DOUBLE_WOSIZE }> =
+            $crate::static_value::StaticBytes::float($value);
+        $crate::static_value::StaticWord::bytes(&FLOAT)
+    }};
+    (@value str($value:expr)) => { $crate::ocaml_static!(@value bytes(::core::primitive::str::as_bytes($value))) };
+    (@value bytes($value:expr)) => {{
+        const BYTES: &[u8] = $value;
+        static STRING: $crate::static_value::StaticBytes<
+            { $crate::static_value::string_words(BYTES.len()) },
+        > = $crate::static_value::StaticBytes::string(BYTES);
+        $crate::static_value::StaticWord::bytes(&STRING)
+    }};
+    (@value $value:literal) => { $crate::ocaml_static!(@value str($value)) };
+    (@value Some($($value:tt)+)) => { $crate::ocaml_static!(@block 0; $($value)+) };
+    (@value ($($fields:tt)*)) => { $crate::ocaml_static!(@block 0; $($fields)*) };
+    (@value array[$($fields:tt)*]) => { $crate::ocaml_static!(@block 0; $($fields)*) };
+    (@value block($tag:expr; $($fields:tt)*)) => {
+        $crate::ocaml_static!(@block $tag; $($fields)*)
+    };
+    (@value [$($elements:tt)*]) => { $crate::ocaml_static!(@list $($elements)*) };
+
+    // Lists and blocks match all of their elements at once (each is a single
+    // token tree, optionally followed by a parenthesized or bracketed group),
+    // rather than recursing once per element, so that long lists do not run
+    // into the `recursion_limit`. The cons cells of a list are stored in a
+    // single static array, each pointing to the next.
+    (@list) => { $crate::static_value::StaticWord::int(0) };
+    (@list $($head:tt $(($($args:tt)*))? $([$($items:tt)*])?),+ $(,)?) => {{
+        const LEN: usize = <[&str]>::len(&[$(stringify!($head)),+]);
+        static CELLS: [$crate::static_value::StaticBlock<2>; LEN] =
+            $crate::static_value::StaticBlock::list(
+                [$($crate::ocaml_static!(@value $head $(($($args)*))? $([$($items)*])?)),+],
+                &CELLS,
+            );
+        $crate::static_value::StaticWord::block(&CELLS[0])
+    }};
+    (@block $tag:expr; $($head:tt $(($($args:tt)*))? $([$($items:tt)*])?),* $(,)?) => {{
+        const SIZE: usize = <[&str]>::len(&[$(stringify!($head)),*]);
+        static BLOCK: $crate::static_value::StaticBlock<SIZE> = $crate::static_value::StaticBlock::new(
+            $tag,
+            [$($crate::ocaml_static!(@value $head $(($($args)*))? $([$($items)*])?)),*],
+        );
+        $crate::static_value::StaticWord::block(&BLOCK)
+    }};
+
+    ($($value:tt)+) => {{
+        static ROOT: $crate::static_value::StaticWord = $crate::ocaml_static!(@value $($value)+);
+        ROOT.to_value()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FromOcamlRep;
+    use crate::Value;
+
+    #[test]
+    fn immediates() {
+        assert_eq!(ocaml_static!(()), Value::int(0));
+        assert_eq!(ocaml_static!(true), Value::int(1));
+        assert_eq!(ocaml_static!(int(-42)), Value::int(-42));
+        assert_eq!(ocaml_static!(None), Value::int(0));
+        assert_eq!(ocaml_static!([]), Value::int(0));
+    }
+
+    #[test]
+    fn strings() {
+        // Check every padding length.
+        let strings = ["", "a", "abcdefg", "abcdefgh", "abcdefghijklmnopq"];
+        let values = [
+            ocaml_static!(""),
+            ocaml_static!("a"),
+            ocaml_static!("abcdefg"),
+            ocaml_static!("abcdefgh"),
+            ocaml_static!(str("abcdefghijklmnopq")),
+        ];
+        let arena = crate::Arena::new();
+        for (s, value) in strings.into_iter().zip(values) {
+            assert_eq!(value.as_str().as_deref(), Some(s));
+            let expected = arena.add(s).as_block().unwrap();
+            assert_eq!(
+                value.as_block().unwrap().as_int_slice(),
+                expected.as_int_slice()
+            );
+        }
+        assert_eq!(
+            ocaml_static!(bytes(b"\xff\x00")).as_byte_string(),
+            Some(&b"\xff\x00"[..])
+        );
+    }
+
+    #[test]
+    fn structured_values() {
+        let value = ocaml_static!([
+            ("if", Some(int(1)), float(1.5)),
+            ("else", None, float(-0.25)),
+        ]);
+        assert_eq!(
+            <Vec<(String, Option<isize>, f64)>>::from_ocamlrep(value),
+            Ok(vec![
+                ("if".to_string(), Some(1), 1.5),
+                ("else".to_string(), None, -0.25),
+            ])
+        );
+        assert_eq!(crate::validate(value).map(|stats| stats.blocks), Ok(9));
+
+        let value = ocaml_static!(array[true, false, [int(1), int(2)]]);
+        assert_eq!(
+            <(bool, bool, Vec<isize>)>::from_ocamlrep(value),
+            Ok((true, false, vec![1, 2]))
+        );
+    }
+
+    #[test]
+    fn long_lists() {
+        // Lists and blocks are expanded without recursing once per element, so
+        // their length is not limited by the `recursion_limit`.
+        macro_rules! eight_times {
+            ($($elements:tt)*) => {
+                (
+                    ocaml_static!([
+                        $($elements)* $($elements)* $($elements)* $($elements)*
+                        $($elements)* $($elements)* $($elements)* $($elements)*
+                    ]),
+                    ocaml_static!(array[
+                        $($elements)* $($elements)* $($elements)* $($elements)*
+                        $($elements)* $($elements)* $($elements)* $($elements)*
+                    ]),
+                )
+            };
+        }
+        let (list, array) = eight_times! {
+            ("kw0", int(0)), ("kw1", int(1)), ("kw2", int(2)), ("kw3", int(3)),
+            ("kw4", int(4)), ("kw5", int(5)), ("kw6", int(6)), ("kw7", int(7)),
+            ("kw8", int(8)), ("kw9", int(9)), ("kw10", int(10)), ("kw11", int(11)),
+            ("kw12", int(12)), ("kw13", int(13)), ("kw14", int(14)), ("kw15", int(15)),
+            ("kw16", int(16)), ("kw17", int(17)), ("kw18", int(18)), ("kw19", int(19)),
+            ("kw20", int(20)), ("kw21", int(21)), ("kw22", int(22)), ("kw23", int(23)),
+            ("kw24", int(24)), ("kw25", int(25)), ("kw26", int(26)), ("kw27", int(27)),
+            ("kw28", int(28)), ("kw29", int(29)), ("kw30", int(30)), ("kw31", int(31)),
+            ("kw32", int(32)), ("kw33", int(33)), ("kw34", int(34)), ("kw35", int(35)),
+            ("kw36", int(36)), ("kw37", int(37)), ("kw38", int(38)), ("kw39", int(39)),
+            ("kw40", int(40)), ("kw41", int(41)), ("kw42", int(42)), ("kw43", int(43)),
+            ("kw44", int(44)), ("kw45", int(45)), ("kw46", int(46)), ("kw47", int(47)),
+            ("kw48", int(48)), ("kw49", int(49)), ("kw50", int(50)), ("kw51", int(51)),
+            ("kw52", int(52)), ("kw53", int(53)), ("kw54", int(54)), ("kw55", int(55)),
+            ("kw56", int(56)), ("kw57", int(57)), ("kw58", int(58)), ("kw59", int(59)),
+            ("kw60", int(60)), ("kw61", int(61)), ("kw62", int(62)), ("kw63", int(63)),
+        };
+        let expected: Vec<(String, isize)> = (0..512)
+            .map(|i| (format!("kw{}", i % 64), i % 64))
+            .collect();
+        assert_eq!(
+            <Vec<(String, isize)>>::from_ocamlrep(list),
+            Ok(expected.clone())
+        );
+        assert_eq!(crate::validate(list).map(|stats| stats.blocks), Ok(512 * 3));
+        assert_eq!(
+            <crate::OcamlArray<(String, isize)>>::from_ocamlrep(array).map(|a| a.into_vec()),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn atoms() {
+        for tag in [0, 3, 255] {
+            let block = super::atom(tag).as_block().unwrap();
+            assert_eq!((block.size(), block.tag()), (0, tag));
+            assert_eq!(block.header().color(), crate::Color::NOT_MARKABLE);
+        }
+        assert_eq!(
+            super::atom(0).as_block().unwrap().header().to_bits(),
+            ocaml_static!(array[])
+                .as_block()
+                .unwrap()
+                .header()
+                .to_bits()
+        );
+    }
+
+    #[test]
+    fn headers_are_not_markable() {
+        let value = ocaml_static!(block(3; "a", array[]));
+        let block = value.as_block().unwrap();
+        assert_eq!(block.tag(), 3);
+        assert_eq!(block.header().color(), crate::Color::NOT_MARKABLE);
+        let empty = block[1].as_block().unwrap();
+        assert_eq!((empty.size(), empty.tag()), (0, 0));
+        assert_eq!(empty.header().color(), crate::Color::NOT_MARKABLE);
+    }
+}
@@ -128,29 +128,26 @@ impl FromOcamlRep for U32Pair {
 
 #[test]
 fn differently_typed_views_of_same_data() {
-    // `Allocator::memoized` is keyed solely off of address and size in bytes.
-    // If we have two views of the same bytes, but the views have two different
-    // OCaml representations, then `Allocator::add_root` will produce an OCaml
-    // value with an unexpected type.
+    // `Allocator::memoized` is keyed off of address, size in bytes, and
+    // `TypeId`. If we have two views of the same bytes, but the views have two
+    // different OCaml representations, then `Allocator::add_root` must convert
+    // each view separately.
     //
-    // Here, `pair_as_int` gets converted first, and memoized. Since its address
-    // and size are the same as `pair`, the allocator uses the same memoized
-    // OCaml value for both. But the implementations of `ToOcamlRep` and
-    // `FromOcamlRep` for U32Pair specify that the OCaml representation is a
-    // tuple, not an immediate integer, so we encounter an error when trying to
-    // convert the OCaml value to `(u64, U32Pair)`.
+    // Here, `pair_as_int` gets converted first, and memoized. Its address and
+    // size are the same as `pair`, but since its type differs, the allocator
+    // does not reuse the memoized OCaml value (an immediate integer) for
+    // `pair` (whose OCaml representation is a tuple).
     let arena = Arena::new();
     let pair = &U32Pair::new(1, 2);
     let pair_as_int = pair.inner();
     let value = (pair_as_int, pair);
 
-    use ocamlrep::FromError::*;
     assert_eq!(
         <(u64, U32Pair)>::from_ocamlrep(arena.add_root(&value)),
-        Err(ErrorInField(1, Box::new(ExpectedBlock(1 << 32 | 2))))
+        Ok((1 << 32 | 2, U32Pair::new(1, 2)))
     );
 
-    // Using arena.add instead produces a correct result.
+    // Using arena.add produces the same result.
     assert_eq!(
         <(u64, U32Pair)>::from_ocamlrep(arena.add(&value)),
         Ok((1 << 32 | 2, U32Pair::new(1, 2)))
@@ -194,3 +191,70 @@ fn shared_arcs() {
     assert_eq!(inner_tuple[0].as_int(), Some(1));
     assert_eq!(inner_tuple[1].as_int(), Some(2));
 }
+
+/// A type whose `ToOcamlRep` implementation uses `add_root` to preserve sharing
+/// between its fields.
+struct SharedPair<'a>((&'a str, &'a str));
+
+impl ToOcamlRep for SharedPair<'_> {
+    fn to_ocamlrep<'a, A: Allocator>(&'a self, alloc: &'a A) -> ocamlrep::Value<'a> {
+        alloc.add_root(&self.0)
+    }
+}
+
+#[test]
+fn nested_add_root() {
+    let arena = Arena::new();
+    let s = "hello";
+
+    // Outside of an outer `add_root`, the inner `add_root` preserves sharing.
+    let pair = SharedPair((s, s));
+    let value = arena.add(&pair).as_block().unwrap();
+    assert_eq!(value[0].to_bits(), value[1].to_bits());
+
+    // Within an outer `add_root`, the inner `add_root` does not panic, and
+    // reuses values memoized in the outer scope.
+    let tuple = (s, SharedPair((s, s)), s);
+    let value = arena.add_root(&tuple).as_block().unwrap();
+    let inner = value[1].as_block().unwrap();
+    assert_eq!(value[0].to_bits(), inner[0].to_bits());
+    assert_eq!(inner[0].to_bits(), inner[1].to_bits());
+    assert_eq!(value[0].to_bits(), value[2].to_bits());
+
+    // Values memoized in the inner scope are forgotten when it returns.
+    let tuple = (SharedPair((s, s)), s);
+    let value = arena.add_root(&tuple).as_block().unwrap();
+    let inner = value[0].as_block().unwrap();
+    assert_eq!(inner[0].to_bits(), inner[1].to_bits());
+    assert_ne!(inner[0].to_bits(), value[1].to_bits());
+
+    // After the outer `add_root` returns, memoized values are not reused.
+    let value2 = arena.add_root(&tuple).as_block().unwrap();
+    assert_ne!(value[1].to_bits(), value2[1].to_bits());
+}
+
+#[test]
+fn add_root_after_panic() {
+    struct Panics;
+    impl ToOcamlRep for Panics {
+        fn to_ocamlrep<'a, A: Allocator>(&'a self, _alloc: &'a A) -> ocamlrep::Value<'a> {
+            panic!("conversion failed")
+        }
+    }
+
+    let arena = Arena::new();
+    let s = "hello";
+    let value = (s, Panics);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        arena.add_root(&value);
+    }));
+    assert!(result.is_err());
+
+    // The cache from the aborted conversion was cleared: `add` does not
+    // preserve sharing, and `add_root` does not panic.
+    let tuple = (s, s);
+    let value = arena.add(&tuple).as_block().unwrap();
+    assert_ne!(value[0].to_bits(), value[1].to_bits());
+    let value = arena.add_root(&tuple).as_block().unwrap();
+    assert_eq!(value[0].to_bits(), value[1].to_bits());
+}
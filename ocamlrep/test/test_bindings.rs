@@ -3,9 +3,19 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::LinkedList;
+use std::collections::VecDeque;
+use std::num::NonZero;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 use ocamlrep::FromOcamlRep;
 use ocamlrep::ToOcamlRep;
@@ -291,6 +301,145 @@ pub extern "C" fn roundtrip_int64(value: usize) -> usize {
     val(i)
 }
 
+// Std type tests
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_small_ints(_unit: usize) -> usize {
+    val((-128i8, i16::MIN, u16::MAX, 1u128 << 60))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_f32(_unit: usize) -> usize {
+    val(1.5f32)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_nonzero(_unit: usize) -> usize {
+    val(NonZero::new(5usize).unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_int_array(_unit: usize) -> usize {
+    val([1isize, 2, 3])
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_vec_deque(_unit: usize) -> usize {
+    let mut deque = VecDeque::from([2isize, 3]);
+    deque.push_front(1);
+    val(deque)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_linked_list(_unit: usize) -> usize {
+    val(LinkedList::from([1isize, 2, 3]))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_binary_heap(_unit: usize) -> usize {
+    val(BinaryHeap::from([3isize, 1, 2]))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_int_hash_map(_unit: usize) -> usize {
+    let mut map = HashMap::new();
+    map.insert(String::from("c"), 3isize);
+    map.insert(String::from("a"), 1);
+    map.insert(String::from("b"), 2);
+    val(map)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_hash_set(_unit: usize) -> usize {
+    let mut set = HashSet::new();
+    set.insert(String::from("c"));
+    set.insert(String::from("a"));
+    set.insert(String::from("b"));
+    val(set)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_std_strs(_unit: usize) -> usize {
+    val((
+        Box::<str>::from("box"),
+        Rc::<str>::from("rc"),
+        Arc::<str>::from("arc"),
+    ))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_cow_slice(_unit: usize) -> usize {
+    val(Cow::<[isize]>::Owned(vec![1, 2, 3]))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_range(_unit: usize) -> usize {
+    val(3isize..7)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn roundtrip_duration(value: usize) -> usize {
+    let value = unsafe { ocamlrep::Value::from_bits(value) };
+    val(Duration::from_ocamlrep(value).unwrap())
+}
+
+// Hashtbl tests
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_hashtbl(_unit: usize) -> usize {
+    let table: ocamlrep::OcamlHashtbl<String, isize> =
+        (0..100).map(|i| (format!("key{i}"), i)).collect();
+    val(table)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn roundtrip_hashtbl(value: usize) -> usize {
+    let value = unsafe { ocamlrep::Value::from_bits(value) };
+    val(ocamlrep::OcamlHashtbl::<String, isize>::from_ocamlrep(value).unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_lazy_float(_unit: usize) -> usize {
+    val(ocamlrep::OcamlLazy::new(1.5f64))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_lazy_string(_unit: usize) -> usize {
+    let cell = std::cell::LazyCell::new(|| String::from("forced in Rust"));
+    let arena = Box::leak(Box::new(ocamlrep::Arena::new()));
+    arena.add(&cell).to_bits()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn forced_lazy_int(value: usize) -> usize {
+    let value = unsafe { ocamlrep::Value::from_bits(value) };
+    let result = match ocamlrep::OcamlLazy::<isize>::from_ocamlrep(value) {
+        Ok(lazy) => Some(lazy.into_inner()),
+        Err(ocamlrep::FromError::UnforcedLazy(_)) => None,
+        Err(err) => panic!("{err}"),
+    };
+    val(result)
+}
+
+// Borrowing tests
+
+ocamlrep_ocamlpool::ocaml_ffi_ref! {
+    fn count_words<'a>(text: &'a str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    fn sum_float_array<'a>(floats: &'a ocamlrep::FloatArraySlice) -> f64 {
+        floats.iter().sum()
+    }
+}
+
+ocamlrep_ocamlpool::ocaml_ffi_ref_fn! {
+    fn longest_str<'a>(strs: Vec<&'a str>, bytes: &'a [u8]) -> (String, usize) {
+        let longest = strs.into_iter().max_by_key(|s| s.len()).unwrap_or_default();
+        (longest.to_owned(), bytes.len())
+    }
+}
+
 // Hack! Trick buck into believing that these libraries are used. See [Note:
 // Test blocks for Cargo] in `ocamlrep_ocamlpool/test/ocamlpool_test.rs`.
 const _: () = {
@@ -5,13 +5,45 @@
 
 #![cfg(test)]
 
+use std::borrow::Cow;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::LinkedList;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::num::NonZero;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
 use ocamlrep::Allocator;
 use ocamlrep::Arena;
+use ocamlrep::FieldName;
 use ocamlrep::FromError::*;
 use ocamlrep::FromOcamlRep;
+use ocamlrep::OcamlLazy;
 use ocamlrep::ToOcamlRep;
 use ocamlrep::Value;
 
+fn field_name(
+    type_name: &'static str,
+    variant_name: Option<&'static str>,
+    field_name: &'static str,
+) -> FieldName {
+    FieldName {
+        type_name,
+        variant_name,
+        field_name,
+    }
+}
+
+fn test_round_trip<T: FromOcamlRep + ToOcamlRep + Debug + PartialEq>(rust_value: T) {
+    let arena = Arena::new();
+    let ocaml_value = arena.add(&rust_value);
+    assert_eq!(T::from_ocamlrep(ocaml_value), Ok(rust_value));
+}
+
 #[test]
 fn expected_block_but_got_int() {
     let value = Value::int(42);
@@ -81,7 +113,10 @@ fn bad_struct_field() {
         block.build()
     };
     let err = Foo::from_ocamlrep(value).err().unwrap();
-    assert_eq!(err, ErrorInField(1, Box::new(ExpectedBool(42))));
+    assert_eq!(
+        err,
+        ErrorInNamedField(field_name("Foo", None, "b"), Box::new(ExpectedBool(42)))
+    );
 }
 
 #[derive(FromOcamlRep, ToOcamlRep)]
@@ -111,7 +146,45 @@ fn bad_nested_struct_field() {
     let err = Bar::from_ocamlrep(outer).err().unwrap();
     assert_eq!(
         err,
-        ErrorInField(0, Box::new(ErrorInField(1, Box::new(ExpectedBool(42)))))
+        ErrorInNamedField(
+            field_name("Bar", None, "c"),
+            Box::new(ErrorInNamedField(
+                field_name("Foo", None, "b"),
+                Box::new(ExpectedBool(42))
+            ))
+        )
+    );
+    assert_eq!(err.to_string(), "Bar.c -> Foo.b: Expected bool, but got 42");
+}
+
+#[derive(FromOcamlRep, ToOcamlRep)]
+struct Flags {
+    flags: Vec<bool>,
+}
+
+#[derive(FromOcamlRep, ToOcamlRep)]
+struct Ints {
+    ints: Vec<isize>,
+}
+
+#[test]
+fn bad_list_element_in_struct_field() {
+    let arena = Arena::new();
+    let ints = Ints {
+        ints: vec![0, 1, 42],
+    };
+    let value = arena.add(&ints);
+    let err = Flags::from_ocamlrep(value).err().unwrap();
+    assert_eq!(
+        err,
+        ErrorInNamedField(
+            field_name("Flags", None, "flags"),
+            Box::new(ErrorInField(2, Box::new(ExpectedBool(42))))
+        )
+    );
+    assert_eq!(
+        err.to_string(),
+        "Flags.flags -> [2]: Expected bool, but got 42"
     );
 }
 
@@ -193,7 +266,14 @@ fn bad_tuple_variant_value() {
         orange.build()
     };
     let err = Fruit::from_ocamlrep(orange).err().unwrap();
-    assert_eq!(err, ErrorInField(0, Box::new(ExpectedBool(42))));
+    assert_eq!(
+        err,
+        ErrorInNamedField(
+            field_name("Fruit", Some("Orange"), "0"),
+            Box::new(ExpectedBool(42))
+        )
+    );
+    assert_eq!(err.to_string(), "Fruit.Orange.0: Expected bool, but got 42");
 }
 
 #[test]
@@ -205,7 +285,13 @@ fn bad_struct_variant_value() {
         pear.build()
     };
     let err = Fruit::from_ocamlrep(pear).err().unwrap();
-    assert_eq!(err, ErrorInField(0, Box::new(ExpectedBool(42))));
+    assert_eq!(
+        err,
+        ErrorInNamedField(
+            field_name("Fruit", Some("Pear"), "is_tasty"),
+            Box::new(ExpectedBool(42))
+        )
+    );
 }
 
 #[test]
@@ -233,3 +319,189 @@ fn round_trip_through_ocaml_value_unsigned_int() {
     assert_eq!((num_int as u64) & !(1 << 63), num);
     assert_eq!(num_uint as u64, num);
 }
+
+#[test]
+fn round_trip_std_numeric_types() {
+    test_round_trip(-128i8);
+    test_round_trip(i16::MAX);
+    test_round_trip(u16::MAX);
+    test_round_trip(-(1i128 << 60));
+    test_round_trip(1u128 << 60);
+    test_round_trip(1.5f32);
+    test_round_trip(NonZero::new(42usize).unwrap());
+    test_round_trip(NonZero::new(-1i8).unwrap());
+    test_round_trip(Duration::from_millis(1500));
+    test_round_trip(Duration::ZERO);
+}
+
+#[test]
+fn out_of_range_128_bit_ints_are_truncated() {
+    let arena = Arena::new();
+    assert_eq!(arena.add(&((1u128 << 64) | 5)), Value::int(5));
+    assert_eq!(arena.add(&(i128::MIN + 7)), Value::int(7));
+    assert_eq!(arena.add(&i128::MAX), Value::int(-1));
+    assert!(u128::from_ocamlrep(arena.add(&u128::MAX)).is_err());
+}
+
+#[test]
+fn round_trip_std_collections() {
+    test_round_trip([1isize, 2, 3]);
+    test_round_trip::<[isize; 0]>([]);
+    test_round_trip(VecDeque::from([1isize, 2, 3]));
+    test_round_trip(LinkedList::from([true, false]));
+    test_round_trip(HashMap::from([
+        (String::from("a"), 1isize),
+        (String::from("b"), 2),
+    ]));
+    test_round_trip(HashSet::from([String::from("a"), String::from("b")]));
+    test_round_trip::<Cow<'_, [isize]>>(Cow::Borrowed(&[1, 2]));
+    test_round_trip::<Cow<'_, [u8]>>(Cow::Borrowed(b"bytes"));
+    test_round_trip(3isize..7);
+
+    // BinaryHeap doesn't implement PartialEq.
+    let arena = Arena::new();
+    let heap = BinaryHeap::from([2isize, 3, 1]);
+    let value = arena.add(&heap);
+    assert_eq!(Vec::<isize>::from_ocamlrep(value), Ok(vec![1, 2, 3]));
+    let heap = BinaryHeap::<isize>::from_ocamlrep(value).unwrap();
+    assert_eq!(heap.into_sorted_vec(), vec![1, 2, 3]);
+}
+
+#[test]
+fn round_trip_std_string_types() {
+    test_round_trip(Box::<str>::from("boxed"));
+    test_round_trip(Rc::<str>::from("rc"));
+    test_round_trip(Arc::<str>::from("arc"));
+}
+
+#[test]
+fn std_types_match_ocaml_representation() {
+    fn assert_same_repr<T: ToOcamlRep, U: ToOcamlRep>(a: &T, b: &U) {
+        let arena = Arena::new();
+        // The Debug impl for Value renders the structure of the value.
+        assert_eq!(format!("{:?}", arena.add(a)), format!("{:?}", arena.add(b)));
+    }
+    // Unlike Vec, HashMap and HashSet become OCaml Maps and Sets.
+    let map = HashMap::from([(String::from("b"), 2isize), (String::from("a"), 1)]);
+    let btree_map: std::collections::BTreeMap<_, _> = map.clone().into_iter().collect();
+    assert_same_repr(&map, &btree_map);
+    let set = HashSet::from([3isize, 1, 2]);
+    let btree_set: std::collections::BTreeSet<_> = set.iter().copied().collect();
+    assert_same_repr(&set, &btree_set);
+    // Range is a pair, Duration a float number of seconds.
+    assert_same_repr(&(3isize..7), &(3isize, 7isize));
+    assert_same_repr(&Duration::from_millis(250), &0.25f64);
+}
+
+#[test]
+fn small_int_out_of_range() {
+    let value = Value::int(128);
+    let err = i8::from_ocamlrep(value).err().unwrap();
+    match err {
+        IntOutOfRange(..) => {}
+        _ => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn expected_nonzero() {
+    let err = NonZero::<isize>::from_ocamlrep(Value::int(0))
+        .err()
+        .unwrap();
+    match err {
+        IntOutOfRange(..) => {}
+        _ => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn wrong_array_length() {
+    let arena = Arena::new();
+    let list = vec![1isize, 2];
+    let value = arena.add(&list);
+    let err = <[isize; 3]>::from_ocamlrep(value).err().unwrap();
+    assert_eq!(
+        err,
+        WrongListLength {
+            expected: 3,
+            actual: 2
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Expected list of length 3, but got length 2"
+    );
+}
+
+#[test]
+fn negative_duration() {
+    let arena = Arena::new();
+    let value = arena.add(&-1.0f64);
+    let err = Duration::from_ocamlrep(value).err().unwrap();
+    match err {
+        DurationOutOfRange(..) => {}
+        _ => panic!("unexpected error: {err}"),
+    }
+}
+
+#[derive(FromOcamlRep, ToOcamlRep, Debug, PartialEq)]
+struct WithLazyField {
+    name: String,
+    value: OcamlLazy<(isize, f64)>,
+}
+
+#[test]
+fn lazy_field() {
+    let arena = Arena::new();
+    let pair = arena.add(&(1isize, 2.5f64));
+    let expected = WithLazyField {
+        name: String::from("x"),
+        value: OcamlLazy::new((1, 2.5)),
+    };
+
+    // `Lazy.force` leaves a forward block in the field...
+    let mut forward = arena.block_with_size_and_tag(1, ocamlrep::FORWARD_TAG);
+    arena.set_field(&mut forward, 0, pair);
+    let forward = forward.build();
+    let mut record = arena.block_with_size(2);
+    arena.set_field(&mut record, 0, arena.add("x"));
+    arena.set_field(&mut record, 1, forward);
+    assert_eq!(
+        WithLazyField::from_ocamlrep(record.build()).as_ref(),
+        Ok(&expected)
+    );
+
+    // ...which the GC may later replace with the value itself.
+    let mut record = arena.block_with_size(2);
+    arena.set_field(&mut record, 0, arena.add("x"));
+    arena.set_field(&mut record, 1, pair);
+    assert_eq!(
+        WithLazyField::from_ocamlrep(record.build()).as_ref(),
+        Ok(&expected)
+    );
+
+    test_round_trip(expected);
+}
+
+#[test]
+fn unforced_lazy_field() {
+    let arena = Arena::new();
+    let mut lazy = arena.block_with_size_and_tag(1, ocamlrep::LAZY_TAG);
+    arena.set_field(&mut lazy, 0, Value::int(0));
+    let lazy = lazy.build();
+    let mut record = arena.block_with_size(2);
+    arena.set_field(&mut record, 0, arena.add("x"));
+    arena.set_field(&mut record, 1, lazy);
+    let err = WithLazyField::from_ocamlrep(record.build()).err().unwrap();
+    assert_eq!(
+        err,
+        ErrorInNamedField(
+            field_name("WithLazyField", None, "value"),
+            Box::new(UnforcedLazy(ocamlrep::LAZY_TAG))
+        )
+    );
+    assert_eq!(
+        err.to_string(),
+        "WithLazyField.value: Expected forced lazy value, but got unforced lazy block with tag 246"
+    );
+}
@@ -5,6 +5,9 @@
 
 #![cfg(test)]
 
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Debug;
 
 use bumpalo::Bump;
@@ -47,6 +50,19 @@ fn convert_std_types() {
     test_round_trip(bump, Err::<&str, &str>("error"));
 }
 
+#[test]
+fn convert_std_collections() {
+    let bump = &Bump::new();
+
+    test_round_trip(
+        bump,
+        HashMap::from([(String::from("a"), 1isize), (String::from("b"), 2)]),
+    );
+    test_round_trip(bump, HashSet::from([1isize, 2, 3]));
+    test_round_trip::<Cow<'_, [isize]>>(bump, Cow::Borrowed(&[1, 2]));
+    test_round_trip::<Cow<'_, [u8]>>(bump, Cow::Borrowed(b"bytes"));
+}
+
 #[derive(Debug, FromOcamlRepIn, ToOcamlRep, PartialEq)]
 struct Foo<'a> {
     bar: &'a usize,
@@ -84,3 +100,50 @@ fn convert_boxed_tuple_variant() {
     let bump = &Bump::new();
     test_round_trip(bump, Fruit::Peach(bump.alloc((42, true))));
 }
+
+#[derive(Debug, FromOcamlRepIn, ToOcamlRep, PartialEq)]
+struct Arrays<'a> {
+    #[ocamlrep(array)]
+    items: &'a [(isize, &'a str)],
+    #[ocamlrep(float_array)]
+    floats: Vec<f64>,
+}
+
+#[test]
+fn convert_array_fields() {
+    let bump = &Bump::new();
+    test_round_trip(
+        bump,
+        Arrays {
+            items: bump.alloc_slice_copy(&[(1, "one"), (2, "two")]),
+            floats: vec![0.5, -1.0],
+        },
+    );
+    test_round_trip(
+        bump,
+        Arrays {
+            items: &[],
+            floats: vec![],
+        },
+    );
+}
+
+#[derive(Debug, FromOcamlRepIn, ToOcamlRep, PartialEq)]
+struct Env<'a> {
+    #[ocamlrep(assoc_list)]
+    vars: std::collections::BTreeMap<&'a str, isize>,
+    #[ocamlrep(assoc_list)]
+    scopes: &'a [(isize, &'a str)],
+}
+
+#[test]
+fn convert_assoc_list_fields() {
+    let bump = &Bump::new();
+    test_round_trip(
+        bump,
+        Env {
+            vars: [("b", 2), ("a", 1)].into_iter().collect(),
+            scopes: bump.alloc_slice_copy(&[(1, "one"), (0, "zero")]),
+        },
+    );
+}
@@ -0,0 +1,275 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+#![cfg(test)]
+
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+use ocamlrep::Allocator;
+use ocamlrep::Arena;
+use ocamlrep::FieldName;
+use ocamlrep::FloatArraySlice;
+use ocamlrep::FromError;
+use ocamlrep::FromOcamlRepRef;
+use ocamlrep::ToOcamlRep;
+use ocamlrep::Value;
+
+fn test_round_trip<'a, T>(arena: &'a Arena, rust_value: &'a T)
+where
+    T: FromOcamlRepRef<'a> + ToOcamlRep + Debug + PartialEq,
+{
+    let ocaml_value = arena.add(rust_value);
+    assert_eq!(T::from_ocamlrep_ref(ocaml_value).as_ref(), Ok(rust_value));
+}
+
+/// Return the address range of the given value's block (including its
+/// header).
+fn block_range(value: Value<'_>) -> std::ops::Range<usize> {
+    let block = value.as_block().unwrap();
+    let start = value.to_bits() - std::mem::size_of::<usize>();
+    start..value.to_bits() + block.size() * std::mem::size_of::<usize>()
+}
+
+#[test]
+fn convert_primitives() {
+    let arena = Arena::new();
+    test_round_trip(&arena, &());
+    test_round_trip(&arena, &1isize);
+    test_round_trip(&arena, &2usize);
+    test_round_trip(&arena, &3i64);
+    test_round_trip(&arena, &4u64);
+    test_round_trip(&arena, &5i32);
+    test_round_trip(&arena, &6u32);
+    test_round_trip(&arena, &true);
+    test_round_trip(&arena, &'a');
+    test_round_trip(&arena, &7.7f64);
+    test_round_trip(&arena, &String::from("owned"));
+}
+
+#[test]
+fn convert_std_types() {
+    let arena = Arena::new();
+    test_round_trip(&arena, &None::<usize>);
+    test_round_trip(&arena, &Some(Box::new(5usize)));
+    test_round_trip(&arena, &Ok::<isize, String>(1));
+    test_round_trip(&arena, &Err::<isize, String>(String::from("error")));
+    test_round_trip(&arena, &vec![(1isize, true), (2, false)]);
+}
+
+#[test]
+fn str_borrows_from_value() {
+    let arena = Arena::new();
+    let value = arena.add("hello");
+    let s = <&str>::from_ocamlrep_ref(value).unwrap();
+    assert_eq!(s, "hello");
+    assert!(block_range(value).contains(&(s.as_ptr() as usize)));
+
+    let bytes = <&[u8]>::from_ocamlrep_ref(value).unwrap();
+    assert_eq!(bytes.as_ptr(), s.as_ptr());
+
+    match <Cow<'_, str>>::from_ocamlrep_ref(value).unwrap() {
+        Cow::Borrowed(b) => assert_eq!(b.as_ptr(), s.as_ptr()),
+        Cow::Owned(_) => panic!("expected a borrowed string"),
+    }
+}
+
+#[test]
+fn borrowed_list_of_strs() {
+    let arena = Arena::new();
+    let list = vec!["a", "bc", "def"];
+    let value = arena.add(&list);
+    let strs = <Vec<&str>>::from_ocamlrep_ref(value).unwrap();
+    assert_eq!(strs, ["a", "bc", "def"]);
+    let first = value.field(0).unwrap();
+    assert_eq!(strs[0].as_ptr() as usize, first.to_bits());
+}
+
+#[test]
+fn float_array() {
+    let arena = Arena::new();
+    let mut block = arena.block_with_size_and_tag(3, ocamlrep::DOUBLE_ARRAY_TAG);
+    for (i, f) in [1.5f64, -2.0, 0.25].into_iter().enumerate() {
+        arena.set_field(&mut block, i, unsafe {
+            Value::from_bits(f.to_bits() as usize)
+        });
+    }
+    let value = block.build();
+    let floats = <&FloatArraySlice>::from_ocamlrep_ref(value).unwrap();
+    assert_eq!(floats.as_slice(), [1.5, -2.0, 0.25]);
+    assert_eq!(floats.as_ptr() as usize, value.to_bits());
+
+    // The empty array is an atom (a block of size 0 and tag 0).
+    let atom = [ocamlrep::Header::new(0, 0).to_bits()];
+    let empty = unsafe { Value::from_bits(atom.as_ptr().add(1) as usize) };
+    assert_eq!(
+        <&FloatArraySlice>::from_ocamlrep_ref(empty).map(FloatArraySlice::as_slice),
+        Ok(&[][..])
+    );
+
+    assert_eq!(
+        <&FloatArraySlice>::from_ocamlrep_ref(arena.add("not floats"))
+            .map(FloatArraySlice::as_slice),
+        Err(FromError::ExpectedBlockTag {
+            expected: ocamlrep::DOUBLE_ARRAY_TAG,
+            actual: ocamlrep::STRING_TAG,
+        })
+    );
+}
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+struct Decl<'a> {
+    name: &'a str,
+    line: usize,
+    #[ocamlrep(skip)]
+    cached: Option<usize>,
+    doc: Option<&'a str>,
+}
+
+#[test]
+fn convert_struct_with_refs() {
+    let arena = Arena::new();
+    test_round_trip(
+        &arena,
+        &Decl {
+            name: "foo",
+            line: 3,
+            cached: None,
+            doc: Some("documentation"),
+        },
+    );
+}
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+struct Name<'a>(&'a str);
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+struct Pair<T>(T, T);
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+struct Marker;
+
+#[test]
+fn convert_newtype_generic_and_unit_structs() {
+    let arena = Arena::new();
+    test_round_trip(&arena, &Name("newtype"));
+    test_round_trip(&arena, &Pair(1isize, 2));
+    test_round_trip(&arena, &Pair(Name("a"), Name("b")));
+    test_round_trip(&arena, &Marker);
+}
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+enum Fruit<'a> {
+    Apple,
+    Orange(&'a str),
+    Pear { is_tasty: bool },
+    Kiwi,
+    Peach(Box<(isize, &'a str)>),
+}
+
+#[test]
+fn convert_enum() {
+    let arena = Arena::new();
+    test_round_trip(&arena, &Fruit::Apple);
+    test_round_trip(&arena, &Fruit::Orange("mandarin"));
+    test_round_trip(&arena, &Fruit::Pear { is_tasty: true });
+    test_round_trip(&arena, &Fruit::Kiwi);
+    test_round_trip(&arena, &Fruit::Peach(Box::new((42, "peach"))));
+}
+
+#[test]
+fn error_names_field() {
+    let arena = Arena::new();
+    let tuple = ("foo", 3usize, 42isize);
+    let value = arena.add(&tuple);
+    assert_eq!(
+        Decl::from_ocamlrep_ref(value),
+        Err(FromError::ErrorInNamedField(
+            FieldName {
+                type_name: "Decl",
+                variant_name: None,
+                field_name: "doc",
+            },
+            Box::new(FromError::NullaryVariantTagOutOfRange { max: 0, actual: 42 })
+        ))
+    );
+}
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+struct Arrays<'a> {
+    #[ocamlrep(array)]
+    names: Vec<String>,
+    #[ocamlrep(float_array)]
+    floats: &'a [f64],
+    #[ocamlrep(array)]
+    strs: Vec<&'a str>,
+    list: Vec<isize>,
+}
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+struct ArrayNewtype(#[ocamlrep(array)] Box<[isize]>);
+
+#[test]
+fn convert_array_fields() {
+    let arena = Arena::new();
+    let arrays = Arrays {
+        names: vec![String::from("x"), String::from("y")],
+        floats: &[1.5, 2.5],
+        strs: vec!["a", "b"],
+        list: vec![1, 2],
+    };
+    test_round_trip(&arena, &arrays);
+
+    let value = arena.add(&arrays);
+    let names = value.field(0).unwrap().as_block().unwrap();
+    assert_eq!((names.tag(), names.size()), (0, 2));
+    let floats = value.field(1).unwrap().as_block().unwrap();
+    assert_eq!(floats.tag(), ocamlrep::DOUBLE_ARRAY_TAG);
+    // The list field is still converted to a list.
+    assert_eq!(
+        value.field(3).unwrap().field(1).unwrap().field(1),
+        Some(Value::int(0))
+    );
+    // Borrowed float arrays are not copied.
+    let floats = Arrays::from_ocamlrep_ref(value).unwrap().floats;
+    assert!(block_range(value.field(1).unwrap()).contains(&(floats.as_ptr() as usize)));
+
+    test_round_trip(&arena, &ArrayNewtype(Box::new([1, 2, 3])));
+    test_round_trip(&arena, &ArrayNewtype(Box::new([])));
+}
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+struct Env<'a> {
+    #[ocamlrep(assoc_list)]
+    vars: std::collections::BTreeMap<&'a str, isize>,
+    #[ocamlrep(assoc_list)]
+    pairs: Vec<(&'a str, bool)>,
+}
+
+#[derive(Debug, FromOcamlRepRef, ToOcamlRep, PartialEq)]
+struct EnvNewtype(#[ocamlrep(assoc_list)] std::collections::HashMap<String, isize>);
+
+#[test]
+fn convert_assoc_list_fields() {
+    let arena = Arena::new();
+    let env = Env {
+        vars: [("b", 2), ("a", 1)].into_iter().collect(),
+        pairs: vec![("y", true), ("x", false)],
+    };
+    test_round_trip(&arena, &env);
+
+    // The map is converted to a list of pairs rather than an OCaml Map.
+    let value = arena.add(&env);
+    let vars = <Vec<(&str, isize)>>::from_ocamlrep_ref(value.field(0).unwrap());
+    assert_eq!(vars, Ok(vec![("a", 1), ("b", 2)]));
+
+    let newtype = EnvNewtype([(String::from("a"), 1)].into_iter().collect());
+    test_round_trip(&arena, &newtype);
+    let value = arena.add(&newtype);
+    assert_eq!(
+        <Vec<(String, isize)>>::from_ocamlrep_ref(value),
+        Ok(vec![(String::from("a"), 1)])
+    );
+}
@@ -121,6 +121,55 @@ external get_sset_singleton : unit -> SSet.t = "get_sset_singleton"
 
 external get_sset : unit -> SSet.t = "get_sset"
 
+(* std type tests *)
+external get_small_ints : unit -> int * int * int * int = "get_small_ints"
+
+external get_f32 : unit -> float = "get_f32"
+
+external get_nonzero : unit -> int = "get_nonzero"
+
+external get_int_array : unit -> int list = "get_int_array"
+
+external get_vec_deque : unit -> int list = "get_vec_deque"
+
+external get_linked_list : unit -> int list = "get_linked_list"
+
+external get_binary_heap : unit -> int list = "get_binary_heap"
+
+external get_int_hash_map : unit -> int SMap.t = "get_int_hash_map"
+
+external get_hash_set : unit -> SSet.t = "get_hash_set"
+
+external get_std_strs : unit -> string * string * string = "get_std_strs"
+
+external get_cow_slice : unit -> int list = "get_cow_slice"
+
+external get_range : unit -> int * int = "get_range"
+
+external roundtrip_duration : float -> float = "roundtrip_duration"
+
+(* hashtbl tests *)
+external get_hashtbl : unit -> (string, int) Hashtbl.t = "get_hashtbl"
+
+external roundtrip_hashtbl : (string, int) Hashtbl.t -> (string, int) Hashtbl.t
+  = "roundtrip_hashtbl"
+
+(* lazy tests *)
+external get_lazy_float : unit -> float Lazy.t = "get_lazy_float"
+
+external get_lazy_string : unit -> string Lazy.t = "get_lazy_string"
+
+external forced_lazy_int : int Lazy.t -> int option = "forced_lazy_int"
+
+type lazy_record = { lazy_field : int Lazy.t }
+
+(* borrowing tests *)
+external count_words : string -> int = "count_words"
+
+external sum_float_array : float array -> float = "sum_float_array"
+
+external longest_str : string list -> bytes -> string * int = "longest_str"
+
 external convert_to_ocamlrep : 'a -> 'a = "convert_to_ocamlrep"
 
 external realloc_in_ocaml_heap : 'a -> 'a = "realloc_in_ocaml_heap"
@@ -327,6 +376,106 @@ let test_sset () =
   | ["a"; "b"; "c"] -> ()
   | _ -> assert false
 
+let test_small_ints () =
+  match get_small_ints () with
+  | (-128, -32768, 65535, n) -> assert (n = 1 lsl 60)
+  | _ -> assert false
+
+let test_f32 () = assert (Float.equal (get_f32 ()) 1.5)
+
+let test_nonzero () = assert (get_nonzero () = 5)
+
+let test_std_lists () =
+  List.iter
+    (fun get ->
+      match get () with
+      | [1; 2; 3] -> ()
+      | _ -> assert false)
+    [
+      get_int_array;
+      get_vec_deque;
+      get_linked_list;
+      get_binary_heap;
+      get_cow_slice;
+    ]
+
+let test_int_hash_map () =
+  match SMap.bindings (get_int_hash_map ()) with
+  | [("a", 1); ("b", 2); ("c", 3)] -> ()
+  | _ -> assert false
+
+let test_hash_set () =
+  match SSet.elements (get_hash_set ()) with
+  | ["a"; "b"; "c"] -> ()
+  | _ -> assert false
+
+let test_std_strs () =
+  match get_std_strs () with
+  | ("box", "rc", "arc") -> ()
+  | _ -> assert false
+
+let test_range () =
+  match get_range () with
+  | (3, 7) -> ()
+  | _ -> assert false
+
+let test_duration () =
+  let cases = [0.; 0.25; 1.5; 86400.] in
+  List.iter (fun x -> assert (Float.equal (roundtrip_duration x) x)) cases
+
+let test_hashtbl () =
+  let tbl = get_hashtbl () in
+  assert (Hashtbl.length tbl = 100);
+  for i = 0 to 99 do
+    assert (Hashtbl.find tbl (Printf.sprintf "key%d" i) = i)
+  done;
+  assert (not (Hashtbl.mem tbl "key100"));
+  (* The table lives outside the OCaml heap, so copy it before mutating it.
+     Hashtbl.copy preserves the layout of the buckets. *)
+  let tbl = Hashtbl.copy tbl in
+  for i = 100 to 999 do
+    Hashtbl.add tbl (Printf.sprintf "key%d" i) i
+  done;
+  for i = 0 to 999 do
+    assert (Hashtbl.find tbl (Printf.sprintf "key%d" i) = i)
+  done
+
+let test_roundtrip_hashtbl () =
+  let tbl = Hashtbl.create 0 in
+  Hashtbl.add tbl "a" 1;
+  Hashtbl.add tbl "b" 2;
+  Hashtbl.add tbl "a" 3;
+  let tbl = roundtrip_hashtbl tbl in
+  assert (Hashtbl.length tbl = 2);
+  assert (Hashtbl.find tbl "a" = 3);
+  assert (Hashtbl.find tbl "b" = 2)
+
+let test_lazy () =
+  assert (Lazy.is_val (get_lazy_float ()));
+  assert (Float.equal (Lazy.force (get_lazy_float ())) 1.5);
+  assert (Lazy.is_val (get_lazy_string ()));
+  assert (Lazy.force (get_lazy_string ()) = "forced in Rust");
+  assert (forced_lazy_int (Lazy.from_val 5) = Some 5);
+  let l = lazy (List.length [1; 2; 3]) in
+  assert (forced_lazy_int l = None);
+  ignore (Lazy.force l);
+  assert (forced_lazy_int l = Some 3);
+  (* Once forced, the GC may short-circuit the forward block in the field. *)
+  let r = { lazy_field = lazy (Sys.opaque_identity 40 + 2) } in
+  ignore (Lazy.force r.lazy_field);
+  assert (forced_lazy_int r.lazy_field = Some 42);
+  Gc.full_major ();
+  assert (forced_lazy_int r.lazy_field = Some 42)
+
+let test_borrowed_args () =
+  assert (count_words "one two  three" = 3);
+  assert (count_words "" = 0);
+  assert (Float.equal (sum_float_array [| 1.5; 2.5; -1. |]) 3.);
+  assert (Float.equal (sum_float_array [||]) 0.);
+  let (longest, len) = longest_str ["a"; "abc"; "ab"] (Bytes.of_string "xy") in
+  assert (longest = "abc");
+  assert (len = 2)
+
 (* Conversion tests *)
 
 let test_convert_char () =
@@ -751,6 +900,19 @@ let test_cases =
     test_empty_sset;
     test_sset_singleton;
     test_sset;
+    test_small_ints;
+    test_f32;
+    test_nonzero;
+    test_std_lists;
+    test_int_hash_map;
+    test_hash_set;
+    test_std_strs;
+    test_range;
+    test_duration;
+    test_hashtbl;
+    test_roundtrip_hashtbl;
+    test_lazy;
+    test_borrowed_args;
     test_convert_char;
     test_convert_int;
     test_convert_true;
@@ -0,0 +1,666 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A human-readable textual notation for untyped OCaml values, which preserves
+//! tags, strings, floats, and sharing. Useful for writing test fixtures by
+//! hand, and for logging values.
+//!
+//! The notation is:
+//!
+//! - `42`, `-1`: an immediate integer.
+//! - `"foo\n\xff"`: a string. Bytes other than printable ASCII are escaped
+//!   (`\"`, `\\`, `\n`, `\t`, `\r`, or `\xNN`).
+//! - `#double 1.5`: a boxed float. When parsing, a number containing a `.` or
+//!   an exponent (e.g., `2.5`) is also accepted as a boxed float, but
+//!   non-finite floats must be written as `#double nan`, `#double inf`, or
+//!   `#double -inf`.
+//! - `#double_array [1.5 2.0]`: a float array.
+//! - `(tag 0 [1 "foo" (tag 3 [2.5])])`: a block with the given tag and fields.
+//! - `(raw 251 [0x1 0x7f])`: a block whose contents are not scanned by the GC
+//!   (e.g., an abstract block, custom block, or closure), with its contents
+//!   given as raw words. Closures and custom blocks are printed, but cannot be
+//!   parsed, since their code and custom operations pointers cannot be
+//!   checked. Other raw blocks must be well-formed (see `validate`).
+//! - `#infix 3 (raw 247 [...])`: a pointer to the infix block at the given
+//!   word offset within a closure (as for mutually recursive functions). Like
+//!   closures, these are printed, but cannot be parsed.
+//! - `#shared 4`: a reference to a block which appears earlier in the text.
+//!   Blocks (including strings and floats) are numbered from 0 in the order in
+//!   which they begin.
+//!
+//! Whitespace separates tokens, and `;` begins a comment which extends to the
+//! end of the line.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+
+use crate::Allocator;
+use crate::Value;
+use crate::block;
+use crate::static_value;
+
+/// Print the given value in the textual notation described in the module
+/// documentation. Blocks reachable along more than one path are printed once,
+/// and referred to with `#shared` thereafter.
+pub fn to_string(value: Value<'_>) -> String {
+    enum Step<'a> {
+        Value(Value<'a>),
+        Text(&'static str),
+    }
+    let mut s = String::new();
+    let mut numbers: HashMap<usize, usize> = HashMap::new();
+    let mut stack = vec![Step::Value(value)];
+    while let Some(step) = stack.pop() {
+        let value = match step {
+            Step::Text(text) => {
+                s.push_str(text);
+                continue;
+            }
+            Step::Value(value) => value,
+        };
+        let block = match value.as_block() {
+            None => {
+                let _ = write!(s, "{}", value.as_int().unwrap());
+                continue;
+            }
+            Some(block) => block,
+        };
+        if block.tag() == block::INFIX_TAG {
+            // The size of an infix block is its offset (in words) from the
+            // start of its enclosing closure, which we print instead.
+            let offset = block.size();
+            let _ = write!(s, "#infix {offset} ");
+            let closure = value.to_bits() - offset * std::mem::size_of::<Value<'_>>();
+            // SAFETY: An infix block lies within its enclosing closure.
+            stack.push(Step::Value(unsafe { Value::from_bits(closure) }));
+            continue;
+        }
+        if let Some(number) = numbers.get(&value.to_bits()) {
+            let _ = write!(s, "#shared {number}");
+            continue;
+        }
+        numbers.insert(value.to_bits(), numbers.len());
+        match block.tag() {
+            block::STRING_TAG => write_string(&mut s, value.as_byte_string().unwrap()),
+            block::DOUBLE_TAG => {
+                let _ = write!(s, "#double {:?}", value.as_float().unwrap());
+            }
+            block::DOUBLE_ARRAY_TAG => {
+                s.push_str("#double_array [");
+                for (i, f) in value.as_double_array().unwrap().iter().enumerate() {
+                    if i > 0 {
+                        s.push(' ');
+                    }
+                    let _ = write!(s, "{f:?}");
+                }
+                s.push(']');
+            }
+            tag if tag >= block::NO_SCAN_TAG || tag == block::CLOSURE_TAG => {
+                let _ = write!(s, "(raw {tag} [");
+                for (i, word) in block.as_int_slice().iter().enumerate() {
+                    if i > 0 {
+                        s.push(' ');
+                    }
+                    let _ = write!(s, "{word:#x}");
+                }
+                s.push_str("])");
+            }
+            tag => {
+                let _ = write!(s, "(tag {tag} [");
+                stack.push(Step::Text("])"));
+                let fields = block.as_values().unwrap();
+                for (i, &field) in fields.iter().enumerate().rev() {
+                    stack.push(Step::Value(field));
+                    if i > 0 {
+                        stack.push(Step::Text(" "));
+                    }
+                }
+            }
+        }
+    }
+    s
+}
+
+fn write_string(s: &mut String, bytes: &[u8]) {
+    s.push('"');
+    for &byte in bytes {
+        match byte {
+            b'"' => s.push_str("\\\""),
+            b'\\' => s.push_str("\\\\"),
+            b'\n' => s.push_str("\\n"),
+            b'\t' => s.push_str("\\t"),
+            b'\r' => s.push_str("\\r"),
+            b' '..=b'~' => s.push(byte as char),
+            _ => {
+                let _ = write!(s, "\\x{byte:02x}");
+            }
+        }
+    }
+    s.push('"');
+}
+
+/// An error encountered while parsing the textual notation for values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset in the input at which the error was detected.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Parse error at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a value written in the textual notation described in the module
+/// documentation, allocating its blocks with `alloc`.
+///
+/// Empty blocks are not allocated: like OCaml, we use a static atom for each
+/// tag (so `(tag 0 [])` parses to the same value as `ocaml_static!(array[])`).
+/// Cyclic values (a `#shared` reference to an enclosing block), closures, and
+/// custom blocks cannot be allocated, and are reported as errors, as are raw
+/// blocks which fail `validate`.
+pub fn parse<'a, A: Allocator>(alloc: &'a A, text: &str) -> Result<Value<'a>, ParseError> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        pos: 0,
+    };
+    // The value of each block, indexed by its number (`None` for blocks which
+    // have begun but not yet ended).
+    let mut blocks: Vec<Option<Value<'a>>> = vec![];
+    // Blocks which have begun but not yet ended, with their tag, number, and
+    // the fields parsed so far.
+    let mut open: Vec<(u8, usize, Vec<Value<'a>>)> = vec![];
+    loop {
+        let start = parser.skip_whitespace();
+        let value = match parser.peek() {
+            None if open.is_empty() => return Err(parser.error("expected a value")),
+            None => return Err(parser.error("unterminated block")),
+            Some(b']') if !open.is_empty() => {
+                parser.pos += 1;
+                parser.expect(b')')?;
+                let (tag, number, fields) = open.pop().unwrap();
+                let value = if fields.is_empty() {
+                    static_value::atom(tag)
+                } else {
+                    let mut block = alloc.block_with_size_and_tag(fields.len(), tag);
+                    for (i, field) in fields.into_iter().enumerate() {
+                        alloc.set_field(&mut block, i, field);
+                    }
+                    block.build()
+                };
+                blocks[number] = Some(value);
+                value
+            }
+            Some(b'(') => {
+                parser.pos += 1;
+                let kind = parser.word();
+                let tag = parser.number::<u8>()?;
+                parser.expect(b'[')?;
+                if matches!(
+                    tag,
+                    block::CLOSURE_TAG | block::INFIX_TAG | block::CUSTOM_TAG
+                ) {
+                    return Err(ParseError {
+                        offset: start,
+                        message: "closures and custom blocks are not supported".to_string(),
+                    });
+                }
+                match kind {
+                    "tag" => {
+                        if tag >= block::NO_SCAN_TAG {
+                            return Err(parser.error("use `raw` for blocks with this tag"));
+                        }
+                        open.push((tag, blocks.len(), vec![]));
+                        blocks.push(None);
+                        continue;
+                    }
+                    "raw" => {
+                        if tag < block::NO_SCAN_TAG {
+                            return Err(parser.error("use `tag` for blocks with this tag"));
+                        }
+                        let mut words = vec![];
+                        while parser.skip_whitespace() < text.len() && parser.peek() != Some(b']') {
+                            words.push(parser.hex_word()?);
+                        }
+                        parser.expect(b']')?;
+                        parser.expect(b')')?;
+                        let value = if words.is_empty() {
+                            static_value::atom(tag)
+                        } else {
+                            let mut block = alloc.block_with_size_and_tag(words.len(), tag);
+                            for (i, word) in words.into_iter().enumerate() {
+                                alloc.set_field(&mut block, i, unsafe { Value::from_bits(word) });
+                            }
+                            block.build()
+                        };
+                        // The contents of no-scan blocks other than custom
+                        // blocks are not pointers, so we need only check that
+                        // they are consistent with the tag (e.g., that the
+                        // padding of a string is in bounds).
+                        if let Err(err) = crate::validate(value) {
+                            return Err(ParseError {
+                                offset: start,
+                                message: format!("invalid raw block: {err}"),
+                            });
+                        }
+                        push_block(&mut blocks, value)
+                    }
+                    _ => {
+                        return Err(ParseError {
+                            offset: start + 1,
+                            message: "expected `tag` or `raw`".to_string(),
+                        });
+                    }
+                }
+            }
+            Some(b'"') => {
+                let bytes = parser.string()?;
+                let mut w = alloc.byte_string_with_len(bytes.len());
+                let _ = std::io::Write::write(&mut w, &bytes).unwrap();
+                push_block(&mut blocks, w.build())
+            }
+            Some(b'#') => {
+                parser.pos += 1;
+                match parser.word() {
+                    "double" => {
+                        let f = parser.number::<f64>()?;
+                        push_block(&mut blocks, alloc_double(alloc, f))
+                    }
+                    "double_array" => {
+                        parser.expect(b'[')?;
+                        let mut floats = vec![];
+                        while parser.skip_whitespace() < text.len() && parser.peek() != Some(b']') {
+                            floats.push(parser.number::<f64>()?);
+                        }
+                        parser.expect(b']')?;
+                        if floats.is_empty() {
+                            push_block(&mut blocks, static_value::atom(block::DOUBLE_ARRAY_TAG))
+                        } else {
+                            let mut block = alloc
+                                .block_with_size_and_tag(floats.len(), block::DOUBLE_ARRAY_TAG);
+                            for (i, f) in floats.into_iter().enumerate() {
+                                let f = unsafe { Value::from_bits(f.to_bits() as usize) };
+                                alloc.set_field(&mut block, i, f);
+                            }
+                            push_block(&mut blocks, block.build())
+                        }
+                    }
+                    "shared" => {
+                        let number = parser.number::<usize>()?;
+                        match blocks.get(number) {
+                            Some(Some(value)) => *value,
+                            Some(None) => {
+                                return Err(ParseError {
+                                    offset: start,
+                                    message: format!(
+                                        "block {number} refers to itself (cyclic values are not supported)"
+                                    ),
+                                });
+                            }
+                            None => {
+                                return Err(ParseError {
+                                    offset: start,
+                                    message: format!("no block {number} precedes this reference"),
+                                });
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(ParseError {
+                            offset: start,
+                            message: "expected `#double`, `#double_array`, or `#shared`"
+                                .to_string(),
+                        });
+                    }
+                }
+            }
+            Some(_) => {
+                let number_start = parser.pos;
+                let word = parser.word();
+                if let Ok(n) = word.parse::<isize>() {
+                    Value::int(n)
+                } else if let (true, Ok(f)) = (
+                    word.bytes().any(|b| b.is_ascii_digit()),
+                    word.parse::<f64>(),
+                ) {
+                    push_block(&mut blocks, alloc_double(alloc, f))
+                } else {
+                    return Err(ParseError {
+                        offset: number_start,
+                        message: format!("expected a value, but got {word:?}"),
+                    });
+                }
+            }
+        };
+        match open.last_mut() {
+            Some((_, _, fields)) => fields.push(value),
+            None => {
+                if parser.skip_whitespace() < text.len() {
+                    return Err(parser.error("unexpected text after value"));
+                }
+                return Ok(value);
+            }
+        }
+    }
+}
+
+fn push_block<'a>(blocks: &mut Vec<Option<Value<'a>>>, value: Value<'a>) -> Value<'a> {
+    blocks.push(Some(value));
+    value
+}
+
+fn alloc_double<A: Allocator>(alloc: &A, f: f64) -> Value<'_> {
+    let mut block = alloc.block_with_size_and_tag(1, block::DOUBLE_TAG);
+    alloc.set_field(&mut block, 0, unsafe {
+        Value::from_bits(f.to_bits() as usize)
+    });
+    block.build()
+}
+
+struct Parser<'t> {
+    text: &'t [u8],
+    pos: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            offset: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    /// Skip whitespace and comments, and return the new position.
+    fn skip_whitespace(&mut self) -> usize {
+        while let Some(byte) = self.peek() {
+            if byte == b';' {
+                while !matches!(self.peek(), None | Some(b'\n')) {
+                    self.pos += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        self.pos
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("expected `{}`", expected as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Return the run of non-delimiter characters at the current position.
+    fn word(&mut self) -> &'t str {
+        self.skip_whitespace();
+        let start = self.pos;
+        while let Some(byte) = self.peek() {
+            if byte.is_ascii_whitespace() || b"()[]\";#".contains(&byte) {
+                break;
+            }
+            self.pos += 1;
+        }
+        // The word is delimited by ASCII characters, so it is valid UTF-8 if
+        // the input is.
+        std::str::from_utf8(&self.text[start..self.pos]).unwrap()
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, ParseError> {
+        let start = self.skip_whitespace();
+        let word = self.word();
+        word.parse().map_err(|_| ParseError {
+            offset: start,
+            message: format!("expected a number, but got {word:?}"),
+        })
+    }
+
+    fn hex_word(&mut self) -> Result<usize, ParseError> {
+        let start = self.skip_whitespace();
+        let word = self.word();
+        let digits = word.strip_prefix("0x").ok_or_else(|| ParseError {
+            offset: start,
+            message: format!("expected a hexadecimal word, but got {word:?}"),
+        })?;
+        usize::from_str_radix(digits, 16).map_err(|_| ParseError {
+            offset: start,
+            message: format!("expected a hexadecimal word, but got {word:?}"),
+        })
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut bytes = vec![];
+        loop {
+            let byte = match self.peek() {
+                None => {
+                    return Err(ParseError {
+                        offset: start,
+                        message: "unterminated string".to_string(),
+                    });
+                }
+                Some(byte) => byte,
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => return Ok(bytes),
+                b'\\' => {
+                    let escape = self.peek();
+                    self.pos += 1;
+                    match escape {
+                        Some(b'"') => bytes.push(b'"'),
+                        Some(b'\\') => bytes.push(b'\\'),
+                        Some(b'n') => bytes.push(b'\n'),
+                        Some(b't') => bytes.push(b'\t'),
+                        Some(b'r') => bytes.push(b'\r'),
+                        Some(b'x') => {
+                            let digits = self.text.get(self.pos..self.pos + 2);
+                            let byte = digits
+                                .and_then(|digits| std::str::from_utf8(digits).ok())
+                                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                                .ok_or_else(|| self.error("expected two hexadecimal digits"))?;
+                            bytes.push(byte);
+                            self.pos += 2;
+                        }
+                        _ => return Err(self.error("unknown escape sequence")),
+                    }
+                }
+                _ => bytes.push(byte),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Arena;
+    use crate::FromOcamlRep;
+
+    fn round_trip(text: &str) {
+        let arena = Arena::new();
+        let value = parse(&arena, text).unwrap();
+        assert_eq!(to_string(value), text);
+    }
+
+    #[test]
+    fn print_values() {
+        let arena = Arena::new();
+        let tuple = (vec![1isize, -2], Some("foo"), 2.5f64, "a\"b\n\u{ff}");
+        let value = arena.add(&tuple);
+        assert_eq!(
+            to_string(value),
+            r#"(tag 0 [(tag 0 [1 (tag 0 [-2 0])]) (tag 0 ["foo"]) #double 2.5 "a\"b\n\xc3\xbf"])"#
+        );
+        assert_eq!(to_string(Value::int(42)), "42");
+    }
+
+    #[test]
+    fn parse_values() {
+        let arena = Arena::new();
+        let value = parse(
+            &arena,
+            r#"
+            ; A tuple of a list, an option, a float, and a string
+            (tag 0 [
+                (tag 0 [1 (tag 0 [-2 0])])
+                (tag 0 ["foo"])
+                2.5
+                "a\"b\n\xc3\xbf"
+            ])
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            <(Vec<isize>, Option<String>, f64, String)>::from_ocamlrep(value),
+            Ok((
+                vec![1, -2],
+                Some("foo".to_string()),
+                2.5,
+                "a\"b\n\u{ff}".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip("(tag 0 [1 \"foo\" (tag 3 [#double 2.5])])");
+        round_trip("#double_array [1.0 -0.0 1e100]");
+        round_trip("#double NaN");
+        round_trip("#double -inf");
+        round_trip("(raw 251 [0x0 0xdeadbeef])");
+        round_trip("\"\\x00\\\\\"");
+    }
+
+    #[test]
+    fn empty_blocks() {
+        round_trip("(tag 0 [])");
+        round_trip("(tag 0 [(tag 5 []) #shared 1 #double_array []])");
+        let arena = Arena::new();
+        let empty = crate::OcamlArray::<isize>::new();
+        assert_eq!(to_string(arena.add(&empty)), "(tag 0 [])");
+        let value = parse(&arena, "(tag 0 [])").unwrap();
+        assert_eq!(
+            crate::OcamlArray::<isize>::from_ocamlrep(value).map(|a| a.into_vec()),
+            Ok(vec![])
+        );
+        assert_eq!(value, parse(&arena, "(tag 0 [])").unwrap());
+    }
+
+    #[test]
+    fn sharing() {
+        let arena = Arena::new();
+        let text = r#"(tag 0 [(tag 0 ["x" #shared 2]) #shared 1 #shared 2])"#;
+        let value = parse(&arena, text).unwrap();
+        let inner = value.field(0).unwrap();
+        assert_eq!(inner.field(0), inner.field(1));
+        assert_eq!(value.field(1), Some(inner));
+        assert_eq!(value.field(2), inner.field(0));
+        assert_eq!(to_string(value), text);
+    }
+
+    #[test]
+    fn print_cyclic_value() {
+        let arena = Arena::new();
+        let mut block = arena.block_with_size(2);
+        arena.set_field(&mut block, 0, Value::int(1));
+        let address = block.address();
+        arena.set_field(&mut block, 1, unsafe { Value::from_bits(address) });
+        let value = block.build();
+        assert_eq!(to_string(value), "(tag 0 [1 #shared 0])");
+    }
+
+    #[test]
+    fn errors() {
+        let arena = Arena::new();
+        let error = |text: &str| parse(&arena, text).unwrap_err();
+        assert_eq!(
+            error("(tag 0 [1 #shared 0])").message,
+            "block 0 refers to itself (cyclic values are not supported)"
+        );
+        assert_eq!(
+            error("#shared 0").message,
+            "no block 0 precedes this reference"
+        );
+        assert_eq!(error("(tag 0 [1").message, "unterminated block");
+        assert_eq!(error("(tag 0 [1]").offset, 10);
+        assert_eq!(error("1 2").message, "unexpected text after value");
+        assert_eq!(error("\"abc").message, "unterminated string");
+        assert_eq!(error("foo").message, "expected a value, but got \"foo\"");
+        assert_eq!(
+            error("(tag 252 [1])").message,
+            "use `raw` for blocks with this tag"
+        );
+        assert_eq!(error("(tag 256 [1])").offset, 5);
+        assert_eq!(
+            error("(raw 0 [0x1])").message,
+            "use `tag` for blocks with this tag"
+        );
+        for text in ["(tag 247 [1 2])", "(raw 249 [0x1])", "(raw 255 [0x1])"] {
+            assert_eq!(
+                error(text).message,
+                "closures and custom blocks are not supported"
+            );
+        }
+        assert!(
+            error("(raw 252 [0xff00000000000000])")
+                .message
+                .starts_with("invalid raw block: Invalid padding")
+        );
+        assert!(
+            error("(raw 253 [0x0 0x0])")
+                .message
+                .starts_with("invalid raw block: Invalid size 2")
+        );
+    }
+
+    #[test]
+    fn print_infix_pointers() {
+        let arena = Arena::new();
+        let mut closure = arena.block_with_size_and_tag(5, block::CLOSURE_TAG);
+        arena.set_field(&mut closure, 0, Value::int(0));
+        arena.set_field(&mut closure, 1, Value::int(5));
+        let infix_header = block::Header::new(3, block::INFIX_TAG).to_bits();
+        arena.set_field(&mut closure, 2, unsafe { Value::from_bits(infix_header) });
+        arena.set_field(&mut closure, 3, Value::int(0));
+        arena.set_field(&mut closure, 4, Value::int(5));
+        let closure = closure.build();
+        let infix =
+            unsafe { Value::from_bits(closure.to_bits() + 3 * std::mem::size_of::<usize>()) };
+        let raw = format!("(raw 247 [0x1 0xb {infix_header:#x} 0x1 0xb])");
+        assert_eq!(to_string(infix), format!("#infix 3 {raw}"));
+        let mut tuple = arena.block_with_size(3);
+        arena.set_field(&mut tuple, 0, infix);
+        arena.set_field(&mut tuple, 1, closure);
+        arena.set_field(&mut tuple, 2, infix);
+        assert_eq!(
+            to_string(tuple.build()),
+            format!("(tag 0 [#infix 3 {raw} #shared 1 #infix 3 #shared 1])")
+        );
+    }
+
+    #[test]
+    fn long_list() {
+        let arena = Arena::new();
+        let list: Vec<isize> = (0..100_000).collect();
+        let text = to_string(arena.add(&list));
+        let value = parse(&arena, &text).unwrap();
+        assert_eq!(<Vec<isize>>::from_ocamlrep(value), Ok(list));
+    }
+}
@@ -0,0 +1,349 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::DOUBLE_WOSIZE;
+use crate::MAX_WOSIZE;
+use crate::Value;
+use crate::ValueStats;
+use crate::block;
+
+/// Returned by [`validate`](fn.validate.html) when a malformed block is
+/// reachable from the given value. Addresses are those of the first field of
+/// the offending block (i.e., the bits of a `Value` pointing to it).
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    NullPointer,
+    MisalignedPointer(usize),
+    BadBlockSize {
+        address: usize,
+        tag: u8,
+        size: usize,
+    },
+    BadStringPadding {
+        address: usize,
+        padding: u8,
+    },
+    BadClosureInfo {
+        address: usize,
+        start_env: usize,
+    },
+    BadCustomOperations {
+        address: usize,
+        ops: usize,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ValidationError::*;
+        match self {
+            NullPointer => write!(f, "Null pointer"),
+            MisalignedPointer(address) => write!(f, "Misaligned pointer {address:#x}"),
+            BadBlockSize { address, tag, size } => write!(
+                f,
+                "Invalid size {size} for block with tag {tag} at {address:#x}"
+            ),
+            BadStringPadding { address, padding } => write!(
+                f,
+                "Invalid padding (final byte {padding}) for string at {address:#x}"
+            ),
+            BadClosureInfo { address, start_env } => write!(
+                f,
+                "Invalid environment start {start_env} for closure at {address:#x}"
+            ),
+            BadCustomOperations { address, ops } => write!(
+                f,
+                "Invalid custom operations pointer {ops:#x} for custom block at {address:#x}"
+            ),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Check that every block reachable from the given value is well-formed:
+///
+/// - pointers are non-null and word-aligned
+/// - block sizes do not exceed `MAX_WOSIZE`, and are appropriate for their
+///   tags (e.g., `DOUBLE_TAG` blocks contain exactly one double, and
+///   `DOUBLE_ARRAY_TAG` sizes are a multiple of `DOUBLE_WOSIZE`)
+/// - the padding of `STRING_TAG` blocks is consistent with the layout produced
+///   by `Allocator::byte_string_with_len`
+/// - closure info fields and custom operations pointers are plausible
+///
+/// Malformed blocks produced by an `Allocator` or a hand-written `ToOcamlRep`
+/// implementation typically cause the OCaml GC to crash long after the value
+/// was handed to OCaml. Checking at the FFI boundary (e.g., with
+/// `debug_assert!(ocamlrep::validate(value).is_ok())`) can surface these bugs
+/// closer to their cause.
+///
+/// On success, returns the same statistics as
+/// [`Value::stats`](struct.Value.html#method.stats). The value is traversed
+/// without recursion, and cycles are detected (and counted in
+/// `ValueStats::cycles`) rather than followed.
+///
+/// Naked pointers cannot be distinguished from pointers to blocks in general,
+/// so this function assumes that each pointer refers to readable memory
+/// preceded by a header word. A wild pointer may cause a segfault rather than
+/// a `ValidationError`.
+pub fn validate(value: Value<'_>) -> Result<ValueStats, ValidationError> {
+    value.walk(check_block)
+}
+
+/// Validate the header and contents of the block pointed to by `value`.
+fn check_block(value: Value<'_>) -> Result<(), ValidationError> {
+    let address = value.to_bits();
+    if address == 0 {
+        return Err(ValidationError::NullPointer);
+    }
+    if !address.is_multiple_of(std::mem::size_of::<Value<'_>>()) {
+        return Err(ValidationError::MisalignedPointer(address));
+    }
+    let block = value.as_block().unwrap();
+    let size = block.size();
+    let tag = block.tag();
+    let bad_size = || Err(ValidationError::BadBlockSize { address, tag, size });
+    if tag == block::INFIX_TAG {
+        // Infix blocks are contained within a closure block, and the size in
+        // their header is their offset (in words) within the closure. The
+        // closure itself is checked when the traversal moves on to it.
+        let offset = size.wrapping_mul(std::mem::size_of::<Value<'_>>());
+        if size == 0 || size > MAX_WOSIZE || offset >= address {
+            return bad_size();
+        }
+        let closure = unsafe { Value::from_bits(address - offset) }
+            .as_block()
+            .unwrap();
+        if closure.tag() != block::CLOSURE_TAG || closure.size() <= size {
+            return bad_size();
+        }
+        return Ok(());
+    }
+    if size > MAX_WOSIZE {
+        return bad_size();
+    }
+    match tag {
+        block::STRING_TAG if size == 0 => bad_size(),
+        block::STRING_TAG => {
+            let bytes = block.as_int_slice();
+            let bytes = unsafe {
+                std::slice::from_raw_parts(
+                    bytes.as_ptr() as *const u8,
+                    std::mem::size_of_val(bytes),
+                )
+            };
+            let (&padding, rest) = bytes.split_last().unwrap();
+            // The final byte holds the number of padding bytes preceding it,
+            // and the padding bytes must be zero (so that the string is also
+            // null-terminated).
+            if padding as usize >= std::mem::size_of::<Value<'_>>()
+                || rest[rest.len() - padding as usize..]
+                    .iter()
+                    .any(|&b| b != 0)
+            {
+                return Err(ValidationError::BadStringPadding { address, padding });
+            }
+            Ok(())
+        }
+        block::DOUBLE_TAG if size != DOUBLE_WOSIZE => bad_size(),
+        block::DOUBLE_ARRAY_TAG if !size.is_multiple_of(DOUBLE_WOSIZE) => bad_size(),
+        block::CUSTOM_TAG if size == 0 => bad_size(),
+        block::CUSTOM_TAG => {
+            let ops = block[0].to_bits();
+            if ops == 0 || !ops.is_multiple_of(std::mem::align_of::<block::CustomOperations>()) {
+                return Err(ValidationError::BadCustomOperations { address, ops });
+            }
+            Ok(())
+        }
+        block::CLOSURE_TAG => {
+            let start_env = match block.closure_start_env() {
+                Some(start_env) => start_env,
+                None => return bad_size(),
+            };
+            if start_env < 2 || start_env > size {
+                return Err(ValidationError::BadClosureInfo { address, start_env });
+            }
+            Ok(())
+        }
+        // Objects contain their method table and object ID, followed by
+        // instance variables.
+        block::OBJECT_TAG if size < 2 || !block[1].is_int() => bad_size(),
+        block::FORWARD_TAG if size == 0 => bad_size(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Allocator;
+    use crate::Arena;
+
+    #[test]
+    fn ints() {
+        assert_eq!(validate(Value::int(42)), Ok(ValueStats::default()));
+    }
+
+    #[test]
+    fn strings() {
+        let arena = Arena::new();
+        for len in 0..20 {
+            let s = "x".repeat(len);
+            let stats = validate(arena.add(s.as_str())).unwrap();
+            assert_eq!(stats.blocks, 1);
+            assert_eq!(
+                stats.words,
+                1 + (len + 1).div_ceil(std::mem::size_of::<usize>())
+            );
+        }
+    }
+
+    #[test]
+    fn bad_string_padding() {
+        let arena = Arena::new();
+        let value = arena.add("abc");
+        let address = value.to_bits();
+        unsafe { *(address as *mut u8).add(7) = 5 };
+        assert_eq!(
+            validate(value),
+            Err(ValidationError::BadStringPadding {
+                address,
+                padding: 5
+            })
+        );
+        unsafe { *(address as *mut u8).add(7) = 8 };
+        assert_eq!(
+            validate(value),
+            Err(ValidationError::BadStringPadding {
+                address,
+                padding: 8
+            })
+        );
+    }
+
+    #[test]
+    fn nested_values() {
+        let arena = Arena::new();
+        let tuple = (vec![1.0f64, 2.0], Some("a"), Box::new(()));
+        let value = arena.add(&tuple);
+        // One tuple, two list cells, two floats, one option, and one string
+        let stats = validate(value).unwrap();
+        assert_eq!(stats.blocks, 7);
+        assert_eq!(stats.words, 4 + 3 + 3 + 2 + 2 + 2 + 2);
+        assert_eq!((stats.shared, stats.cycles), (0, 0));
+        assert_eq!(stats, value.stats());
+    }
+
+    #[test]
+    fn sharing() {
+        let arena = Arena::new();
+        let s = arena.add("shared");
+        let mut block = arena.block_with_size(3);
+        for i in 0..3 {
+            arena.set_field(&mut block, i, s);
+        }
+        let stats = validate(block.build()).unwrap();
+        assert_eq!(stats.blocks, 2);
+        assert_eq!(stats.shared, 2);
+        assert_eq!(stats.cycles, 0);
+    }
+
+    #[test]
+    fn cycles() {
+        let arena = Arena::new();
+        let mut block = arena.block_with_size(2);
+        let value = unsafe { Value::from_bits(block.address()) };
+        arena.set_field(&mut block, 0, Value::int(1));
+        arena.set_field(&mut block, 1, value);
+        let stats = validate(block.build()).unwrap();
+        assert_eq!(stats.blocks, 1);
+        assert_eq!(stats.cycles, 1);
+    }
+
+    /// A closure of two mutually recursive functions, whose second function is
+    /// represented by an infix block within the closure.
+    fn closure_with_infix(arena: &Arena) -> (Value<'_>, Value<'_>) {
+        let env = arena.add("env");
+        let mut closure = arena.block_with_size_and_tag(6, block::CLOSURE_TAG);
+        // Code pointers (which are never scanned) and closure info fields
+        // recording that the environment starts at field 5.
+        arena.set_field(&mut closure, 0, Value::int(0));
+        arena.set_field(&mut closure, 1, Value::int(5));
+        let infix_header = block::Header::new(3, block::INFIX_TAG);
+        arena.set_field(&mut closure, 2, unsafe {
+            Value::from_bits(infix_header.to_bits())
+        });
+        arena.set_field(&mut closure, 3, Value::int(0));
+        arena.set_field(&mut closure, 4, Value::int(5));
+        arena.set_field(&mut closure, 5, env);
+        let closure = closure.build();
+        let infix =
+            unsafe { Value::from_bits(closure.to_bits() + 3 * std::mem::size_of::<usize>()) };
+        (closure, infix)
+    }
+
+    #[test]
+    fn infix_blocks() {
+        let arena = Arena::new();
+        let (closure, infix) = closure_with_infix(&arena);
+        let stats = validate(infix).unwrap();
+        assert_eq!(stats, infix.stats());
+        assert_eq!(stats, validate(closure).unwrap());
+        assert_eq!((stats.blocks, stats.words), (2, 7 + 2));
+
+        let mut pair = arena.block_with_size(2);
+        arena.set_field(&mut pair, 0, closure);
+        arena.set_field(&mut pair, 1, infix);
+        let stats = validate(pair.build()).unwrap();
+        assert_eq!((stats.blocks, stats.shared), (3, 1));
+    }
+
+    #[test]
+    fn bad_pointers() {
+        let arena = Arena::new();
+        let mut block = arena.block_with_size(1);
+        arena.set_field(&mut block, 0, unsafe { Value::from_bits(0) });
+        assert_eq!(validate(block.build()), Err(ValidationError::NullPointer));
+        let s = arena.add("abc");
+        let mut block = arena.block_with_size(1);
+        arena.set_field(&mut block, 0, unsafe { Value::from_bits(s.to_bits() + 2) });
+        assert_eq!(
+            validate(block.build()),
+            Err(ValidationError::MisalignedPointer(s.to_bits() + 2))
+        );
+    }
+
+    #[test]
+    fn bad_sizes() {
+        let arena = Arena::new();
+        let value = arena.block_with_size_and_tag(2, block::DOUBLE_TAG).build();
+        assert_eq!(
+            validate(value),
+            Err(ValidationError::BadBlockSize {
+                address: value.to_bits(),
+                tag: block::DOUBLE_TAG,
+                size: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn bad_custom_operations() {
+        let arena = Arena::new();
+        let mut block = arena.block_with_size_and_tag(1, block::CUSTOM_TAG);
+        arena.set_field(&mut block, 0, unsafe { Value::from_bits(0) });
+        let value = block.build();
+        assert_eq!(
+            validate(value),
+            Err(ValidationError::BadCustomOperations {
+                address: value.to_bits(),
+                ops: 0,
+            })
+        );
+    }
+}
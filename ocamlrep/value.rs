@@ -4,6 +4,7 @@
 // LICENSE file in the root directory of this source tree.
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Debug;
@@ -191,6 +192,128 @@ impl<'a> Value<'a> {
     pub fn clone_with_allocator(self, alloc: &impl Allocator) -> Value<'_> {
         self.clone_with(alloc, &mut HashMap::new())
     }
+
+    /// The number of words (including headers) occupied by the blocks
+    /// reachable from this `Value`, like OCaml's `Obj.reachable_words`. Blocks
+    /// which are reachable by multiple paths are counted once.
+    pub fn reachable_words(self) -> usize {
+        self.stats().words
+    }
+
+    /// Compute statistics about the blocks reachable from this `Value`. Blocks
+    /// which are reachable by multiple paths are counted once (as in
+    /// `Value::clone_with_allocator`, blocks are identified by address).
+    pub fn stats(self) -> ValueStats {
+        let Ok(stats) = self.walk(|_| Ok::<(), std::convert::Infallible>(()));
+        stats
+    }
+
+    /// Traverse the blocks reachable from this `Value` without recursion,
+    /// computing the statistics returned by `Value::stats` (and
+    /// `crate::validate`).
+    ///
+    /// `check` is invoked with each pointer before the block it points to is
+    /// inspected, and may abort the traversal. A pointer to an infix block is
+    /// checked and then replaced with a pointer to its enclosing closure, which
+    /// is checked in turn.
+    pub(crate) fn walk<E>(
+        self,
+        mut check: impl FnMut(Value<'a>) -> Result<(), E>,
+    ) -> Result<ValueStats, E> {
+        let mut stats = ValueStats::default();
+        // Maps the address of each visited block to `true` if we have finished
+        // visiting its fields, or `false` if it is on the stack.
+        let mut visited = HashMap::new();
+        let mut stack: Vec<(usize, std::slice::Iter<'a, Value<'a>>)> = vec![];
+        let mut next = Some(self);
+        loop {
+            let mut value = match next.take() {
+                Some(value) => value,
+                None => match stack.last_mut() {
+                    None => return Ok(stats),
+                    Some((address, fields)) => match fields.next() {
+                        Some(&field) => field,
+                        None => {
+                            visited.insert(*address, true);
+                            stack.pop();
+                            continue;
+                        }
+                    },
+                },
+            };
+            if value.is_int() {
+                continue;
+            }
+            check(value)?;
+            let mut block = value.as_block().unwrap();
+            if block.tag() == block::INFIX_TAG {
+                // Count the enclosing closure instead.
+                let offset = block.size() * std::mem::size_of::<Value<'_>>();
+                value = Value(value.0 - offset, PhantomData);
+                check(value)?;
+                block = value.as_block().unwrap();
+            }
+            match visited.get(&value.0) {
+                Some(true) => {
+                    stats.shared += 1;
+                    continue;
+                }
+                Some(false) => {
+                    stats.cycles += 1;
+                    continue;
+                }
+                None => {}
+            }
+            visited.insert(value.0, false);
+            let size = block.size();
+            stats.blocks += 1;
+            stats.words += size + 1;
+            *stats.blocks_by_tag.entry(block.tag()).or_insert(0) += 1;
+            let fields = match block.tag() {
+                block::STRING_TAG => {
+                    stats.string_bytes += value.as_byte_string().unwrap().len();
+                    &[]
+                }
+                block::DOUBLE_TAG | block::DOUBLE_ARRAY_TAG => {
+                    stats.float_words += size;
+                    &[]
+                }
+                block::CLOSURE_TAG => {
+                    let fields = block.as_values().unwrap();
+                    let start_env = block
+                        .closure_start_env()
+                        .map_or(fields.len(), |start_env| start_env.min(fields.len()));
+                    &fields[start_env..]
+                }
+                _ => block.as_values().unwrap_or(&[]),
+            };
+            stack.push((value.0, fields.iter()));
+        }
+    }
+}
+
+/// Statistics about the blocks reachable from a value, returned by
+/// [`Value::stats`](struct.Value.html#method.stats).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValueStats {
+    /// The number of distinct blocks reachable from the value.
+    pub blocks: usize,
+    /// The number of words occupied by those blocks, including headers.
+    pub words: usize,
+    /// The number of distinct blocks with each tag.
+    pub blocks_by_tag: BTreeMap<u8, usize>,
+    /// The total length of all strings (excluding padding).
+    pub string_bytes: usize,
+    /// The number of words occupied by floats and float arrays (excluding
+    /// headers).
+    pub float_words: usize,
+    /// The number of pointers to blocks which were already counted (not
+    /// counting pointers which form a cycle).
+    pub shared: usize,
+    /// The number of pointers to blocks which contain (directly or indirectly)
+    /// the pointer itself. Cyclic values can be constructed in OCaml (e.g.,
+    /// with `let rec`), but not by `ToOcamlRep` implementations.
+    pub cycles: usize,
 }
 
 impl Debug for Value<'_> {
@@ -201,3 +324,58 @@ impl Debug for Value<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Arena;
+
+    #[test]
+    fn reachable_words() {
+        let arena = Arena::new();
+        assert_eq!(Value::int(5).reachable_words(), 0);
+        // Two list cells, each with a header and two fields.
+        assert_eq!(arena.add(&vec![1, 2]).reachable_words(), 6);
+        // A one-word string block and its header.
+        assert_eq!(arena.add("abc").reachable_words(), 2);
+    }
+
+    #[test]
+    fn shared_blocks_counted_once() {
+        let arena = Arena::new();
+        let s = arena.add("a string of 24 bytes....");
+        let mut block = arena.block_with_size(2);
+        arena.set_field(&mut block, 0, s);
+        arena.set_field(&mut block, 1, s);
+        let stats = block.build().stats();
+        assert_eq!(
+            stats,
+            ValueStats {
+                blocks: 2,
+                words: 3 + 5,
+                blocks_by_tag: [(0, 1), (block::STRING_TAG, 1)].into_iter().collect(),
+                string_bytes: 24,
+                float_words: 0,
+                shared: 1,
+                cycles: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let arena = Arena::new();
+        let floats = (1.0f64, 2.0f64);
+        let stats = arena.add(&floats).stats();
+        assert_eq!(stats.blocks, 3);
+        assert_eq!(stats.float_words, 2 * crate::DOUBLE_WOSIZE);
+        assert_eq!(stats.blocks_by_tag[&block::DOUBLE_TAG], 2);
+    }
+
+    #[test]
+    fn long_lists() {
+        let arena = Arena::new();
+        let list: Vec<isize> = (0..100_000).collect();
+        assert_eq!(arena.add(&list).reachable_words(), 300_000);
+    }
+}
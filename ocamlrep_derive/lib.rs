@@ -31,6 +31,19 @@ use synstructure::decl_derive;
 decl_derive!([ToOcamlRep, attributes(rust_to_ocaml, ocamlrep)] => derive_to_ocamlrep);
 decl_derive!([FromOcamlRep, attributes(rust_to_ocaml, ocamlrep)] => derive_from_ocamlrep);
 decl_derive!([FromOcamlRepIn, attributes(rust_to_ocaml, ocamlrep)] => derive_from_ocamlrep_in);
+decl_derive!([FromOcamlRepRef, attributes(rust_to_ocaml, ocamlrep)] => derive_from_ocamlrep_ref);
+
+/// Which of the `FromOcamlRep*` traits a derived conversion implements. This
+/// determines how the fields of the type are converted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FromKind {
+    /// `FromOcamlRep`
+    Owned,
+    /// `FromOcamlRepIn`
+    In,
+    /// `FromOcamlRepRef`
+    Ref,
+}
 
 fn workaround_non_local_def(impl_block: TokenStream) -> TokenStream {
     // We need to upgrade synstructure to remove this warning, but doing so will also require upgrading
@@ -42,6 +55,10 @@ fn workaround_non_local_def(impl_block: TokenStream) -> TokenStream {
 }
 
 fn derive_to_ocamlrep(mut s: synstructure::Structure<'_>) -> TokenStream {
+    if let Err(err) = check_field_attrs(&s) {
+        return err.to_compile_error();
+    }
+
     // remove #[ocamlrep(skip)]
     for variant in s.variants_mut() {
         variant.filter(|bi| !matches!(has_ocamlrep_skip_attr(&bi.ast().attrs), Ok(true)));
@@ -69,6 +86,9 @@ fn derive_to_ocamlrep(mut s: synstructure::Structure<'_>) -> TokenStream {
 }
 
 fn derive_from_ocamlrep(mut s: synstructure::Structure<'_>) -> TokenStream {
+    if let Err(err) = check_field_attrs(&s) {
+        return err.to_compile_error();
+    }
     s.add_bounds(synstructure::AddBounds::Generics);
 
     let from_body = from_ocamlrep_body(&mut s);
@@ -83,6 +103,9 @@ fn derive_from_ocamlrep(mut s: synstructure::Structure<'_>) -> TokenStream {
 }
 
 fn derive_from_ocamlrep_in(mut s: synstructure::Structure<'_>) -> TokenStream {
+    if let Err(err) = check_field_attrs(&s) {
+        return err.to_compile_error();
+    }
     s.add_bounds(synstructure::AddBounds::Generics);
 
     if s.ast().generics.lifetimes().next().is_none() {
@@ -142,6 +165,44 @@ fn derive_from_ocamlrep_in(mut s: synstructure::Structure<'_>) -> TokenStream {
     }))
 }
 
+fn derive_from_ocamlrep_ref(mut s: synstructure::Structure<'_>) -> TokenStream {
+    if let Err(err) = check_field_attrs(&s) {
+        return err.to_compile_error();
+    }
+    // As in `derive_from_ocamlrep_in`, constrain `'__ocamlrep_derive_value` to
+    // be equal to any declared lifetimes, so that fields may borrow from the
+    // value for as long as the type's lifetime parameters permit.
+    s.add_bounds(synstructure::AddBounds::None);
+    let lifetimes = s.ast().generics.lifetimes();
+    let lifetimes: TokenStream = lifetimes
+        .map(|l| {
+            quote! {
+                '__ocamlrep_derive_value : #l,
+                #l : '__ocamlrep_derive_value,
+            }
+        })
+        .collect();
+    let tparams = s.ast().generics.type_params();
+    let tparams_implement_from_ocamlrep_ref: TokenStream = tparams
+        .map(|t| quote!(#t : ::ocamlrep::FromOcamlRepRef<'__ocamlrep_derive_value>,))
+        .collect();
+
+    let from_ref_body = from_ocamlrep_ref_body(&mut s);
+    workaround_non_local_def(s.gen_impl(quote! {
+        gen impl<'__ocamlrep_derive_value> ::ocamlrep::FromOcamlRepRef<'__ocamlrep_derive_value> for @Self
+        where
+            #tparams_implement_from_ocamlrep_ref #lifetimes
+        {
+            fn from_ocamlrep_ref(
+                value: ::ocamlrep::Value<'__ocamlrep_derive_value>,
+            ) -> ::std::result::Result<Self, ::ocamlrep::FromError> {
+                use ::ocamlrep::FromOcamlRepRef;
+                #from_ref_body
+            }
+        }
+    }))
+}
+
 fn to_ocamlrep_body(s: &synstructure::Structure<'_>) -> TokenStream {
     match &s.ast().data {
         syn::Data::Struct(struct_data) => struct_to_ocamlrep(s, struct_data),
@@ -151,17 +212,21 @@ fn to_ocamlrep_body(s: &synstructure::Structure<'_>) -> TokenStream {
 }
 
 fn from_ocamlrep_body(s: &mut synstructure::Structure<'_>) -> TokenStream {
-    match &s.ast().data {
-        syn::Data::Struct(struct_data) => struct_from_ocamlrep(s, struct_data, false),
-        syn::Data::Enum(_) => enum_from_ocamlrep(collect_enum_variants(s), false),
-        syn::Data::Union(_) => panic!("untagged unions not supported"),
-    }
+    from_body(s, FromKind::Owned)
 }
 
 fn from_ocamlrep_in_body(s: &mut synstructure::Structure<'_>) -> TokenStream {
+    from_body(s, FromKind::In)
+}
+
+fn from_ocamlrep_ref_body(s: &mut synstructure::Structure<'_>) -> TokenStream {
+    from_body(s, FromKind::Ref)
+}
+
+fn from_body(s: &mut synstructure::Structure<'_>, from_kind: FromKind) -> TokenStream {
     match &s.ast().data {
-        syn::Data::Struct(struct_data) => struct_from_ocamlrep(s, struct_data, true),
-        syn::Data::Enum(_) => enum_from_ocamlrep(collect_enum_variants(s), true),
+        syn::Data::Struct(struct_data) => struct_from_ocamlrep(s, struct_data, from_kind),
+        syn::Data::Enum(_) => enum_from_ocamlrep(collect_enum_variants(s), from_kind),
         syn::Data::Union(_) => panic!("untagged unions not supported"),
     }
 }
@@ -178,7 +243,7 @@ fn struct_to_ocamlrep(
         syn::Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => {
             // For the newtype pattern (a tuple struct with a single field),
             // don't allocate a block--just use the inner value directly.
-            s.each(|bi| quote! { arena.add(#bi) })
+            s.each(field_to_ocamlrep)
         }
         syn::Fields::Named(_) | syn::Fields::Unnamed(_) => {
             // Otherwise, we have a record-like struct or a tuple struct. Both
@@ -188,15 +253,42 @@ fn struct_to_ocamlrep(
     }
 }
 
-/// Returns true if the attributes contain an `#[ocamlrep(skip)]`
-fn has_ocamlrep_skip_attr(attrs: &[Attribute]) -> Result<bool> {
-    let mut skip = false;
+/// The options given in `#[ocamlrep(...)]` attributes on a field.
+#[derive(Default)]
+struct FieldAttrs {
+    /// `#[ocamlrep(skip)]`: the field is not converted (and is initialized with
+    /// `Default::default()` when converting from OCaml).
+    skip: bool,
+    /// `#[ocamlrep(array)]`: the field (a sequence type like `Vec<T>`,
+    /// `Box<[T]>`, or `&'a [T]`) is converted to an OCaml array rather than a
+    /// list.
+    array: bool,
+    /// `#[ocamlrep(float_array)]`: the field (a sequence of `f64`) is converted
+    /// to an OCaml `float array`, which stores its elements unboxed.
+    float_array: bool,
+    /// `#[ocamlrep(assoc_list)]`: the field (a map type like `IndexMap<K, V>`
+    /// or `BTreeMap<K, V>`) is converted to an OCaml association list in
+    /// iteration order, rather than to an OCaml `Map`.
+    assoc_list: bool,
+}
+
+fn parse_ocamlrep_attrs(attrs: &[Attribute]) -> Result<FieldAttrs> {
+    let mut field_attrs = FieldAttrs::default();
 
     for attr in attrs {
         if attr.path().is_ident("ocamlrep") {
             attr.parse_nested_meta(|meta| {
                 if meta.path.is_ident("skip") {
-                    skip = true;
+                    field_attrs.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("array") {
+                    field_attrs.array = true;
+                    Ok(())
+                } else if meta.path.is_ident("float_array") {
+                    field_attrs.float_array = true;
+                    Ok(())
+                } else if meta.path.is_ident("assoc_list") {
+                    field_attrs.assoc_list = true;
                     Ok(())
                 } else {
                     Err(meta.error("unknown ocamlrep attribute"))
@@ -205,40 +297,112 @@ fn has_ocamlrep_skip_attr(attrs: &[Attribute]) -> Result<bool> {
         }
     }
 
-    Ok(skip)
+    Ok(field_attrs)
+}
+
+/// Check the `#[ocamlrep(...)]` attributes of every field, so that misuse is
+/// reported as an error on the field rather than producing a conversion which
+/// doesn't match the OCaml type.
+fn check_field_attrs(s: &synstructure::Structure<'_>) -> Result<()> {
+    for variant in s.variants() {
+        for field in variant.ast().fields.iter() {
+            let attrs = parse_ocamlrep_attrs(&field.attrs)?;
+            let representations = [attrs.array, attrs.float_array, attrs.assoc_list];
+            let representations = representations.iter().filter(|&&r| r).count();
+            if attrs.skip && representations > 0 {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "`skip` cannot be combined with `array`, `float_array`, or `assoc_list`",
+                ));
+            }
+            if representations > 1 {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "at most one of `array`, `float_array`, and `assoc_list` may be given",
+                ));
+            }
+            if attrs.array && is_float_sequence(&field.ty) {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "use `#[ocamlrep(float_array)]` for sequences of `f64`, \
+                     which OCaml represents as a `float array`",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if the attributes contain an `#[ocamlrep(skip)]`
+fn has_ocamlrep_skip_attr(attrs: &[Attribute]) -> Result<bool> {
+    Ok(parse_ocamlrep_attrs(attrs)?.skip)
+}
+
+/// If the field is marked `#[ocamlrep(array)]`, `#[ocamlrep(float_array)]`,
+/// or `#[ocamlrep(assoc_list)]`, return the ocamlrep type to convert the field
+/// from, and the method calls converting that type to the field's type.
+fn field_wrapper(field: &syn::Field) -> Option<(TokenStream, TokenStream)> {
+    let attrs = parse_ocamlrep_attrs(&field.attrs).ok()?;
+    if attrs.array || attrs.float_array {
+        Some(array_wrapper(&field.ty, attrs.float_array))
+    } else if attrs.assoc_list && !matches!(field.ty, syn::Type::Reference(_)) {
+        // References to sequences of pairs (e.g., `&'a [(K, V)]`) are already
+        // converted from association lists.
+        Some((
+            quote!(::ocamlrep::AssocList<_, _>),
+            quote!(.into_iter().collect()),
+        ))
+    } else {
+        None
+    }
 }
 
 fn struct_from_ocamlrep(
     s: &mut synstructure::Structure<'_>,
     struct_data: &syn::DataStruct,
-    from_in: bool,
+    from_kind: FromKind,
 ) -> TokenStream {
     let variant = &mut s.variants_mut()[0];
     match struct_data.fields {
         syn::Fields::Unit => {
             let constructor = variant.construct(|_, _| quote!(unreachable!()));
-            quote! { <()>::from_ocamlrep(value)?; Ok(#constructor) }
+            if from_kind == FromKind::Ref {
+                quote! { <()>::from_ocamlrep_ref(value)?; Ok(#constructor) }
+            } else {
+                quote! { <()>::from_ocamlrep(value)?; Ok(#constructor) }
+            }
         }
         syn::Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => {
             let constructor = variant.construct(|field, _| {
                 let ty = &field.ty;
-                if from_in {
-                    quote! { <#ty>::from_ocamlrep_in(value, alloc)? }
-                } else {
-                    quote! { <#ty>::from_ocamlrep(value)? }
+                if let Some((wrapper, finish)) = field_wrapper(field) {
+                    return match from_kind {
+                        FromKind::Owned => quote! { <#wrapper>::from_ocamlrep(value)? #finish },
+                        FromKind::In => {
+                            quote! { <#wrapper>::from_ocamlrep_in(value, alloc)? #finish }
+                        }
+                        FromKind::Ref => quote! { <#wrapper>::from_ocamlrep_ref(value)? #finish },
+                    };
+                }
+                match from_kind {
+                    FromKind::Owned => quote! { <#ty>::from_ocamlrep(value)? },
+                    FromKind::In => quote! { <#ty>::from_ocamlrep_in(value, alloc)? },
+                    FromKind::Ref => quote! { <#ty>::from_ocamlrep_ref(value)? },
                 }
             });
             quote! { Ok(#constructor) }
         }
         syn::Fields::Named(_) | syn::Fields::Unnamed(_) => {
             let mut binding = 0;
-            let constructor = variant.construct(|field, _| {
+            let variant = &*variant;
+            let constructor = variant.construct(|field, i| {
                 if let Ok(true) = has_ocamlrep_skip_attr(&field.attrs) {
                     quote!(::std::default::Default::default())
                 } else {
                     let idx = binding;
                     binding += 1;
-                    field_constructor(idx, from_in)
+                    let name = field_name(variant, field, i);
+                    field_constructor(idx, name, from_kind, Some(field))
                 }
             });
             quote! {
@@ -308,7 +472,7 @@ fn enum_to_ocamlrep(s: &synstructure::Structure<'_>, variants: EnumVariants<'_>)
     })
 }
 
-fn enum_from_ocamlrep(variants: EnumVariants<'_>, from_in: bool) -> TokenStream {
+fn enum_from_ocamlrep(variants: EnumVariants<'_>, from_kind: FromKind) -> TokenStream {
     let EnumVariants {
         nullary_variants,
         block_variants,
@@ -334,9 +498,14 @@ fn enum_from_ocamlrep(variants: EnumVariants<'_>, from_in: bool) -> TokenStream
         let (size, constructor) = match get_boxed_tuple_len(variant) {
             None => (
                 variant.bindings().len(),
-                variant.construct(|_, i| field_constructor(i, from_in)),
+                variant.construct(|field, i| {
+                    field_constructor(i, field_name(variant, field, i), from_kind, Some(field))
+                }),
+            ),
+            Some(len) => (
+                len,
+                boxed_tuple_variant_constructor(variant, len, from_kind),
             ),
-            Some(len) => (len, boxed_tuple_variant_constructor(variant, len, from_in)),
         };
         block_arms.extend(quote! { #tag => {
             ::ocamlrep::from::expect_block_size(block, #size)?;
@@ -378,8 +547,9 @@ fn allocate_block(variant: &VariantInfo<'_>, tag: u8) -> TokenStream {
     let size = variant.bindings().len();
     let mut fields = TokenStream::new();
     for (i, bi) in variant.bindings().iter().enumerate() {
+        let field = field_to_ocamlrep(bi);
         fields.extend(quote! {
-            arena.set_field(&mut block, #i, arena.add(#bi));
+            arena.set_field(&mut block, #i, #field);
         });
     }
     quote! {
@@ -389,6 +559,27 @@ fn allocate_block(variant: &VariantInfo<'_>, tag: u8) -> TokenStream {
     }
 }
 
+fn field_to_ocamlrep(bi: &BindingInfo<'_>) -> TokenStream {
+    let field = bi.ast();
+    let attrs = parse_ocamlrep_attrs(&field.attrs).unwrap_or_default();
+    if attrs.assoc_list {
+        return if let syn::Type::Reference(_) = field.ty {
+            quote! { ::ocamlrep::iter_to_ocaml_assoc_list(*#bi, arena) }
+        } else {
+            quote! { ::ocamlrep::iter_to_ocaml_assoc_list(#bi, arena) }
+        };
+    }
+    if !attrs.array && !attrs.float_array {
+        return quote! { arena.add(#bi) };
+    }
+    let slice = quote! { ::std::convert::AsRef::<[_]>::as_ref(#bi) };
+    if attrs.float_array {
+        quote! { arena.add(::ocamlrep::FloatArraySlice::new(#slice)) }
+    } else {
+        quote! { arena.add(::ocamlrep::OcamlArraySlice::new(#slice)) }
+    }
+}
+
 fn boxed_tuple_variant_to_block(bi: &BindingInfo<'_>, tag: u8, len: usize) -> TokenStream {
     let mut fields = TokenStream::new();
     for i in 0..len {
@@ -404,18 +595,78 @@ fn boxed_tuple_variant_to_block(bi: &BindingInfo<'_>, tag: u8, len: usize) -> To
     }
 }
 
-fn field_constructor(index: usize, from_in: bool) -> TokenStream {
-    if from_in {
-        quote! { ::ocamlrep::from::field_in(block, #index, alloc)? }
-    } else {
-        quote! { ::ocamlrep::from::field(block, #index)? }
+/// Convert the field at the given index of `block`. If `field` is given and is
+/// marked `#[ocamlrep(array)]`, `#[ocamlrep(float_array)]`, or
+/// `#[ocamlrep(assoc_list)]`, it is converted from an OCaml array or
+/// association list.
+fn field_constructor(
+    index: usize,
+    name: TokenStream,
+    from_kind: FromKind,
+    field: Option<&syn::Field>,
+) -> TokenStream {
+    if let Some((wrapper, finish)) = field.and_then(field_wrapper) {
+        return match from_kind {
+            FromKind::Owned => {
+                quote! { ::ocamlrep::from::named_field::<#wrapper>(block, #index, #name)? #finish }
+            }
+            FromKind::In => quote! {
+                ::ocamlrep::from::named_field_in::<#wrapper>(block, #index, #name, alloc)? #finish
+            },
+            FromKind::Ref => quote! {
+                ::ocamlrep::from::named_field_ref::<#wrapper>(block, #index, #name)? #finish
+            },
+        };
+    }
+    match from_kind {
+        FromKind::Owned => {
+            quote! { ::ocamlrep::from::named_field(block, #index, #name)? }
+        }
+        FromKind::In => {
+            quote! { ::ocamlrep::from::named_field_in(block, #index, #name, alloc)? }
+        }
+        FromKind::Ref => {
+            quote! { ::ocamlrep::from::named_field_ref(block, #index, #name)? }
+        }
+    }
+}
+
+/// Build an `ocamlrep::FieldName` describing the given field, so that
+/// conversion errors can report which Rust field failed to convert. Unnamed
+/// fields are named by their index.
+fn field_name(variant: &VariantInfo<'_>, field: &syn::Field, index: usize) -> TokenStream {
+    let field_name = match &field.ident {
+        Some(ident) => ident.to_string(),
+        None => index.to_string(),
+    };
+    field_name_with_label(variant, &field_name)
+}
+
+fn field_name_with_label(variant: &VariantInfo<'_>, field_name: &str) -> TokenStream {
+    let ident = variant.ast().ident;
+    let (type_name, variant_name) = match variant.prefix {
+        Some(prefix) => {
+            let variant_name = ident.to_string();
+            (
+                prefix.to_string(),
+                quote!(::std::option::Option::Some(#variant_name)),
+            )
+        }
+        None => (ident.to_string(), quote!(::std::option::Option::None)),
+    };
+    quote! {
+        ::ocamlrep::FieldName {
+            type_name: #type_name,
+            variant_name: #variant_name,
+            field_name: #field_name,
+        }
     }
 }
 
 fn boxed_tuple_variant_constructor(
     variant: &VariantInfo<'_>,
     len: usize,
-    from_in: bool,
+    from_kind: FromKind,
 ) -> TokenStream {
     let mut ident = TokenStream::new();
     if let Some(prefix) = variant.prefix {
@@ -426,19 +677,75 @@ fn boxed_tuple_variant_constructor(
 
     let mut fields = TokenStream::new();
     for idx in 0..len {
-        fields.extend(if from_in {
-            quote! { ::ocamlrep::from::field_in(block, #idx, alloc)?, }
-        } else {
-            quote! { ::ocamlrep::from::field(block, #idx)?, }
-        })
+        let name = field_name_with_label(variant, &idx.to_string());
+        let field = field_constructor(idx, name, from_kind, None);
+        fields.extend(quote! { #field, })
     }
-    if from_in {
+    if from_kind == FromKind::In {
         quote! { #ident(alloc.alloc((#fields))) }
     } else {
         quote! { #ident(::std::boxed::Box::new((#fields))) }
     }
 }
 
+/// For a field marked `#[ocamlrep(array)]` (or `#[ocamlrep(float_array)]`, if
+/// `is_float`), return the ocamlrep array type to convert the field from, and
+/// the method calls converting that type to the field's type. References
+/// (e.g., `&'a [T]`) are converted from slice types (e.g.,
+/// `&'a OcamlArraySlice<T>`), and other sequence types are collected from
+/// owned arrays.
+fn array_wrapper(ty: &syn::Type, is_float: bool) -> (TokenStream, TokenStream) {
+    if let syn::Type::Reference(_) = ty {
+        if is_float {
+            (quote!(&::ocamlrep::FloatArraySlice), quote!(.as_slice()))
+        } else {
+            (quote!(&::ocamlrep::OcamlArraySlice<_>), quote!(.as_slice()))
+        }
+    } else if is_float {
+        (
+            quote!(::ocamlrep::FloatArray),
+            quote!(.into_iter().collect()),
+        )
+    } else {
+        (
+            quote!(::ocamlrep::OcamlArray<_>),
+            quote!(.into_iter().collect()),
+        )
+    }
+}
+
+/// Returns true if the given type is spelled as a sequence of `f64` (like
+/// `Vec<f64>`, `Box<[f64]>`, or `&[f64]`), which OCaml represents as a `float
+/// array`. Used only to reject `#[ocamlrep(array)]` on such types: a type alias
+/// for `f64` can't be recognized here, which is why float arrays must be
+/// requested explicitly with `#[ocamlrep(float_array)]`.
+fn is_float_sequence(ty: &syn::Type) -> bool {
+    use syn::GenericArgument;
+    use syn::PathArguments;
+    use syn::Type;
+
+    fn is_f64(ty: &Type) -> bool {
+        match ty {
+            Type::Path(path) => path.qself.is_none() && path.path.is_ident("f64"),
+            Type::Slice(slice) => is_f64(&slice.elem),
+            _ => false,
+        }
+    }
+
+    match ty {
+        Type::Reference(reference) => is_f64(&reference.elem),
+        Type::Slice(slice) => is_f64(&slice.elem),
+        Type::Path(path) => match path.path.segments.last().map(|seg| &seg.arguments) {
+            Some(PathArguments::AngleBracketed(args)) => match args.args.first() {
+                Some(GenericArgument::Type(ty)) => is_f64(ty),
+                _ => false,
+            },
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
 fn get_boxed_tuple_len(variant: &VariantInfo<'_>) -> Option<usize> {
     use syn::Fields;
     use syn::GenericArgument;
@@ -554,10 +861,34 @@ mod tests {
                             use ::ocamlrep::FromOcamlRep;
                             let block = ::ocamlrep::from::expect_tuple(value, 3usize)?;
                             Ok(A {
-                                a: ::ocamlrep::from::field(block, 0usize)?,
-                                b: ::ocamlrep::from::field(block, 1usize)?,
+                                a: ::ocamlrep::from::named_field(
+                                    block,
+                                    0usize,
+                                    ::ocamlrep::FieldName {
+                                        type_name: "A",
+                                        variant_name: ::std::option::Option::None,
+                                        field_name: "a",
+                                    }
+                                )?,
+                                b: ::ocamlrep::from::named_field(
+                                    block,
+                                    1usize,
+                                    ::ocamlrep::FieldName {
+                                        type_name: "A",
+                                        variant_name: ::std::option::Option::None,
+                                        field_name: "b",
+                                    }
+                                )?,
                                 c: ::std::default::Default::default(),
-                                d: ::ocamlrep::from::field(block, 2usize)?,
+                                d: ::ocamlrep::from::named_field(
+                                    block,
+                                    2usize,
+                                    ::ocamlrep::FieldName {
+                                        type_name: "A",
+                                        variant_name: ::std::option::Option::None,
+                                        field_name: "d",
+                                    }
+                                )?,
                             })
                         }
                     }
@@ -566,4 +897,49 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn float_arrays_must_be_explicit() -> Result<()> {
+        let input = quote! {
+            struct A {
+                #[ocamlrep(array)]
+                a: Vec<f64>,
+            }
+        };
+        let output = derive_to_ocamlrep(Structure::new(&syn::parse2(input)?)).to_string();
+        assert!(output.contains("compile_error"));
+        assert!(output.contains("use `#[ocamlrep(float_array)]`"));
+
+        let input = quote! {
+            struct A {
+                #[ocamlrep(float_array)]
+                a: Vec<F>,
+            }
+        };
+        let output = derive_to_ocamlrep(Structure::new(&syn::parse2(input.clone())?));
+        assert!(output.to_string().contains("FloatArraySlice"));
+        let output = derive_from_ocamlrep(Structure::new(&syn::parse2(input)?)).to_string();
+        assert!(output.contains("FloatArray"));
+        Ok(())
+    }
+
+    #[test]
+    fn conflicting_field_attrs() -> Result<()> {
+        let error = |field: TokenStream| -> Result<String> {
+            let input = quote!(struct A { #field });
+            let output = derive_from_ocamlrep(Structure::new(&syn::parse2(input)?));
+            Ok(output.to_string())
+        };
+        let output = error(quote!(#[ocamlrep(array, assoc_list)] a: Vec<(K, V)>))?;
+        assert!(output.contains("at most one of"));
+        let output = error(quote!(#[ocamlrep(array)] #[ocamlrep(float_array)] a: Vec<F>))?;
+        assert!(output.contains("at most one of"));
+        let output = error(quote!(#[ocamlrep(skip, assoc_list)] a: BTreeMap<K, V>))?;
+        assert!(output.contains("`skip` cannot be combined"));
+        let output = error(quote!(#[ocamlrep(skip)] #[ocamlrep(array)] a: Vec<T>))?;
+        assert!(output.contains("`skip` cannot be combined"));
+        let output = error(quote!(#[ocamlrep(arrray)] a: Vec<T>))?;
+        assert!(output.contains("unknown ocamlrep attribute"));
+        Ok(())
+    }
 }
@@ -11,6 +11,7 @@ use ocamlrep::Allocator;
 use ocamlrep::BlockBuilder;
 pub use ocamlrep::FromOcamlRep;
 pub use ocamlrep::FromOcamlRepIn;
+pub use ocamlrep::FromOcamlRepRef;
 use ocamlrep::MemoizationCache;
 use ocamlrep::ToOcamlRep;
 pub use ocamlrep::Value;
@@ -95,9 +96,12 @@ impl Allocator for Pool {
         &'a self,
         ptr: usize,
         size: usize,
+        type_id: std::any::TypeId,
         f: impl FnOnce(&'a Self) -> Value<'a>,
     ) -> Value<'a> {
-        let bits = self.cache.memoized(ptr, size, || f(self).to_bits());
+        let bits = self
+            .cache
+            .memoized(ptr, size, type_id, || f(self).to_bits());
         // SAFETY: The only memoized values in the cache are those computed in
         // the closure on the previous line. Since f returns Value<'a>, any
         // cached bits must represent a valid Value<'a>,
@@ -326,6 +330,74 @@ macro_rules! ocaml_ffi_with_arena {
     };
 }
 
+#[macro_export]
+macro_rules! ocaml_ffi_ref_fn {
+    (fn $name:ident<$lifetime:lifetime>($($param:ident: $ty:ty),+ $(,)?) -> $ret:ty $code:block) => {
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn $name ($($param: usize,)*) -> usize {
+            $crate::catch_unwind(|| {
+                use $crate::FromOcamlRepRef;
+                fn inner<$lifetime>(_scope: &$lifetime (), $($param: usize,)*) -> $ret {
+                    $(let $param = unsafe {
+                        <$ty>::from_ocamlrep_ref($crate::Value::from_bits($param)).unwrap()
+                    };)*
+                    $code
+                }
+                // The arguments may only be borrowed for the lifetime of
+                // `scope`, which ends before we allocate the result on the
+                // OCaml heap (at which point the GC may move the arguments).
+                // Returning a value which borrows from the arguments is a
+                // compile error.
+                let result = {
+                    let scope = ();
+                    inner(&scope, $($param,)*)
+                };
+                $crate::to_ocaml(&result)
+            })
+        }
+    };
+
+    (fn $name:ident<$lifetime:lifetime>($($param:ident: $ty:ty),* $(,)?) $code:block) => {
+        $crate::ocaml_ffi_ref_fn! {
+            fn $name<$lifetime>($($param: $ty),*) -> () $code
+        }
+    };
+}
+
+/// Convenience macro for declaring OCaml FFI wrappers which borrow their
+/// arguments from the OCaml heap rather than copying them.
+///
+/// FFI functions declared with this macro must declare exactly one lifetime
+/// parameter and at least one value parameter. Each parameter will be
+/// converted from OCaml using `ocamlrep::FromOcamlRepRef`, so parameters of
+/// type `&'a str`, `&'a [u8]`, etc. point directly into the OCaml values:
+///
+/// ```
+/// ocaml_ffi_ref! {
+///     fn count_lines<'a>(text: &'a str) -> usize {
+///         text.lines().count()
+///     }
+/// }
+/// ```
+///
+/// This is sound because the OCaml GC cannot run (and therefore cannot move
+/// or free the arguments) while the function body is running, provided that
+/// the body does not allocate on the OCaml heap or call back into OCaml. The
+/// return value may not borrow from the arguments. It will be converted to
+/// OCaml using `ocamlrep::ToOcamlRep` and allocated on the OCaml heap using
+/// `ocamlpool` after the arguments are no longer in use.
+///
+/// Panics in the function body will be caught and converted to an OCaml
+/// exception of type `Failure`.
+#[macro_export]
+macro_rules! ocaml_ffi_ref {
+    ($(fn $name:ident<$lifetime:lifetime>($($param:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? $code:block)*) => {
+        $($crate::ocaml_ffi_ref_fn! {
+            fn $name<$lifetime>($($param: $ty),*) $(-> $ret)* $code
+        })*
+    };
+}
+
 #[macro_export]
 macro_rules! ocaml_ffi_arena_result_fn {
     (fn $name:ident<$lifetime:lifetime>($arena:ident: $arena_ty:ty, $($param:ident: $ty:ty),+ $(,)?) -> $ret:ty $code:block) => {